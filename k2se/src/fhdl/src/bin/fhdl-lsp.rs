@@ -0,0 +1,3 @@
+fn main() {
+  std::process::exit(fhdl::lsp_main());
+}
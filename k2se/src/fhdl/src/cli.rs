@@ -0,0 +1,247 @@
+//! The command line interface.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::err::{Cerr, CerrSpan, Warning};
+use crate::parse::printer::format_source;
+use crate::parse::project::Project;
+use crate::synth::builtins::collect_builtins;
+use crate::synth::lint::{lint_modules, lint_netlist, LintLevel, LintLevels};
+use crate::synth::synth::{synthesize, SingleMixedStrategy, SynthSettings};
+use crate::synth::transform::transform_project;
+use crate::synth::virt_signals::VIRTUAL_SIGNALS;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: fhdl [options] <file>
+       fhdl fmt [--check] <file>...
+
+Checks an FHDL program, along with every file it imports. `fmt` formats files in place instead,
+or with `--check`, lists the files that aren't formatted.
+
+options:
+  -I, --search-path <dir>  Also look for imported files in <dir>
+  -A, --allow <lint>       Don't report <lint>
+  -D, --deny <lint>        Report <lint> as an error
+  -m, --main <module>      Also synthesize <module>, with its ports as the main module's connections
+  --single-mixed <strategy>
+                           Synthesize ops between single and mixed nets clean (the default) or dirty
+  --color <when>           Colour the output: auto (the default), always or never
+  --error-format <format>  Print errors as human (the default), or as json with one object per line on stdout
+
+lints, or `warnings` for all of them:
+  unused-wire, unwritten-wire, unwritten-mem, constant-trigger, shift-overflow,
+  dirty-op (only with --single-mixed dirty), latency-mismatch (only with --main)";
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Args {
+  pub input: PathBuf,
+  /// Directories to look for imported files in, after the directory of the importing file.
+  pub search_path: Vec<PathBuf>,
+  pub lints: LintLevels,
+  /// The module to synthesize, which finds the warnings that need a netlist.
+  pub main: Option<String>,
+  pub single_mixed_strategy: SingleMixedStrategy,
+  pub color: ColorChoice,
+  pub error_format: ErrorFormat,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ColorChoice {
+  /// Colours the output if it goes to a terminal, and `NO_COLOR` isn't set.
+  #[default]
+  Auto,
+  Always,
+  Never,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ErrorFormat {
+  #[default]
+  Human,
+  /// One JSON object per diagnostic and line, for editors and other tools.
+  Json,
+}
+
+impl Args {
+  pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut args = args.into_iter();
+    let mut input = None;
+    let mut search_path = vec![];
+    let mut lints = LintLevels::default();
+    let mut main = None;
+    let mut single_mixed_strategy = SingleMixedStrategy::default();
+    let mut color = ColorChoice::default();
+    let mut error_format = ErrorFormat::default();
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "-I" | "--search-path" => {
+          let dir = args.next().ok_or_else(|| format!("missing directory after '{}'", arg))?;
+          search_path.push(dir.into());
+        }
+        "-A" | "--allow" | "-D" | "--deny" => {
+          let lint = args.next().ok_or_else(|| format!("missing lint after '{}'", arg))?;
+          let level = if arg == "-A" || arg == "--allow" { LintLevel::Allow } else { LintLevel::Deny };
+          lints.set(&lint, level)?;
+        }
+        "-m" | "--main" => {
+          main = Some(args.next().ok_or_else(|| format!("missing module after '{}'", arg))?);
+        }
+        "--single-mixed" => {
+          single_mixed_strategy = match args.next().as_deref() {
+            Some("clean") => SingleMixedStrategy::Clean,
+            Some("dirty") => SingleMixedStrategy::Dirty,
+            _ => return Err("expected clean or dirty after '--single-mixed'".into()),
+          };
+        }
+        "--color" => {
+          color = match args.next().as_deref() {
+            Some("auto") => ColorChoice::Auto,
+            Some("always") => ColorChoice::Always,
+            Some("never") => ColorChoice::Never,
+            _ => return Err("expected auto, always or never after '--color'".into()),
+          };
+        }
+        "--error-format" => {
+          error_format = match args.next().as_deref() {
+            Some("human") => ErrorFormat::Human,
+            Some("json") => ErrorFormat::Json,
+            _ => return Err("expected human or json after '--error-format'".into()),
+          };
+        }
+        _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+        _ if input.is_some() => return Err(format!("unexpected argument '{}'", arg)),
+        _ => input = Some(arg.into()),
+      }
+    }
+    Ok(Args {
+      input: input.ok_or("missing input file")?,
+      search_path,
+      lints,
+      main,
+      single_mixed_strategy,
+      color,
+      error_format,
+    })
+  }
+}
+
+/// Arguments of `fhdl fmt`.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct FmtArgs {
+  pub files: Vec<PathBuf>,
+  /// Only lists the files that aren't formatted, rather than formatting them.
+  pub check: bool,
+}
+
+impl FmtArgs {
+  pub fn parse(args: impl IntoIterator<Item = String>) -> Result<FmtArgs, String> {
+    let mut files = vec![];
+    let mut check = false;
+    for arg in args {
+      match arg.as_str() {
+        "--check" => check = true,
+        _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+        _ => files.push(arg.into()),
+      }
+    }
+    if files.is_empty() {
+      return Err("missing input file".into());
+    }
+    Ok(FmtArgs { files, check })
+  }
+}
+
+/// Loads and checks a program, printing any errors and warnings. Returns the exit code.
+pub fn run(args: &Args) -> i32 {
+  let (project, errors) = Project::load(&args.input, &args.search_path);
+  let mut diagnostics = errors.iter().map(Diagnostic::error).collect::<Vec<_>>();
+  if diagnostics.is_empty() {
+    let (modules, errors) = transform_project(&project, &collect_builtins());
+    diagnostics.extend(errors.iter().map(Diagnostic::error));
+    if diagnostics.is_empty() {
+      let mut warnings = lint_modules(&modules, args.single_mixed_strategy);
+      if let Some(main) = &args.main {
+        let ports = modules.iter().find(|v| &v.name == main).map_or(0, |v| v.ports.len());
+        let settings = SynthSettings {
+          main: main.clone(),
+          main_module_conn_names: vec![[' '; 4]; ports],
+          main_module_conn_signals: VIRTUAL_SIGNALS.iter().cycle().take(ports).cloned().collect(),
+          single_mixed_strategy: args.single_mixed_strategy,
+          balance_latency: false,
+        };
+        match synthesize(&settings, &modules, &collect_builtins()) {
+          Ok(netlist) => warnings.extend(lint_netlist(&netlist)),
+          Err(errors) => diagnostics.extend(errors.iter().map(Diagnostic::error)),
+        }
+      }
+      diagnostics.extend(warnings.into_iter().filter_map(|v| warning_diagnostic(&args.lints, v)));
+    }
+  }
+  let color = use_color(args.color);
+  for diag in &diagnostics {
+    match args.error_format {
+      ErrorFormat::Human => eprintln!("{}", project.render(diag, color)),
+      ErrorFormat::Json => println!("{}", project.render_json(diag)),
+    }
+  }
+  if diagnostics.iter().any(|v| v.severity == Severity::Error) {
+    1
+  } else {
+    0
+  }
+}
+
+/// Reports a warning at the level its lint is set to, if any.
+fn warning_diagnostic(lints: &LintLevels, warning: Warning) -> Option<Diagnostic> {
+  match lints.level(&warning.lint) {
+    LintLevel::Allow => None,
+    LintLevel::Warn => Some(Diagnostic::warning(&warning)),
+    LintLevel::Deny => Some(Diagnostic::error(&warning.into_err())),
+  }
+}
+
+/// Formats files in place, or with `--check`, lists those that aren't formatted. Returns the exit
+/// code, which is 1 if a file has a syntax error, or isn't formatted with `--check`.
+pub fn fmt(args: &FmtArgs) -> i32 {
+  let color = use_color(ColorChoice::Auto);
+  let mut code = 0;
+  for path in &args.files {
+    let src = match std::fs::read_to_string(path) {
+      Ok(src) => src,
+      Err(e) => {
+        let err = CerrSpan::without_span(Cerr::ReadFile(path.display().to_string(), e.to_string()));
+        eprintln!("{}", Diagnostic::error(&err).render(&path.display().to_string(), "", color));
+        code = 1;
+        continue;
+      }
+    };
+    let formatted = match format_source(&src) {
+      Ok(formatted) => formatted,
+      Err(err) => {
+        eprintln!("{}", Diagnostic::error(&err).render(&path.display().to_string(), &src, color));
+        code = 1;
+        continue;
+      }
+    };
+    if formatted == src {
+      continue;
+    }
+    if args.check {
+      println!("{}", path.display());
+      code = 1;
+    } else if let Err(e) = std::fs::write(path, formatted) {
+      eprintln!("error: couldn't write {}: {}", path.display(), e);
+      code = 1;
+    }
+  }
+  code
+}
+
+/// Whether to colour what's printed to stderr.
+fn use_color(choice: ColorChoice) -> bool {
+  match choice {
+    ColorChoice::Auto => std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    ColorChoice::Always => true,
+    ColorChoice::Never => false,
+  }
+}
@@ -0,0 +1,179 @@
+//! Renders errors and warnings, either for people reading them in a terminal, or as JSON for
+//! editors.
+//!
+//! A [`Diagnostic`] points at its source code with labels. The primary label is where the problem
+//! is, and secondary labels point at related code, such as the earlier declaration of a name that's
+//! declared again. All labels are in the diagnostic's file.
+
+use crate::err::{CerrSpan, Warning};
+use crate::parse::span::{FileId, Span};
+use serde::Serialize;
+use std::fmt::Write;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Label {
+  pub span: Span,
+  pub primary: bool,
+  pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  /// Identifies the kind of diagnostic, e.g. `E0201` for errors, or the name of a lint.
+  pub code: &'static str,
+  pub message: String,
+  pub file: FileId,
+  pub labels: Vec<Label>,
+  /// Help shown below the source code.
+  pub notes: Vec<String>,
+}
+
+/// The JSON form of a diagnostic, with the path of its file instead of its id.
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+  severity: Severity,
+  code: &'a str,
+  message: &'a str,
+  file: &'a str,
+  labels: &'a [Label],
+  notes: &'a [String],
+}
+
+/// Spans longer than this only show their first and last lines.
+const MAX_LABEL_LINES: u32 = 4;
+
+impl Diagnostic {
+  pub fn error(err: &CerrSpan) -> Self {
+    let mut labels = vec![];
+    if let Some(span) = err.span {
+      labels.push(Label { span, primary: true, message: None });
+    }
+    labels.extend(err.cerr.secondary_labels().into_iter().map(|(span, message)| Label {
+      span,
+      primary: false,
+      message: Some(message),
+    }));
+    Diagnostic {
+      severity: Severity::Error,
+      code: err.cerr.code(),
+      message: err.cerr.to_string(),
+      file: err.file,
+      labels,
+      notes: err.cerr.help().into_iter().collect(),
+    }
+  }
+
+  pub fn warning(warning: &Warning) -> Self {
+    Diagnostic {
+      severity: Severity::Warning,
+      ..Diagnostic::error(&warning.clone().into_err())
+    }
+  }
+
+  /// Renders the diagnostic for a terminal, with the source code of its file.
+  /// Line numbers are 1-based and so are the columns shown, unlike the columns of a `Span`.
+  pub fn render(&self, path: &str, src: &str, color: bool) -> String {
+    let style = Style { color };
+    let lines = src.split('\n').collect::<Vec<_>>();
+    let width = self.labels.iter().map(|v| v.span.end.line).max().unwrap_or(0).to_string().len();
+    let pad = " ".repeat(width);
+    let (name, severity_color) = match self.severity {
+      Severity::Error => ("error", RED),
+      Severity::Warning => ("warning", YELLOW),
+    };
+
+    let mut s = String::new();
+    let heading = format!("{}[{}]", name, self.code);
+    let _ = writeln!(s, "{}{}", style.paint(severity_color, &heading), style.paint(BOLD, &format!(": {}", self.message)));
+    let location = match self.labels.iter().find(|v| v.primary) {
+      Some(label) => format!("{}:{}:{}", path, label.span.start.line, label.span.start.col + 1),
+      None => path.to_owned(),
+    };
+    let _ = writeln!(s, "{}{} {}", pad, style.paint(BLUE, "-->"), location);
+    if !self.labels.is_empty() {
+      let _ = writeln!(s, "{} {}", pad, style.paint(BLUE, "|"));
+    }
+    let mut labels = self.labels.iter().collect::<Vec<_>>();
+    labels.sort_by_key(|v| v.span.start);
+    for label in labels {
+      let (marker, marker_color) = if label.primary { ('^', severity_color) } else { ('-', BLUE) };
+      let span = label.span;
+      let shown = if span.end.line.saturating_sub(span.start.line) < MAX_LABEL_LINES {
+        (span.start.line..=span.end.line).collect::<Vec<_>>()
+      } else {
+        vec![span.start.line, span.start.line + 1, span.end.line]
+      };
+      for (i, &line) in shown.iter().enumerate() {
+        if i > 0 && shown[i - 1] + 1 != line {
+          let _ = writeln!(s, "{}", style.paint(BLUE, "..."));
+        }
+        let text = (line as usize).checked_sub(1).and_then(|v| lines.get(v)).copied().unwrap_or("");
+        let text = text.trim_end_matches('\r');
+        let len = text.chars().count();
+        // lines after the first are underlined without their indentation
+        let start = if line == span.start.line {
+          span.start.col as usize
+        } else {
+          len - text.trim_start().chars().count()
+        };
+        let end = if line == span.end.line { span.end.col as usize + 1 } else { len };
+        let mut underline = marker.to_string().repeat(end.max(start + 1) - start);
+        if let (true, Some(message)) = (line == span.end.line, &label.message) {
+          let _ = write!(underline, " {}", message);
+        }
+        let gutter = format!("{:>w$} |", line, w = width);
+        let _ = writeln!(s, "{} {}", style.paint(BLUE, &gutter), text);
+        let gutter = format!("{} |", pad);
+        let _ = writeln!(s, "{} {}{}", style.paint(BLUE, &gutter), " ".repeat(start), style.paint(marker_color, &underline));
+      }
+    }
+    for note in &self.notes {
+      let _ = writeln!(s, "{} {} {}", pad, style.paint(BOLD, "= help:"), note);
+    }
+    s
+  }
+
+  /// Serializes the diagnostic as one line of JSON. Spans are given as they're stored: lines are
+  /// 1-based, columns 0-based, and the end position is included in the span.
+  pub fn to_json(&self, path: &str) -> String {
+    let json = JsonDiagnostic {
+      severity: self.severity,
+      code: self.code,
+      message: &self.message,
+      file: path,
+      labels: &self.labels,
+      notes: &self.notes,
+    };
+    // unwrap: the diagnostic only contains strings and numbers
+    serde_json::to_string(&json).unwrap()
+  }
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Wraps text in ANSI escape codes if colours are enabled.
+struct Style {
+  color: bool,
+}
+
+impl Style {
+  fn paint(&self, code: &str, text: &str) -> String {
+    if self.color {
+      format!("{}{}{}", code, text, RESET)
+    } else {
+      text.to_owned()
+    }
+  }
+}
@@ -1,7 +1,8 @@
 use std::error::Error;
-use crate::parse::span::{Pos, Span};
+use crate::diagnostic::Diagnostic;
+use crate::parse::span::{FileId, Span};
 use crate::parse::tokenizer::BinaryOp;
-use std::fmt::{Debug, Display, Formatter, Write};
+use std::fmt::{Debug, Display, Formatter};
 use std::num::ParseIntError;
 use thiserror::Error;
 
@@ -24,12 +25,21 @@ pub enum Cerr {
   UnexpectedEOF,
   #[error("Invalid expression")]
   InvalidExpr,
+  #[error("Cannot find imported file '{0}'")]
+  ImportNotFound(String),
+  #[error("Cannot read file '{0}': {1}")]
+  ReadFile(String, String),
+  #[error("Import cycle: {0}")]
+  ImportCycle(String),
 
   // Validation Errors
   #[error("'{0}' not declared")]
   NotDeclared(String),
+  /// Also has the span of the previous declaration.
   #[error("Multiple declarations for {0}")]
-  MultipleDeclarations(String),
+  MultipleDeclarations(String, Span),
+  #[error("'{0}' is already declared at {1}")]
+  AlreadyDeclaredAt(String, String),
   #[error("Cannot write to input port")]
   WriteToInput,
   #[error(
@@ -40,8 +50,12 @@ pub enum Cerr {
   MemAssignOutsideOfTrigger,
   #[error("Wrong number of arguments to module instatiation (expected {0})")]
   WrongNumberOfModuleArgs(usize),
-  #[error("Cannot nest trigger blocks")]
-  NestedTriggerBlocks,
+  #[error("Module '{0}' has no port named '{1}'")]
+  UnknownPort(String, String),
+  #[error("Port '{0}' is connected more than once")]
+  DuplicatePortConnection(String),
+  #[error("{0}")]
+  MissingPorts(PortDiff),
   #[error("In argument {0}: cannot connect expression to out or inout port")]
   ExprForOutInoutPort(usize),
   #[error("Type error: {0}")]
@@ -50,6 +64,12 @@ pub enum Cerr {
   TypeErrArgMismatch(usize, String, TypeError),
   #[error("In argument {0} of function {1}: Expected string literal")]
   ExpectedString(usize, String),
+  #[error("In argument {0} of function {1}: Invalid signal \"{2}\", expected e.g. \"item=iron-plate\"")]
+  InvalidSignal(usize, String, String),
+  #[error("In argument {0} of function {1}: Invalid quality \"{2}\", expected one of normal, uncommon, rare, epic or legendary")]
+  InvalidQuality(usize, String, String),
+  #[error("In argument {0} of function {1}: Expected non-negative integer literal")]
+  ExpectedCount(usize, String),
   #[error("Unexpected string literal")]
   UnexpectedString,
   #[error("Unknown function '{0}'")]
@@ -58,25 +78,205 @@ pub enum Cerr {
   WrongNumberOfFunctionArgs(usize),
   #[error("Cannot use op {0} on two mixed nets")]
   InvalidOpOnMixedNets(BinaryOp),
+  #[error("Cannot use logical op {0} on mixed nets")]
+  LogicalOpOnMixedNet(BinaryOp),
+  #[error("Memory array length must be between 1 and {0}")]
+  InvalidArrayLength(usize),
+  #[error("'{0}' is not a memory array")]
+  NotAnArray(String),
+  #[error("Cannot load ROM file '{0}': {1}")]
+  RomFile(String, String),
+  #[error("ROM '{0}' can only be read by index")]
+  RomWithoutIndex(String),
+  #[error("Cannot write to ROM")]
+  WriteToRom,
+  #[error("Invalid signal \"{0}\" in ROM entry, expected e.g. \"item=iron-plate\"")]
+  InvalidRomSignal(String),
+  #[error("Bundle '{0}' can only be used through its members")]
+  BundleAsValue(String),
 
   // Synthesis Errors
   #[error("Main module '{0}' not found")]
   MainNotFound(String),
+  #[error("Main module '{0}' has {1} ports, but {2} connections were configured")]
+  MainPortCount(String, usize, usize),
+  #[error("Net '{0}' not found")]
+  NetNotFound(String),
+  #[error("Not enough distinct signals for the single nets of this module")]
+  OutOfSignals,
+  #[error("Mixed net used where a single signal is needed")]
+  SignalOfMixedNet,
+
+  // Lints that were denied
+  #[error("{0}")]
+  Lint(Lint),
   
   // Layout Errors (todo)
   #[error("LayoutShaper invalid argument: {0}")]
   LayoutShaperInvalidArg(String)
 }
 
+/// Code that is valid, but likely a mistake. Lints are reported as warnings, unless they're
+/// allowed or denied, see [`crate::synth::lint`].
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum Lint {
+  #[error("Wire '{0}' is never read")]
+  UnusedWire(String),
+  #[error("Wire '{0}' is read but never written")]
+  UnwrittenWire(String),
+  #[error("Memory cell '{0}' is never written")]
+  UnwrittenMem(String),
+  #[error("Trigger condition is constant")]
+  ConstantTrigger,
+  #[error("Shift by {0}, which is not less than 32")]
+  ShiftOverflow(i32),
+  #[error("Op {0} between a single and a mixed net leaks the single net's signal into the result")]
+  DirtyOp(BinaryOp),
+  #[error("Values that lag behind the inputs by {0} and {1} ticks are combined here")]
+  LatencyMismatch(u32, u32),
+}
+
+impl Lint {
+  /// The names of all lints, as given to `--allow` and `--deny`.
+  pub const NAMES: [&'static str; 7] = [
+    "unused-wire",
+    "unwritten-wire",
+    "unwritten-mem",
+    "constant-trigger",
+    "shift-overflow",
+    "dirty-op",
+    "latency-mismatch",
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Lint::UnusedWire(_) => "unused-wire",
+      Lint::UnwrittenWire(_) => "unwritten-wire",
+      Lint::UnwrittenMem(_) => "unwritten-mem",
+      Lint::ConstantTrigger => "constant-trigger",
+      Lint::ShiftOverflow(_) => "shift-overflow",
+      Lint::DirtyOp(_) => "dirty-op",
+      Lint::LatencyMismatch(..) => "latency-mismatch",
+    }
+  }
+}
+
+/// A lint, along with where it was found.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+  pub span: Option<Span>,
+  pub file: FileId,
+  pub lint: Lint,
+}
+
+impl Warning {
+  /// Turns the warning into an error, for lints that are denied.
+  pub fn into_err(self) -> CerrSpan {
+    Cerr::Lint(self.lint).with_opt(self.span).in_file(self.file)
+  }
+}
+
 impl Cerr {
   pub fn with(self, span: Span) -> CerrSpan {
     CerrSpan::new(span, self)
   }
+
+  /// Like [`Cerr::with`], for errors that may not have a span.
+  pub fn with_opt(self, span: Option<Span>) -> CerrSpan {
+    CerrSpan { span, file: 0, cerr: self }
+  }
+
+  /// A stable code for each kind of error, so that it can be looked up and matched by tools.
+  /// The hundreds digit is the stage that reports it, and denied lints use their name instead.
+  pub fn code(&self) -> &'static str {
+    match self {
+      Cerr::InvalidChar => "E0001",
+      Cerr::InvalidInteger(_) => "E0002",
+      Cerr::InvalidOperator => "E0003",
+
+      Cerr::UnexpectedToken(_) => "E0101",
+      Cerr::UnexpectedTokenType(_) => "E0102",
+      Cerr::UnexpectedEOF => "E0103",
+      Cerr::InvalidExpr => "E0104",
+      Cerr::ImportNotFound(_) => "E0105",
+      Cerr::ReadFile(..) => "E0106",
+      Cerr::ImportCycle(_) => "E0107",
+
+      Cerr::NotDeclared(_) => "E0201",
+      Cerr::MultipleDeclarations(..) => "E0202",
+      Cerr::AlreadyDeclaredAt(..) => "E0203",
+      Cerr::WriteToInput => "E0204",
+      Cerr::MultipleExclusiveWrites => "E0205",
+      Cerr::MemAssignOutsideOfTrigger => "E0206",
+      Cerr::WrongNumberOfModuleArgs(_) => "E0207",
+      Cerr::UnknownPort(..) => "E0208",
+      Cerr::DuplicatePortConnection(_) => "E0209",
+      Cerr::MissingPorts(_) => "E0210",
+      Cerr::ExprForOutInoutPort(_) => "E0211",
+      Cerr::TypeErrorGeneric(_) => "E0212",
+      Cerr::TypeErrArgMismatch(..) => "E0213",
+      Cerr::ExpectedString(..) => "E0214",
+      Cerr::InvalidSignal(..) => "E0215",
+      Cerr::ExpectedCount(..) => "E0216",
+      Cerr::UnexpectedString => "E0217",
+      Cerr::UnknownFunction(_) => "E0218",
+      Cerr::WrongNumberOfFunctionArgs(_) => "E0219",
+      Cerr::InvalidOpOnMixedNets(_) => "E0220",
+      Cerr::LogicalOpOnMixedNet(_) => "E0221",
+      Cerr::InvalidArrayLength(_) => "E0222",
+      Cerr::NotAnArray(_) => "E0223",
+      Cerr::RomFile(..) => "E0224",
+      Cerr::RomWithoutIndex(_) => "E0225",
+      Cerr::WriteToRom => "E0226",
+      Cerr::InvalidRomSignal(_) => "E0227",
+      Cerr::BundleAsValue(_) => "E0228",
+      Cerr::InvalidQuality(..) => "E0229",
+
+      Cerr::MainNotFound(_) => "E0301",
+      Cerr::MainPortCount(..) => "E0302",
+      Cerr::NetNotFound(_) => "E0303",
+      Cerr::OutOfSignals => "E0304",
+      Cerr::SignalOfMixedNet => "E0305",
+
+      Cerr::Lint(lint) => lint.name(),
+
+      Cerr::LayoutShaperInvalidArg(_) => "E0401",
+    }
+  }
+
+  /// A hint on how to fix the error, if there's a common fix.
+  pub fn help(&self) -> Option<String> {
+    Some(match self {
+      Cerr::MultipleExclusiveWrites => {
+        "use '+=' to add several values to a wire, or write them in different branches of a trigger".into()
+      }
+      Cerr::MemAssignOutsideOfTrigger => "move the assignment into a trigger block, or use '+=' to add to the memory cell every tick".into(),
+      Cerr::InvalidOpOnMixedNets(_) => "only '+' and '-' work on two mixed nets".into(),
+      Cerr::LogicalOpOnMixedNet(_) => "use extract() to get a single signal of the mixed net first".into(),
+      Cerr::RomWithoutIndex(name) => format!("read an entry with '{}[index]'", name),
+      Cerr::BundleAsValue(name) => format!("use a member instead, e.g. '{}.<member>'", name),
+      Cerr::ImportNotFound(_) => "imports are looked up next to the importing file, then in each '-I' directory".into(),
+      Cerr::Lint(Lint::ConstantTrigger) => "raw triggers fire while their condition is 0, so this fires every tick or never".into(),
+      Cerr::Lint(Lint::DirtyOp(_)) => "synthesize with the Clean single-mixed strategy to remove the signal".into(),
+      Cerr::Lint(_) => format!("allow this with '--allow {}'", self.code()),
+      _ => return None,
+    })
+  }
+
+  /// Spans of related code, each with a note on how it's related.
+  pub fn secondary_labels(&self) -> Vec<(Span, String)> {
+    match self {
+      Cerr::MultipleDeclarations(_, first) => vec![(*first, "previously declared here".into())],
+      _ => vec![],
+    }
+  }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CerrSpan {
   pub span: Option<Span>,
+  /// The file the span is in. Programs that aren't loaded from files only have file 0.
+  pub file: FileId,
   pub cerr: Cerr,
 }
 
@@ -84,48 +284,22 @@ impl CerrSpan {
   pub fn new(span: Span, cerr: Cerr) -> Self {
     CerrSpan {
       span: Some(span),
+      file: 0,
       cerr,
     }
   }
 
   pub fn without_span(cerr: Cerr) -> Self {
-    CerrSpan { span: None, cerr }
+    CerrSpan { span: None, file: 0, cerr }
   }
 
-  pub fn format_err(&self, filename: &str, source: &[&str]) -> Result<String, std::fmt::Error> {
-    let span = self.span.unwrap_or_else(|| {
-      Span::from(Pos::new(
-        source.len() as u32,
-        (source[source.len() - 1].len() - 1) as u32,
-      ))
-    });
-    let mut s = String::new();
-    writeln!(
-      &mut s,
-      "at {}:{}:{}: {}",
-      filename, span.start.line, span.start.col, self.cerr
-    )?;
-    for i in span.start.line..=span.end.line {
-      let i = i as usize;
-      let cstart = if i == span.start.line as usize {
-        span.start.col as usize
-      } else {
-        0usize
-      };
-      let cend = if i == span.end.line as usize {
-        span.end.col as usize
-      } else {
-        source[i].len() - 1
-      };
-      writeln!(&mut s, "  {}", source[i - 1])?;
-      writeln!(
-        &mut s,
-        "  {}{}",
-        " ".repeat(cstart),
-        "^".repeat(cend - cstart + 1)
-      )?;
-    }
-    Ok(s)
+  pub fn in_file(self, file: FileId) -> Self {
+    CerrSpan { file, ..self }
+  }
+
+  /// Renders the error without colours, see [`Diagnostic::render`].
+  pub fn format_err(&self, filename: &str, src: &str) -> String {
+    Diagnostic::error(self).render(filename, src, false)
   }
 }
 
@@ -149,6 +323,25 @@ impl From<Cerr> for CerrSpan {
   }
 }
 
+/// The ports of a module instantiation that weren't connected.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PortDiff {
+  pub module: String,
+  /// Every port of the module, in order, and whether it is connected.
+  pub ports: Vec<(String, bool)>,
+}
+
+impl Display for PortDiff {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Missing connections to ports of module '{}':", self.module)?;
+    for (port, connected) in &self.ports {
+      let marker = if *connected { ' ' } else { '-' };
+      write!(f, "\n  {} {}", marker, port)?;
+    }
+    Ok(())
+  }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TypeError {
   pub src_ty: String,
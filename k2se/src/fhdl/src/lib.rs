@@ -0,0 +1,44 @@
+//! The FHDL compiler. Its binaries are the command line interface `fhdl`, and the language
+//! server `fhdl-lsp`.
+
+#![warn(missing_debug_implementations)]
+#![allow(dead_code)]
+mod cli;
+mod diagnostic;
+mod err;
+mod lsp;
+mod parse;
+mod synth;
+#[cfg(test)]
+mod test;
+mod util;
+mod layout;
+
+use crate::cli::{Args, FmtArgs, USAGE};
+
+/// Runs the command line interface with the arguments of the process. Returns the exit code.
+pub fn cli_main() -> i32 {
+  let mut args = std::env::args().skip(1).peekable();
+  if args.peek().map(String::as_str) == Some("fmt") {
+    return match FmtArgs::parse(args.skip(1)) {
+      Ok(args) => cli::fmt(&args),
+      Err(msg) => {
+        eprintln!("error: {}\n\n{}", msg, USAGE);
+        2
+      }
+    };
+  }
+  let args = match Args::parse(args) {
+    Ok(args) => args,
+    Err(msg) => {
+      eprintln!("error: {}\n\n{}", msg, USAGE);
+      return 2;
+    }
+  };
+  cli::run(&args)
+}
+
+/// Runs the language server on stdin and stdout. Returns the exit code.
+pub fn lsp_main() -> i32 {
+  lsp::run(std::io::stdin().lock(), std::io::stdout().lock())
+}
@@ -0,0 +1,250 @@
+//! Answers the questions of an editor about one file, using the IR of its project.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::parse::ast::{Expr, ModuleArgs, PortDecl, Stmt};
+use crate::parse::project::Project;
+use crate::parse::span::{FileId, Pos, Span, WithSpan};
+use crate::parse::tokenizer::{tokenize, Token};
+use crate::synth::builtins::collect_builtins;
+use crate::synth::ir::IRModule;
+use crate::synth::lint::lint_modules;
+use crate::synth::synth::{synthesize, SynthSettings};
+use crate::synth::transform::transform_project;
+use crate::synth::virt_signals::VIRTUAL_SIGNALS;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A file that was loaded and transformed along with its imports. The file is always file 0
+/// of the project.
+#[derive(Debug)]
+pub struct Analysis {
+  pub project: Project,
+  pub modules: Vec<IRModule>,
+  /// Errors and warnings of the whole project, in the same order as the command line prints them.
+  pub diagnostics: Vec<Diagnostic>,
+  /// The tokens of the file, up to the first one that couldn't be tokenized.
+  tokens: Vec<WithSpan<Token>>,
+}
+
+impl Analysis {
+  pub fn new(path: &Path, search_path: &[PathBuf], open: &HashMap<PathBuf, String>) -> Self {
+    let (project, errors) = Project::load_with(path, search_path, open);
+    let (modules, transform_errors) = transform_project(&project, &collect_builtins());
+    // like the command line, only check the modules of a project that loaded without errors,
+    // but keep the modules that could be transformed for navigating
+    let mut diagnostics = errors.iter().map(Diagnostic::error).collect::<Vec<_>>();
+    if diagnostics.is_empty() {
+      diagnostics.extend(transform_errors.iter().map(Diagnostic::error));
+    }
+    if diagnostics.is_empty() {
+      diagnostics.extend(lint_modules(&modules, Default::default()).iter().map(Diagnostic::warning));
+    }
+    let src = project.files.first().map_or("", |v| v.src.as_str());
+    let tokens = tokenize(src.chars()).map_while(Result::ok).collect();
+    Analysis { project, modules, diagnostics, tokens }
+  }
+
+  /// The name under or right after the cursor.
+  fn name_at(&self, pos: Pos) -> Option<&str> {
+    self.tokens.iter().find_map(|v| match &v.t {
+      Token::Name(name) if v.span.contains(pos) || v.span.contains(Pos::new(pos.line, pos.col.saturating_sub(1))) => {
+        Some(name.as_str())
+      }
+      _ => None,
+    })
+  }
+
+  /// The module of the file that contains the cursor.
+  fn module_at(&self, pos: Pos) -> Option<(&IRModule, Span)> {
+    let program = self.project.files.first()?.program.as_ref()?;
+    let (module, span) = program.modules.iter().find(|(_, span)| span.contains(pos))?;
+    let ir = self.modules.iter().find(|v| v.file == 0 && v.name == module.name)?;
+    Some((ir, *span))
+  }
+
+  /// Finds where the wire or module under the cursor is declared.
+  pub fn definition(&self, pos: Pos) -> Option<(FileId, Span)> {
+    let name = self.name_at(pos)?;
+    if let Some((module, _)) = self.module_at(pos) {
+      if let Some(decl) = module.objects.get(name) {
+        return Some((module.file, decl.span?));
+      }
+    }
+    self.project.programs().find_map(|(file, program)| {
+      let (_, span) = program.modules.iter().find(|(v, _)| v.name == name)?;
+      Some((file, *span))
+    })
+  }
+
+  /// Describes the wire or module under the cursor, as Markdown. Wires of a module without
+  /// errors also show the signal they're synthesized to, with the module as the main module.
+  pub fn hover(&self, pos: Pos) -> Option<String> {
+    let name = self.name_at(pos)?;
+    if let Some((module, _)) = self.module_at(pos) {
+      if let Some(decl) = module.objects.get(name) {
+        let kind = match decl.port_idx.map(|v| module.ports[v].port_class) {
+          Some(class) => class.to_string(),
+          None if decl.mem => "mem".into(),
+          None => "wire".into(),
+        };
+        let mut hover = format!("```fhdl\n{} {} {}\n```", kind, decl.ty, name);
+        if let Some(signal) = self.resolve_signal(module, name) {
+          hover.push_str(&format!("\n\nsignal: `{}`", signal));
+        }
+        return Some(hover);
+      }
+    }
+    let module = self.modules.iter().find(|v| v.name == name)?;
+    let ports = module.ports.iter().map(port_signature).collect::<Vec<_>>();
+    Some(format!("```fhdl\nmodule {}({})\n```", module.name, ports.join(", ")))
+  }
+
+  /// Synthesizes a module on its own to find the signal of one of its single wires.
+  /// Ports of the module are given distinct virtual signals.
+  fn resolve_signal(&self, module: &IRModule, name: &str) -> Option<String> {
+    if self.diagnostics.iter().any(|v| v.severity == Severity::Error) {
+      return None;
+    }
+    let settings = SynthSettings {
+      main: module.name.clone(),
+      main_module_conn_names: vec![[' '; 4]; module.ports.len()],
+      main_module_conn_signals: VIRTUAL_SIGNALS.iter().cycle().take(module.ports.len()).cloned().collect(),
+      single_mixed_strategy: Default::default(),
+      balance_latency: false,
+    };
+    let netlist = synthesize(&settings, &self.modules, &collect_builtins()).ok()?;
+    let path = format!("{}.{}", module.name, name);
+    let net = netlist.nets_at(&path).next()?;
+    netlist.nets[net].signal.as_ref().map(|v| v.to_string())
+  }
+
+  /// Names that can be completed anywhere: built-in functions and modules.
+  pub fn completions(&self) -> Vec<(String, CompletionKind)> {
+    let mut builtins = collect_builtins()
+      .into_keys()
+      .filter(|v| !v.starts_with('$'))
+      .map(|v| (v, CompletionKind::Function))
+      .collect::<Vec<_>>();
+    builtins.sort();
+    let modules = self.modules.iter().map(|v| (v.name.clone(), CompletionKind::Module));
+    builtins.into_iter().chain(modules).collect()
+  }
+
+  /// Finds every mention of the wire under the cursor in its module, so that it can be
+  /// renamed to `new_name`. Fails with a reason if it can't be renamed.
+  pub fn rename(&self, pos: Pos, new_name: &str) -> Result<Vec<Span>, String> {
+    let name = self.name_at(pos).ok_or("Nothing to rename here")?;
+    let (module, span) = self.module_at(pos).ok_or("Only wires and memory cells can be renamed")?;
+    let decl = module.objects.get(name).ok_or("Only wires and memory cells can be renamed")?;
+    let references = self.references(span, name);
+    // the cursor may be on a named port or a function that happens to share the name
+    if !references.iter().any(|v| v.contains(pos) || v.contains(Pos::new(pos.line, pos.col.saturating_sub(1)))) {
+      return Err("Only wires and memory cells can be renamed".into());
+    }
+    if decl.port_idx.is_some() || name.contains('.') {
+      return Err("Ports and bundle members can't be renamed, since instances may refer to them by name".into());
+    }
+    let valid = new_name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+      && new_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+      return Err(format!("'{}' is not a valid name", new_name));
+    }
+    if module.objects.contains_key(new_name) {
+      return Err(format!("'{}' is already declared", new_name));
+    }
+    Ok(references)
+  }
+
+  /// Finds the names in the module declared at `span` that refer to the wire `name`: its
+  /// declaration, the statements that set it and the expressions that read it. Named ports,
+  /// function names and keywords are left out, even if they're spelled the same.
+  fn references(&self, span: Span, name: &str) -> Vec<Span> {
+    let Some((module, _)) = self
+      .project
+      .files
+      .first()
+      .and_then(|v| v.program.as_ref())
+      .and_then(|v| v.modules.iter().find(|(_, v)| *v == span))
+    else {
+      return vec![];
+    };
+    let mut starts = vec![];
+    module.stmts.iter().for_each(|(stmt, span)| self.stmt_references(stmt, *span, name, &mut starts));
+    self
+      .tokens
+      .iter()
+      .filter(|v| starts.contains(&v.span.start) && matches!(&v.t, Token::Name(v) if v == name))
+      .map(|v| v.span)
+      .collect()
+  }
+
+  /// Collects where the names that may refer to `name` start in a statement.
+  fn stmt_references(&self, stmt: &Stmt, span: Span, name: &str, starts: &mut Vec<Pos>) {
+    let tokens = self.tokens.iter().filter(|v| span.contains(v.span.start)).collect::<Vec<_>>();
+    // declarations are a keyword and a type followed by the name, and `set` is followed by the name
+    let mut token_at = |i: usize| starts.extend(tokens.get(i).map(|v| v.span.start));
+    match stmt {
+      Stmt::MemDecl { reset, .. } => {
+        token_at(2);
+        if reset.is_some() {
+          // the reset wire comes right before the semicolon
+          token_at(tokens.len().saturating_sub(2));
+        }
+      }
+      Stmt::MemArrayDecl { .. } | Stmt::RomDecl { .. } => token_at(2),
+      Stmt::WireDecl { expr, .. } | Stmt::BundleWireDecl { expr, .. } => {
+        token_at(2);
+        expr.iter().for_each(|v| expr_references(v, name, starts));
+      }
+      Stmt::Set { expr, .. } => {
+        token_at(1);
+        expr_references(expr, name, starts);
+      }
+      Stmt::SetIndexed { index, expr, .. } => {
+        token_at(1);
+        expr_references(index, name, starts);
+        expr_references(expr, name, starts);
+      }
+      Stmt::ModuleInst { args, .. } => match args {
+        ModuleArgs::Positional(args) => args.iter().for_each(|v| expr_references(v, name, starts)),
+        ModuleArgs::Named(args) => args.iter().for_each(|(_, v)| expr_references(v, name, starts)),
+      },
+      Stmt::Trigger { watching, statements, else_statements, .. } => {
+        expr_references(watching, name, starts);
+        statements
+          .iter()
+          .chain(else_statements)
+          .for_each(|(stmt, span)| self.stmt_references(stmt, *span, name, starts));
+      }
+    }
+  }
+}
+
+/// Collects where the names in an expression that refer to `name` start.
+fn expr_references(expr: &Expr, name: &str, starts: &mut Vec<Pos>) {
+  match expr {
+    Expr::Identifier { name: v, span } if v == name => starts.push(span.start),
+    Expr::Identifier { .. } | Expr::Literal { .. } | Expr::StringLiteral { .. } => {}
+    Expr::FnCall { args, .. } => args.iter().for_each(|v| expr_references(v, name, starts)),
+    Expr::Index { name: v, index, span } => {
+      if v == name {
+        starts.push(span.start);
+      }
+      expr_references(index, name, starts);
+    }
+    Expr::BinaryOps { car, cdr, .. } => {
+      expr_references(car, name, starts);
+      cdr.iter().for_each(|(_, v)| expr_references(v, name, starts));
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum CompletionKind {
+  Function,
+  Module,
+}
+
+fn port_signature(port: &PortDecl) -> String {
+  format!("{} {} {}", port.port_class, port.signal_class, port.name)
+}
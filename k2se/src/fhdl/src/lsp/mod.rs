@@ -0,0 +1,347 @@
+//! A language server for FHDL, which talks LSP over stdin and stdout.
+//!
+//! The server keeps the text of every open document, and analyzes a document again whenever it's
+//! needed: after it changes, to publish its diagnostics, and for every request about it. Imports
+//! are read from the other open documents where possible, and from disk otherwise. Positions are
+//! counted in characters if the client supports it, and in UTF-16 code units otherwise.
+
+pub mod analysis;
+pub mod rpc;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lsp::analysis::{Analysis, CompletionKind};
+use crate::parse::span::{Pos, Span};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Serves one client until it exits. Returns the exit code, which is only 0 if the client shut
+/// the server down before exiting.
+pub fn run(mut input: impl BufRead, output: impl Write) -> i32 {
+  let mut server = Server {
+    output,
+    documents: HashMap::new(),
+    search_path: vec![],
+    encoding: PositionEncoding::Utf16,
+    shut_down: false,
+  };
+  loop {
+    let message = match rpc::read_message(&mut input) {
+      Ok(Some(message)) => message,
+      Ok(None) => return 1,
+      Err(e) => {
+        eprintln!("fhdl-lsp: {}", e);
+        return 1;
+      }
+    };
+    let method = message["method"].as_str().unwrap_or_default();
+    if method == "exit" {
+      return if server.shut_down { 0 } else { 1 };
+    }
+    let params = &message["params"];
+    let Some(id) = message.get("id") else {
+      server.notification(method, params);
+      continue;
+    };
+    let response = match server.request(method, params) {
+      Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+      Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    };
+    if let Err(e) = rpc::write_message(&mut server.output, &response) {
+      eprintln!("fhdl-lsp: {}", e);
+      return 1;
+    }
+  }
+}
+
+/// JSON-RPC error codes.
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const REQUEST_FAILED: i32 = -32803;
+
+#[derive(Debug)]
+struct Server<W> {
+  output: W,
+  /// The text of each open document, by URI.
+  documents: HashMap<String, String>,
+  search_path: Vec<PathBuf>,
+  encoding: PositionEncoding,
+  shut_down: bool,
+}
+
+/// How the columns of LSP positions are counted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum PositionEncoding {
+  /// UTF-16 code units, which every client supports.
+  Utf16,
+  /// Characters, which is how spans count them.
+  Utf32,
+}
+
+impl<W: Write> Server<W> {
+  fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i32, String)> {
+    match method {
+      "initialize" => {
+        let search_path = params["initializationOptions"]["searchPath"].as_array();
+        self.search_path = search_path
+          .into_iter()
+          .flatten()
+          .filter_map(|v| v.as_str().map(PathBuf::from))
+          .collect();
+        let encodings = params["capabilities"]["general"]["positionEncodings"].as_array();
+        self.encoding = if encodings.into_iter().flatten().any(|v| v == "utf-32") {
+          PositionEncoding::Utf32
+        } else {
+          PositionEncoding::Utf16
+        };
+        Ok(json!({
+          "capabilities": {
+            "positionEncoding": match self.encoding {
+              PositionEncoding::Utf16 => "utf-16",
+              PositionEncoding::Utf32 => "utf-32",
+            },
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "completionProvider": {},
+            "renameProvider": true,
+          },
+          "serverInfo": { "name": "fhdl-lsp" },
+        }))
+      }
+      "shutdown" => {
+        self.shut_down = true;
+        Ok(Value::Null)
+      }
+      "textDocument/definition" => {
+        let (uri, pos) = self.text_position(params)?;
+        let analysis = self.analyze(uri);
+        Ok(match analysis.definition(pos) {
+          Some((file, span)) => json!({
+            "uri": path_to_uri(&analysis.project.files[file].path),
+            "range": range(&analysis.project.files[file].src, span, self.encoding),
+          }),
+          None => Value::Null,
+        })
+      }
+      "textDocument/hover" => {
+        let (uri, pos) = self.text_position(params)?;
+        Ok(match self.analyze(uri).hover(pos) {
+          Some(hover) => json!({ "contents": { "kind": "markdown", "value": hover } }),
+          None => Value::Null,
+        })
+      }
+      "textDocument/completion" => {
+        let (uri, _) = self.text_position(params)?;
+        let items = self
+          .analyze(uri)
+          .completions()
+          .into_iter()
+          .map(|(label, kind)| {
+            let kind = match kind {
+              CompletionKind::Function => 3,
+              CompletionKind::Module => 9,
+            };
+            json!({ "label": label, "kind": kind })
+          })
+          .collect::<Vec<_>>();
+        Ok(Value::Array(items))
+      }
+      "textDocument/rename" => {
+        let (uri, pos) = self.text_position(params)?;
+        let new_name = params["newName"].as_str().ok_or((INVALID_PARAMS, "missing newName".into()))?;
+        let analysis = self.analyze(uri);
+        let spans = analysis.rename(pos, new_name).map_err(|e| (REQUEST_FAILED, e))?;
+        let src = &analysis.project.files[0].src;
+        let edits = spans
+          .into_iter()
+          .map(|span| json!({ "range": range(src, span, self.encoding), "newText": new_name }))
+          .collect::<Vec<_>>();
+        Ok(json!({ "changes": { uri: edits } }))
+      }
+      _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+    }
+  }
+
+  fn notification(&mut self, method: &str, params: &Value) {
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_owned();
+    match method {
+      "textDocument/didOpen" => {
+        let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+        self.documents.insert(uri.clone(), text.to_owned());
+        self.publish_diagnostics(&uri);
+      }
+      "textDocument/didChange" => {
+        // only full syncs are supported, so the last change has the whole text
+        let changes = params["contentChanges"].as_array();
+        if let Some(text) = changes.and_then(|v| v.last()).and_then(|v| v["text"].as_str()) {
+          self.documents.insert(uri.clone(), text.to_owned());
+          self.publish_diagnostics(&uri);
+        }
+      }
+      "textDocument/didClose" => {
+        self.documents.remove(&uri);
+        self.send_diagnostics(&uri, vec![]);
+      }
+      _ => {}
+    }
+  }
+
+  /// Gets the document and position of a request, with the position counted in characters.
+  fn text_position<'a>(&self, params: &'a Value) -> Result<(&'a str, Pos), (i32, String)> {
+    let (uri, pos) = text_position(params)?;
+    let line = self.documents.get(uri).and_then(|v| v.lines().nth(pos.line as usize - 1)).unwrap_or_default();
+    Ok((uri, Pos::new(pos.line, decode_col(line, pos.col, self.encoding))))
+  }
+
+  fn analyze(&self, uri: &str) -> Analysis {
+    let open = self
+      .documents
+      .iter()
+      .map(|(uri, text)| (uri_to_path(uri), text.clone()))
+      .collect();
+    Analysis::new(&uri_to_path(uri), &self.search_path, &open)
+  }
+
+  /// Publishes the diagnostics of a document. Those of imported files are left to their own
+  /// documents, once they're opened.
+  fn publish_diagnostics(&mut self, uri: &str) {
+    let analysis = self.analyze(uri);
+    let src = &analysis.project.files[0].src;
+    let diagnostics = analysis
+      .diagnostics
+      .iter()
+      .filter(|v| v.file == 0)
+      .map(|v| lsp_diagnostic(uri, v, |span| range(src, span, self.encoding)))
+      .collect();
+    self.send_diagnostics(uri, diagnostics);
+  }
+
+  fn send_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) {
+    let notification = json!({
+      "jsonrpc": "2.0",
+      "method": "textDocument/publishDiagnostics",
+      "params": { "uri": uri, "diagnostics": diagnostics },
+    });
+    if let Err(e) = rpc::write_message(&mut self.output, &notification) {
+      eprintln!("fhdl-lsp: {}", e);
+    }
+  }
+}
+
+fn lsp_diagnostic(uri: &str, diag: &Diagnostic, range: impl Fn(Span) -> Value) -> Value {
+  let primary = diag.labels.iter().find(|v| v.primary).map(|v| v.span);
+  let mut message = diag.message.clone();
+  for note in &diag.notes {
+    message.push_str(&format!("\nhelp: {}", note));
+  }
+  let related = diag
+    .labels
+    .iter()
+    .filter(|v| !v.primary)
+    .map(|v| {
+      json!({
+        "location": { "uri": uri, "range": range(v.span) },
+        "message": v.message.clone().unwrap_or_default(),
+      })
+    })
+    .collect::<Vec<_>>();
+  json!({
+    "range": range(primary.unwrap_or_default()),
+    "severity": match diag.severity { Severity::Error => 1, Severity::Warning => 2 },
+    "code": diag.code,
+    "source": "fhdl",
+    "message": message,
+    "relatedInformation": related,
+  })
+}
+
+/// Gets the document and position of a request, as the client sent it.
+fn text_position(params: &Value) -> Result<(&str, Pos), (i32, String)> {
+  let uri = params["textDocument"]["uri"].as_str();
+  let line = params["position"]["line"].as_u64();
+  let character = params["position"]["character"].as_u64();
+  match (uri, line, character) {
+    (Some(uri), Some(line), Some(character)) => Ok((uri, Pos::new(line as u32 + 1, character as u32))),
+    _ => Err((INVALID_PARAMS, "missing textDocument or position".into())),
+  }
+}
+
+/// Converts a span in `src` to an LSP range, whose lines are 0-based and whose end is excluded.
+fn range(src: &str, span: Span, encoding: PositionEncoding) -> Value {
+  let position = |pos: Pos| {
+    let line = src.lines().nth(pos.line.saturating_sub(1) as usize).unwrap_or_default();
+    json!({ "line": pos.line.saturating_sub(1), "character": encode_col(line, pos.col, encoding) })
+  };
+  json!({
+    "start": position(span.start),
+    "end": position(Pos::new(span.end.line, span.end.col + 1)),
+  })
+}
+
+/// The UTF-16 length of each character of a line. Columns past its end count as one unit each.
+fn utf16_lens(line: &str) -> impl Iterator<Item = u32> + '_ {
+  line.chars().map(|c| c.len_utf16() as u32).chain(std::iter::repeat(1))
+}
+
+/// Converts a column counted in characters into the client's encoding.
+fn encode_col(line: &str, col: u32, encoding: PositionEncoding) -> u32 {
+  match encoding {
+    PositionEncoding::Utf16 => utf16_lens(line).take(col as usize).sum(),
+    PositionEncoding::Utf32 => col,
+  }
+}
+
+/// Converts a column in the client's encoding into one counted in characters. A column in the
+/// middle of a character refers to the character.
+fn decode_col(line: &str, col: u32, encoding: PositionEncoding) -> u32 {
+  match encoding {
+    PositionEncoding::Utf16 => {
+      let mut units = 0;
+      utf16_lens(line)
+        .take_while(|len| {
+          units += len;
+          units <= col
+        })
+        .count() as u32
+    }
+    PositionEncoding::Utf32 => col,
+  }
+}
+
+pub fn uri_to_path(uri: &str) -> PathBuf {
+  let path = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+  let mut bytes = vec![];
+  let mut i = 0;
+  while i < path.len() {
+    // a '%' that isn't followed by two hex digits is kept as it is
+    let escaped = path
+      .get(i + 1..i + 3)
+      .filter(|hex| path[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+      .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+    match escaped {
+      Some(b) => {
+        bytes.push(b);
+        i += 3;
+      }
+      None => {
+        bytes.push(path[i]);
+        i += 1;
+      }
+    }
+  }
+  PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+  let path = path.display().to_string();
+  let escaped = path
+    .bytes()
+    .map(|b| match b {
+      b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+      _ => format!("%{:02X}", b),
+    })
+    .collect::<String>();
+  format!("file://{}", escaped)
+}
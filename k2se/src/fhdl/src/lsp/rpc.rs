@@ -0,0 +1,33 @@
+//! Reads and writes JSON-RPC messages, framed by a `Content-Length` header as in LSP.
+
+use serde_json::Value;
+use std::io::{BufRead, Error, ErrorKind, Write};
+
+/// Reads one message. Returns `None` at the end of the input.
+pub fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<Value>> {
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some(value) = line.strip_prefix("Content-Length:") {
+      let value = value.trim().parse::<usize>().map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+      length = Some(value);
+    }
+  }
+  let length = length.ok_or_else(|| Error::new(ErrorKind::InvalidData, "missing Content-Length header"))?;
+  let mut body = vec![0; length];
+  input.read_exact(&mut body)?;
+  Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> std::io::Result<()> {
+  let body = message.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  output.flush()
+}
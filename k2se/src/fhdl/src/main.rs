@@ -1,13 +1,3 @@
-#![warn(missing_debug_implementations)]
-#![allow(dead_code)]
-mod err;
-mod parse;
-mod synth;
-#[cfg(test)]
-mod test;
-mod util;
-mod layout;
-
 fn main() {
-  println!("Hello, world!");
+  std::process::exit(fhdl::cli_main());
 }
@@ -1,37 +1,83 @@
 //! AST parsing. All structs here are part of the AST, and
 //! have a parse function that returns `Self` or `(Self, Span)`.
+//!
+//! Syntax errors are recovered from in panic mode, so that one typo doesn't hide the rest of the
+//! errors in a file. A statement with an error is skipped up to the next `;` or `}`, and a
+//! top level item with an error is skipped up to the next `module`, `bundle` or `import`.
 
 use crate::err::{Cerr, CerrSpan};
 use crate::parse::span::Span;
-use crate::parse::tokenizer::{BinaryOp, Token};
+use crate::parse::tokenizer::{BinaryOp, Token, HIGHEST_PREC};
 use crate::parse::tokenstream::Cursor;
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Program {
   pub version: Version,
+  /// Paths of imported files, such as `import "lib/ltn.fhdl";`.
+  pub imports: Vec<(String, Span)>,
+  pub bundles: Vec<(Bundle, Span)>,
   pub modules: Vec<(Module, Span)>,
 }
 
 impl Program {
+  /// Parses a program, failing with its first syntax error.
   pub fn parse(tokens: &Cursor) -> Result<Self, CerrSpan> {
-    let version = Version::parse(tokens)?;
+    let (program, errors) = Self::parse_all(tokens);
+    first_error(program, errors)
+  }
+
+  /// Parses a program, recovering from syntax errors. Returns everything that could be parsed,
+  /// along with all syntax errors.
+  pub fn parse_all(tokens: &Cursor) -> (Self, Vec<CerrSpan>) {
+    let mut errors = vec![];
+    let version = Version::parse(tokens).unwrap_or_else(|err| {
+      errors.push(err);
+      skip_to_item(tokens);
+      Version::V2
+    });
+    let mut imports = vec![];
+    let mut bundles = vec![];
     let mut modules = vec![];
-    loop {
-      let peeker = tokens.clone();
-      let Some((token, span)) = peeker.next_or_eof() else {
-        break;
-      };
-      match &token {
+    while let Some((token, span)) = tokens.peek_or_eof() {
+      let mark = tokens.mark();
+      let parsed = match &token {
         Token::Name(name) if name == "module" => {
-          modules.push(Module::parse(tokens)?);
+          Module::parse_all(tokens, &mut errors).map(|v| modules.push(v))
         }
-        _ => return Err(Cerr::UnexpectedToken(vec!["module".into()]).with(span)),
+        Token::Name(name) if name == "bundle" => Bundle::parse(tokens).map(|v| bundles.push(v)),
+        Token::Name(name) if name == "import" => parse_import(tokens).map(|v| imports.push(v)),
+        _ => Err(
+          Cerr::UnexpectedToken(vec!["module".into(), "bundle".into(), "import".into()]).with(span),
+        ),
+      };
+      if let Err(err) = parsed {
+        errors.push(err);
+        tokens.reset(mark + 1);
+        skip_to_item(tokens);
       }
     }
-    Ok(Program { version, modules })
+    let program = Program {
+      version,
+      imports,
+      bundles,
+      modules,
+    };
+    (program, errors)
   }
 }
 
+/// Parses an import such as `import "lib/ltn.fhdl";`, and returns the imported path.
+fn parse_import(tokens: &Cursor) -> Result<(String, Span), CerrSpan> {
+  let start = tokens.next_assert(&Token::Name("import".into()))?;
+  let (path, path_span) = tokens.next_map(|v| match v {
+    Token::String(path) => Ok(path.clone()),
+    _ => Err(Cerr::UnexpectedTokenType("string")),
+  })?;
+  tokens.next_assert(&Token::Semicolon)?;
+  Ok((path, start.union(path_span)))
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Version {
   V2,
@@ -58,7 +104,15 @@ pub struct Module {
 }
 
 impl Module {
+  /// Parses a module, failing with its first syntax error.
   pub fn parse(tokens: &Cursor) -> Result<(Self, Span), CerrSpan> {
+    let mut errors = vec![];
+    let module = Self::parse_all(tokens, &mut errors)?;
+    first_error(module, errors)
+  }
+
+  /// Parses a module, collecting the errors of statements that could be recovered from into `errors`.
+  pub fn parse_all(tokens: &Cursor, errors: &mut Vec<CerrSpan>) -> Result<(Self, Span), CerrSpan> {
     let start_span = tokens.next_assert(&Token::Name("module".into()))?;
     let (name, _) = tokens.next_map(|v| {
       v.get_name()
@@ -66,26 +120,74 @@ impl Module {
         .ok_or(Cerr::UnexpectedTokenType("identifier"))
     })?;
     let ports = parse_list_paren_comma(tokens, PortDecl::parse)?;
-    let stmts = parse_list_brace_semi(tokens, Stmt::parse)?;
+    let stmts = parse_stmt_block(tokens, errors)?;
     tokens.rewind(1);
     let end_span = tokens.next()?.1;
     Ok((Module { name, ports, stmts }, start_span.union(end_span)))
   }
 }
 
+/// A named group of wires, such as `bundle Req { single item; mixed payload; }`.
+/// Ports and wires of a bundle type are flattened into one net per member,
+/// which is referred to as `name.member`.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Bundle {
+  pub name: String,
+  pub members: Vec<BundleMember>,
+}
+
+impl Bundle {
+  pub fn parse(tokens: &Cursor) -> Result<(Self, Span), CerrSpan> {
+    let start_span = tokens.next_assert(&Token::Name("bundle".into()))?;
+    let name = tokens.next_identifier()?.0;
+    let members = parse_list_brace_semi(tokens, BundleMember::parse)?;
+    tokens.rewind(1);
+    let end_span = tokens.next()?.1;
+    Ok((Bundle { name, members }, start_span.union(end_span)))
+  }
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct BundleMember {
+  pub signal_class: NetType,
+  pub name: String,
+}
+
+impl BundleMember {
+  pub fn parse(tokens: &Cursor) -> Result<Self, CerrSpan> {
+    Ok(BundleMember {
+      signal_class: NetType::parse(tokens)?.0,
+      name: tokens.next_identifier()?.0,
+    })
+  }
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct PortDecl {
   pub port_class: PortClass,
   pub signal_class: NetType,
   pub name: String,
+  /// The bundle type of the port, if it has one. Bundle ports are flattened into one
+  /// port per member during transformation, so `signal_class` is unused for them.
+  pub bundle: Option<String>,
 }
 
 impl PortDecl {
   pub fn parse(tokens: &Cursor) -> Result<Self, CerrSpan> {
+    let port_class = PortClass::parse(tokens)?.0;
+    if let Some(bundle) = parse_bundle_type(tokens) {
+      return Ok(PortDecl {
+        port_class,
+        signal_class: NetType::Mixed,
+        name: tokens.next_identifier()?.0,
+        bundle: Some(bundle),
+      });
+    }
     Ok(PortDecl {
-      port_class: PortClass::parse(tokens)?.0,
+      port_class,
       signal_class: NetType::parse(tokens)?.0,
       name: tokens.next_identifier()?.0,
+      bundle: None,
     })
   }
 }
@@ -116,46 +218,164 @@ impl PortClass {
   }
 }
 
+impl Display for PortClass {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      PortClass::In => "in",
+      PortClass::Out => "out",
+      PortClass::InOut => "inout",
+    })
+  }
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Stmt {
   MemDecl {
     name: String,
     signal_class: NetType,
+    /// Value the memory cell starts with, and is reset to.
+    init: Option<i32>,
+    /// Wire that resets the memory cell while it's nonzero.
+    reset: Option<String>,
+  },
+  /// An array of single-signal memory cells, such as `mem single buf[64];`.
+  MemArrayDecl {
+    name: String,
+    signal_class: NetType,
+    len: u32,
   },
   Set {
     name: String,
     assign_type: BinaryOp,
     expr: Expr,
   },
+  /// A read-only lookup table, such as `rom single sizes = [1, 2, 4];`.
+  RomDecl {
+    name: String,
+    signal_class: NetType,
+    data: RomData,
+  },
+  /// A write to one element of a memory array, such as `set buf[addr] = v;`.
+  SetIndexed {
+    name: String,
+    index: Expr,
+    assign_type: BinaryOp,
+    expr: Expr,
+  },
   WireDecl {
     name: String,
     signal_class: NetType,
     expr: Option<Expr>,
   },
+  /// A wire of a bundle type, such as `wire Req r;`. It may be initialized from
+  /// another bundle, which connects the members one by one.
+  BundleWireDecl {
+    name: String,
+    bundle: String,
+    expr: Option<Expr>,
+  },
   ModuleInst {
     module: String,
-    args: Vec<Expr>,
+    args: ModuleArgs,
   },
   Trigger {
-    watching: String,
+    /// The condition, which is usually just an identifier.
+    watching: Expr,
     trigger_kind: TriggerKind,
+    /// If set, the trigger fires only on the first tick its condition holds.
+    once: bool,
     statements: Vec<(Stmt, Span)>,
+    /// Statements that execute when the trigger does not fire.
+    else_statements: Vec<(Stmt, Span)>,
   },
 }
 
 impl Stmt {
+  /// Parses a statement, failing with its first syntax error.
   pub fn parse(tokens: &Cursor) -> Result<(Self, Span), CerrSpan> {
+    let mut errors = vec![];
+    let stmt = Self::parse_all(tokens, &mut errors)?;
+    first_error(stmt, errors)
+  }
+
+  /// Parses a statement, collecting the errors of nested statements that could be recovered
+  /// from into `errors`.
+  pub fn parse_all(tokens: &Cursor, errors: &mut Vec<CerrSpan>) -> Result<(Self, Span), CerrSpan> {
     let (kw, start) = tokens.next()?;
     Ok(match kw {
       Token::Name(kw) if kw == "mem" => {
         let signal_class = NetType::parse(tokens)?.0;
         let name = tokens.next_identifier()?.0;
+        if tokens.peek()?.0 == &Token::LBracket {
+          tokens.next()?;
+          let len = tokens
+            .next_map(|v| {
+              v.get_literal()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or(Cerr::UnexpectedToken(vec!["literal".into()]))
+            })?
+            .0;
+          tokens.next_assert(&Token::RBracket)?;
+          let end = tokens.peek_assert(&Token::Semicolon)?;
+          return Ok((
+            Stmt::MemArrayDecl {
+              name,
+              signal_class,
+              len,
+            },
+            start.union(end),
+          ));
+        }
+        let init = if tokens.peek()?.0 == &Token::Op(BinaryOp::Assign) {
+          tokens.next()?;
+          Some(parse_signed_literal(tokens)?)
+        } else {
+          None
+        };
+        let reset = if matches!(tokens.peek()?.0, Token::Name(kw) if kw == "reset") {
+          tokens.next()?;
+          Some(tokens.next_identifier()?.0)
+        } else {
+          None
+        };
         let end = tokens.peek_assert(&Token::Semicolon)?;
-        (Stmt::MemDecl { name, signal_class }, start.union(end))
+        (
+          Stmt::MemDecl {
+            name,
+            signal_class,
+            init,
+            reset,
+          },
+          start.union(end),
+        )
+      }
+
+      Token::Name(kw) if kw == "rom" => {
+        let signal_class = NetType::parse(tokens)?.0;
+        let name = tokens.next_identifier()?.0;
+        tokens.next_assert(&Token::Op(BinaryOp::Assign))?;
+        let data = RomData::parse(tokens)?;
+        let end = tokens.peek_assert(&Token::Semicolon)?;
+        (
+          Stmt::RomDecl {
+            name,
+            signal_class,
+            data,
+          },
+          start.union(end),
+        )
       }
 
       Token::Name(kw) if kw == "set" => {
         let name = tokens.next_identifier()?.0;
+        let index = if tokens.peek()?.0 == &Token::LBracket {
+          tokens.next()?;
+          let index = Expr::parse(tokens)?;
+          tokens.next_assert(&Token::RBracket)?;
+          Some(index)
+        } else {
+          None
+        };
         let assign_type = tokens
           .next_map(|v| {
             v.get_op()
@@ -164,17 +384,34 @@ impl Stmt {
           .0;
         let expr = Expr::parse(tokens)?;
         let end = tokens.peek_assert(&Token::Semicolon)?;
-        (
-          Stmt::Set {
+        let stmt = match index {
+          Some(index) => Stmt::SetIndexed {
             name,
+            index,
             assign_type,
             expr,
           },
-          start.union(end),
-        )
+          None => Stmt::Set {
+            name,
+            assign_type,
+            expr,
+          },
+        };
+        (stmt, start.union(end))
       }
 
       Token::Name(kw) if kw == "wire" => {
+        if let Some(bundle) = parse_bundle_type(tokens) {
+          let name = tokens.next_identifier()?.0;
+          let expr = if tokens.peek()?.0 != &Token::Semicolon {
+            tokens.next_assert(&Token::Op(BinaryOp::Assign))?;
+            Some(Expr::parse(tokens)?)
+          } else {
+            None
+          };
+          let end = tokens.peek_assert(&Token::Semicolon)?;
+          return Ok((Stmt::BundleWireDecl { name, bundle, expr }, start.union(end)));
+        }
         let signal_class = NetType::parse(tokens)?.0;
         let name = tokens.next_identifier()?.0;
         let (maybe_assign, _) = tokens.peek()?;
@@ -205,21 +442,33 @@ impl Stmt {
 
       Token::Name(kw) if kw == "inst" => {
         let name = tokens.next_identifier()?.0;
-        let args = parse_list_paren_comma(tokens, Expr::parse)?;
+        let args = ModuleArgs::parse(tokens)?;
         let end = tokens.peek_assert(&Token::Semicolon)?;
         (Stmt::ModuleInst { module: name, args }, start.union(end))
       }
 
       Token::Name(kw) if kw == "trigger" => {
-        let name = tokens.next_identifier()?.0;
+        let watching = Expr::parse(tokens)?;
         let trigger_kind = TriggerKind::parse(tokens)?.0;
-        let stmts = parse_list_brace_semi(tokens, Stmt::parse)?;
+        let once = matches!(tokens.peek()?.0, Token::Name(kw) if kw == "once");
+        if once {
+          tokens.next()?;
+        }
+        let stmts = parse_stmt_block(tokens, errors)?;
+        let else_stmts = if matches!(tokens.peek()?.0, Token::Name(kw) if kw == "else") {
+          tokens.next()?;
+          parse_stmt_block(tokens, errors)?
+        } else {
+          vec![]
+        };
         let end = tokens.peek_assert(&Token::Semicolon)?;
         (
           Stmt::Trigger {
-            watching: name,
+            watching,
             trigger_kind,
+            once,
             statements: stmts,
+            else_statements: else_stmts,
           },
           start.union(end),
         )
@@ -229,6 +478,7 @@ impl Stmt {
         return Err(
           Cerr::UnexpectedToken(vec![
             "mem".into(),
+            "rom".into(),
             "set".into(),
             "wire".into(),
             "inst".into(),
@@ -241,6 +491,81 @@ impl Stmt {
   }
 }
 
+/// Arguments of a module instantiation, which are either all positional,
+/// or all connected by port name as in `inst counter(reset: r, output: o);`.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ModuleArgs {
+  Positional(Vec<Expr>),
+  Named(Vec<(String, Expr)>),
+}
+
+impl ModuleArgs {
+  pub fn parse(tokens: &Cursor) -> Result<Self, CerrSpan> {
+    let peeker = tokens.clone();
+    peeker.next_assert(&Token::LParen)?;
+    let named = peeker.next_identifier().is_ok() && peeker.peek()?.0 == &Token::Colon;
+    if !named {
+      return Ok(ModuleArgs::Positional(parse_list_paren_comma(tokens, Expr::parse)?));
+    }
+    let args = parse_list_paren_comma(tokens, |tokens| {
+      let port = tokens.next_identifier()?.0;
+      tokens.next_assert(&Token::Colon)?;
+      Ok((port, Expr::parse(tokens)?))
+    })?;
+    Ok(ModuleArgs::Named(args))
+  }
+}
+
+/// Contents of a ROM, either written inline or read from a file.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum RomData {
+  Inline(Vec<RomEntry>),
+  /// Path to a CSV or JSON file. These are replaced by their contents after parsing.
+  File(String),
+}
+
+impl RomData {
+  pub fn parse(tokens: &Cursor) -> Result<Self, CerrSpan> {
+    if let (Token::String(path), _) = tokens.peek()? {
+      let path = path.clone();
+      tokens.next()?;
+      return Ok(RomData::File(path));
+    }
+    let entries = parse_list(tokens, RomEntry::parse, &Token::LBracket, &Token::RBracket, &Token::Comma)?;
+    Ok(RomData::Inline(entries))
+  }
+}
+
+/// One entry of a ROM. Entries of mixed ROMs are lists of signals and their values.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum RomEntry {
+  Single(i32),
+  Mixed(Vec<(String, i32)>),
+}
+
+impl RomEntry {
+  pub fn parse(tokens: &Cursor) -> Result<Self, CerrSpan> {
+    if tokens.peek()?.0 != &Token::LBrace {
+      return Ok(RomEntry::Single(parse_signed_literal(tokens)?));
+    }
+    let signals = parse_list(
+      tokens,
+      |tokens| {
+        let (signal, _) = tokens.next_map(|v| match v {
+          Token::String(s) => Ok(s.clone()),
+          _ => Err(Cerr::UnexpectedToken(vec!["string".into()])),
+        })?;
+        tokens.next_assert(&Token::Op(BinaryOp::Assign))?;
+        Ok((signal, parse_signed_literal(tokens)?))
+      },
+      &Token::LBrace,
+      &Token::RBrace,
+      &Token::Comma,
+    )?;
+    Ok(RomEntry::Mixed(signals))
+  }
+}
+
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum TriggerKind {
   Increasing,
@@ -270,6 +595,17 @@ impl TriggerKind {
   }
 }
 
+impl Display for TriggerKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      TriggerKind::Increasing => "increasing",
+      TriggerKind::Decreasing => "decreasing",
+      TriggerKind::Changed => "changed",
+      TriggerKind::Raw => "raw",
+    })
+  }
+}
+
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum NetType {
   Single,
@@ -288,30 +624,65 @@ impl NetType {
   }
 }
 
+impl Display for NetType {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      NetType::Single => "single",
+      NetType::Mixed => "mixed",
+    })
+  }
+}
+
+/// Parses the name of a bundle type, if the next token is a name other than a signal class.
+fn parse_bundle_type(tokens: &Cursor) -> Option<String> {
+  match tokens.peek() {
+    Ok((Token::Name(name), _)) if name != "single" && name != "mixed" => {
+      let name = name.clone();
+      // unwrap: the token was just peeked
+      tokens.next().unwrap();
+      Some(name)
+    }
+    _ => None,
+  }
+}
+
+/// An expression. Every node keeps the span it was parsed from, so that errors can point at
+/// the exact operand rather than the whole statement.
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Expr {
   Identifier {
     name: String,
+    span: Span,
   },
   Literal {
     val: i32,
+    span: Span,
   },
   StringLiteral {
     str: String,
+    span: Span,
   },
   FnCall {
     func: String,
     args: Vec<Expr>,
+    span: Span,
+  },
+  /// Reads one element of a memory array.
+  Index {
+    name: String,
+    index: Box<Expr>,
+    span: Span,
   },
   BinaryOps {
     car: Box<Expr>,
     cdr: Vec<(BinaryOp, Expr)>,
+    span: Span,
   },
 }
 
 impl Expr {
   pub fn parse(tokens: &Cursor) -> Result<Self, CerrSpan> {
-    Self::parse_with_prec(tokens, 6)
+    Self::parse_with_prec(tokens, HIGHEST_PREC)
   }
 
   pub fn parse_with_prec(tokens: &Cursor, prec: u32) -> Result<Self, CerrSpan> {
@@ -319,32 +690,44 @@ impl Expr {
       let (t, span) = tokens.next()?;
       match t {
         Token::Name(name) => {
-          // disambiguate between function call and variable use
+          // disambiguate between function call, array read and variable use
           if matches!(tokens.peek_or_eof(), Some((&Token::LParen, _))) {
             // function call
             let args = parse_list_paren_comma(tokens, Expr::parse)?;
             Expr::FnCall {
               func: name.clone(),
               args,
+              span: span.union(tokens.prev_span()),
+            }
+          } else if matches!(tokens.peek_or_eof(), Some((&Token::LBracket, _))) {
+            // array read
+            tokens.next()?;
+            let index = Expr::parse(tokens)?;
+            let end = tokens.next_assert(&Token::RBracket)?;
+            Expr::Index {
+              name: name.clone(),
+              index: Box::new(index),
+              span: span.union(end),
             }
           } else {
             // peeked token is binary op or semicolon or something, anyways end of expression
             // therefore, this is a variable
-            Expr::Identifier { name: name.clone() }
+            Expr::Identifier { name: name.clone(), span }
           }
         }
         Token::Literal(i) => {
           // literal expression
-          Expr::Literal { val: *i }
+          Expr::Literal { val: *i, span }
         }
         Token::String(s) => {
           // string literal
-          Expr::StringLiteral { str: s.clone() }
+          Expr::StringLiteral { str: s.clone(), span }
         }
         Token::LParen => {
-          // braced expression
-          let expr = Expr::parse(tokens)?;
-          tokens.next_assert(&Token::RParen)?;
+          // braced expression, which includes the braces in its span
+          let mut expr = Expr::parse(tokens)?;
+          let end = tokens.next_assert(&Token::RParen)?;
+          *expr.span_mut() = span.union(end);
           expr
         }
         _ => return Err(Cerr::InvalidExpr.with(span)),
@@ -358,30 +741,66 @@ impl Expr {
         let expr = Expr::parse_with_prec(tokens, prec - 1)?;
         cdr.push((op, expr));
       }
-      if cdr.is_empty() {
-        car
-      } else {
+      if let Some((_, last)) = cdr.last() {
+        let span = car.span().union(last.span());
         Expr::BinaryOps {
           car: Box::new(car),
           cdr,
+          span,
         }
+      } else {
+        car
       }
     })
   }
 
   pub fn into_ident(self) -> Option<String> {
     match self {
-      Expr::Identifier { name } => Some(name),
+      Expr::Identifier { name, .. } => Some(name),
       _ => None,
     }
   }
 
   pub fn as_ident(&self) -> Option<&String> {
     match self {
-      Expr::Identifier { name } => Some(name),
+      Expr::Identifier { name, .. } => Some(name),
       _ => None,
     }
   }
+
+  pub fn span(&self) -> Span {
+    match self {
+      Expr::Identifier { span, .. }
+      | Expr::Literal { span, .. }
+      | Expr::StringLiteral { span, .. }
+      | Expr::FnCall { span, .. }
+      | Expr::Index { span, .. }
+      | Expr::BinaryOps { span, .. } => *span,
+    }
+  }
+
+  fn span_mut(&mut self) -> &mut Span {
+    match self {
+      Expr::Identifier { span, .. }
+      | Expr::Literal { span, .. }
+      | Expr::StringLiteral { span, .. }
+      | Expr::FnCall { span, .. }
+      | Expr::Index { span, .. }
+      | Expr::BinaryOps { span, .. } => span,
+    }
+  }
+}
+
+/// Parses an integer literal with an optional minus sign in front.
+fn parse_signed_literal(tokens: &Cursor) -> Result<i32, CerrSpan> {
+  let negative = tokens.peek()?.0 == &Token::Op(BinaryOp::Sub);
+  if negative {
+    tokens.next()?;
+  }
+  let val = tokens
+    .next_map(|v| v.get_literal().ok_or(Cerr::UnexpectedToken(vec!["literal".into()])))?
+    .0;
+  Ok(if negative { val.wrapping_neg() } else { val })
 }
 
 fn parse_list_paren_comma<T>(
@@ -391,6 +810,87 @@ fn parse_list_paren_comma<T>(
   parse_list(tokens, f, &Token::LParen, &Token::RParen, &Token::Comma)
 }
 
+/// Parses statements separated by `;` within braces. A statement with a syntax error is skipped,
+/// and its error is collected into `errors`. Errors in the braces themselves can't be recovered
+/// from, and fail the whole block.
+fn parse_stmt_block(tokens: &Cursor, errors: &mut Vec<CerrSpan>) -> Result<Vec<(Stmt, Span)>, CerrSpan> {
+  tokens.next_assert(&Token::LBrace)?;
+  let mut stmts = vec![];
+  loop {
+    let (token, span) = tokens.peek()?;
+    if token == &Token::RBrace {
+      tokens.next()?;
+      return Ok(stmts);
+    }
+    if is_item_keyword(token) {
+      // the block was never closed, so the item belongs to the top level
+      return Err(Cerr::UnexpectedToken(vec!["}".into()]).with(span));
+    }
+    let mark = tokens.mark();
+    let parsed = Stmt::parse_all(tokens, errors).and_then(|stmt| {
+      let (token, span) = tokens.peek()?;
+      match token {
+        Token::Semicolon => {
+          tokens.next()?;
+        }
+        Token::RBrace => {}
+        _ => return Err(Cerr::UnexpectedToken(vec!["}".into(), ";".into()]).with(span)),
+      }
+      Ok(stmt)
+    });
+    match parsed {
+      Ok(stmt) => stmts.push(stmt),
+      Err(err) => {
+        errors.push(err);
+        tokens.reset(mark);
+        skip_stmt(tokens);
+      }
+    }
+  }
+}
+
+/// Skips the rest of a statement with a syntax error, up to and including its `;`.
+/// Stops before the `}` that closes the block the statement is in.
+fn skip_stmt(tokens: &Cursor) {
+  let mut depth = 0usize;
+  while let Some((token, _)) = tokens.peek_or_eof() {
+    match token {
+      Token::Semicolon if depth == 0 => {
+        tokens.next_or_eof();
+        return;
+      }
+      Token::RBrace if depth == 0 => return,
+      Token::RBrace => depth -= 1,
+      Token::LBrace => depth += 1,
+      token if is_item_keyword(token) => return,
+      _ => {}
+    }
+    tokens.next_or_eof();
+  }
+}
+
+/// Skips to the next top level item after a syntax error.
+fn skip_to_item(tokens: &Cursor) {
+  while let Some((token, _)) = tokens.peek_or_eof() {
+    if is_item_keyword(token) {
+      return;
+    }
+    tokens.next_or_eof();
+  }
+}
+
+fn is_item_keyword(token: &Token) -> bool {
+  matches!(token, Token::Name(kw) if kw == "module" || kw == "bundle" || kw == "import")
+}
+
+/// Turns the result of a recovering parse into the result of a parse that fails on the first error.
+fn first_error<T>(parsed: T, errors: Vec<CerrSpan>) -> Result<T, CerrSpan> {
+  match errors.into_iter().next() {
+    Some(err) => Err(err),
+    None => Ok(parsed),
+  }
+}
+
 fn parse_list_brace_semi<T>(
   tokens: &Cursor,
   f: impl FnMut(&Cursor) -> Result<T, CerrSpan>,
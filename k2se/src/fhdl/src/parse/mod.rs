@@ -2,6 +2,10 @@
 
 pub mod ast;
 pub mod iter_with_pos;
+pub mod printer;
+pub mod project;
+pub mod rom;
 pub mod span;
+pub mod stdlib;
 pub mod tokenizer;
 pub mod tokenstream;
@@ -0,0 +1,414 @@
+//! Prints an AST back to source, in the canonical style that `fhdl fmt` formats files to.
+//!
+//! The AST doesn't keep comments, so they're taken from the tokens of the file and put back by
+//! position: a comment on the last line of a statement stays after it, and every other comment goes
+//! before the next statement, bundle member or top level item. Comments within something that is
+//! always written on one line, such as the ports of a module, move up before it. Blank lines
+//! between statements and comments are kept, but never more than one in a row.
+
+use crate::err::CerrSpan;
+use crate::parse::ast::{Bundle, Expr, Module, ModuleArgs, PortDecl, Program, RomData, RomEntry, Stmt};
+use crate::parse::span::{Pos, Span, WithSpan};
+use crate::parse::tokenizer::{tokenize, Token};
+use crate::parse::tokenstream::TokenStream;
+use std::collections::VecDeque;
+
+const INDENT: &str = "  ";
+
+/// Formats the source of a file, keeping its comments. Fails with the first syntax error.
+pub fn format_source(src: &str) -> Result<String, CerrSpan> {
+  let tokens = tokenize(src.chars()).collect::<Result<Vec<_>, _>>()?;
+  let program = Program::parse(&TokenStream::from_tokens(tokens.clone()).begin())?;
+  Ok(Printer::new(&tokens).program(&program))
+}
+
+/// Prints a program that wasn't parsed from source, and so has no comments.
+pub fn print_program(program: &Program) -> String {
+  Printer::new(&[]).program(program)
+}
+
+#[derive(Debug)]
+struct Printer<'a> {
+  out: String,
+  indent: usize,
+  /// Tokens of the source other than comments, for positions that the AST doesn't keep, such as
+  /// those of braces.
+  tokens: Vec<&'a WithSpan<Token>>,
+  /// Comments that haven't been written yet, in order.
+  comments: VecDeque<(Span, &'a str)>,
+  /// The source line of what was written last, to keep blank lines.
+  line: u32,
+}
+
+/// A top level item, to print items in the order of the source.
+enum Item<'a> {
+  Import(&'a str),
+  Bundle(&'a Bundle),
+  Module(&'a Module),
+}
+
+impl<'a> Printer<'a> {
+  fn new(tokens: &'a [WithSpan<Token>]) -> Self {
+    let comments = tokens
+      .iter()
+      .filter_map(|v| match &v.t {
+        Token::Comment(text) => Some((v.span, text.as_str())),
+        _ => None,
+      })
+      .collect();
+    Printer {
+      out: String::new(),
+      indent: 0,
+      tokens: tokens.iter().filter(|v| !matches!(v.t, Token::Comment(_))).collect(),
+      comments,
+      line: 0,
+    }
+  }
+
+  fn program(mut self, program: &Program) -> String {
+    let semicolon = self.find(Pos::new(0, 0), &Token::Semicolon);
+    self.write_line("version 2;", semicolon.line, semicolon.line);
+
+    let imports = program.imports.iter().map(|(v, span)| (*span, Item::Import(v)));
+    let bundles = program.bundles.iter().map(|(v, span)| (*span, Item::Bundle(v)));
+    let modules = program.modules.iter().map(|(v, span)| (*span, Item::Module(v)));
+    let mut items = imports.chain(bundles).chain(modules).collect::<Vec<_>>();
+    items.sort_by_key(|(span, _)| span.start);
+    let mut after_import = false;
+    for (span, item) in items {
+      // imports are grouped together, and everything else is separated by a blank line
+      let import = matches!(item, Item::Import(_));
+      if !(import && after_import) {
+        self.blank_line();
+      }
+      after_import = import;
+      match item {
+        Item::Import(path) => {
+          let semicolon = self.find(span.end, &Token::Semicolon);
+          self.write_line(&format!("import \"{}\";", path), span.start.line, semicolon.line);
+        }
+        Item::Bundle(bundle) => self.bundle(bundle, span),
+        Item::Module(module) => self.module(module, span),
+      }
+    }
+    self.comments_before(Pos::new(u32::MAX, 0));
+    self.out
+  }
+
+  fn bundle(&mut self, bundle: &Bundle, span: Span) {
+    let open = self.find(span.start, &Token::LBrace);
+    self.comments_before(Pos::new(open.line, 0));
+    self.start_line(span.start.line);
+    self.out.push_str(&format!("bundle {}", bundle.name));
+    let first = (!bundle.members.is_empty()).then(|| self.find(open, &Token::Semicolon).min(span.end).line);
+    if self.open_block(first, open, span.end) {
+      let mut pos = open;
+      for member in &bundle.members {
+        // the last member doesn't need a semicolon
+        let end = self.find(pos, &Token::Semicolon).min(span.end);
+        self.write_line(&format!("{} {};", member.signal_class, member.name), end.line, end.line);
+        pos = Pos::new(end.line, end.col + 1);
+      }
+      self.close_block(span.end);
+    }
+    self.end_line(span.end.line);
+  }
+
+  fn module(&mut self, module: &Module, span: Span) {
+    let open = self.find(span.start, &Token::LBrace);
+    self.comments_before(Pos::new(open.line, 0));
+    self.start_line(span.start.line);
+    let ports = module.ports.iter().map(print_port).collect::<Vec<_>>();
+    self.out.push_str(&format!("module {}({})", module.name, ports.join(", ")));
+    if self.open_block(first_line(&module.stmts), open, span.end) {
+      self.stmts(&module.stmts);
+      self.close_block(span.end);
+    }
+    self.end_line(span.end.line);
+  }
+
+  fn stmts(&mut self, stmts: &[(Stmt, Span)]) {
+    for (stmt, span) in stmts {
+      match stmt {
+        Stmt::Trigger {
+          watching,
+          trigger_kind,
+          once,
+          statements,
+          else_statements,
+        } => {
+          let open = self.find(watching.span().end, &Token::LBrace);
+          self.comments_before(Pos::new(open.line, 0));
+          self.start_line(span.start.line);
+          let watching = match watching {
+            Expr::BinaryOps { .. } => format!("({})", print_expr(watching)),
+            _ => print_expr(watching),
+          };
+          let once = if *once { " once" } else { "" };
+          self.out.push_str(&format!("trigger {} {}{}", watching, trigger_kind, once));
+          let then_end = statements.last().map_or(open, |(_, span)| span.end);
+          let close = self.find(then_end, &Token::RBrace);
+          if self.open_block(first_line(statements), open, close) {
+            self.stmts(statements);
+            self.close_block(close);
+          }
+          let else_token = self
+            .tokens
+            .iter()
+            .find(|v| v.span.start > close)
+            .filter(|v| matches!(&v.t, Token::Name(kw) if kw == "else"));
+          let else_pos = else_token.map_or(close, |v| v.span.start);
+          let else_open = self.find(else_pos, &Token::LBrace);
+          let else_end = else_statements.last().map_or(else_open, |(_, span)| span.end);
+          let else_close = self.find(else_end, &Token::RBrace);
+          // an empty else block is only kept for its comments
+          let has_comments = self.comments.front().is_some_and(|(span, _)| span.start < else_close);
+          if !else_statements.is_empty() || (else_token.is_some() && has_comments) {
+            if self.comments.front().is_some_and(|(span, _)| span.start < else_pos) {
+              // a comment after the `}` stays on its line, and the `else` moves to the next one
+              self.end_line(close.line);
+              self.comments_before(else_pos);
+              self.out.push_str(&INDENT.repeat(self.indent));
+              self.out.push_str("else");
+            } else {
+              self.out.push_str(" else");
+            }
+            if self.open_block(first_line(else_statements), else_open, else_close) {
+              self.stmts(else_statements);
+              self.close_block(else_close);
+            }
+          }
+          self.out.push(';');
+          self.end_line(span.end.line);
+        }
+        _ => self.write_line(&format!("{};", print_stmt(stmt)), span.start.line, span.end.line),
+      }
+    }
+  }
+
+  /// Finds where the first `token` at or after `pos` starts. Programs that weren't parsed from
+  /// source have no tokens, and get `pos` back.
+  fn find(&self, pos: Pos, token: &Token) -> Pos {
+    self
+      .tokens
+      .iter()
+      .find(|v| v.span.start >= pos && &v.t == token)
+      .map_or(pos, |v| v.span.start)
+  }
+
+  /// Writes a line for something from line `start` to line `end` of the source. Comments on the
+  /// lines in between move before it.
+  fn write_line(&mut self, text: &str, start: u32, end: u32) {
+    self.comments_before(Pos::new(end, 0));
+    self.start_line(start);
+    self.out.push_str(text);
+    self.end_line(end);
+  }
+
+  /// Indents a new line for something that starts on `line` of the source, after a blank line if
+  /// the source has one before it.
+  fn start_line(&mut self, line: u32) {
+    if line > self.line + 1 {
+      self.blank_line();
+    }
+    self.out.push_str(&INDENT.repeat(self.indent));
+  }
+
+  /// Adds a blank line, unless it would start the file or a block, or follow another blank line.
+  fn blank_line(&mut self) {
+    if !(self.out.is_empty() || self.out.ends_with("\n\n") || self.out.ends_with("{\n")) {
+      self.out.push('\n');
+    }
+  }
+
+  /// Ends the current line, which ends on `line` of the source, along with the comment after it.
+  fn end_line(&mut self, line: u32) {
+    if let Some(&(span, text)) = self.comments.front() {
+      if span.start.line == line {
+        self.comments.pop_front();
+        self.out.push_str(&format!(" //{}", text.trim_end()));
+      }
+    }
+    self.out.push('\n');
+    self.line = line;
+  }
+
+  /// Writes the comments before `pos` on lines of their own.
+  fn comments_before(&mut self, pos: Pos) {
+    while let Some(&(span, text)) = self.comments.front() {
+      if span.start >= pos {
+        break;
+      }
+      self.comments.pop_front();
+      self.start_line(span.start.line);
+      self.out.push_str(&format!("//{}\n", text.trim_end()));
+      self.line = span.end.line;
+    }
+  }
+
+  /// Opens a block after the text on the current line, whose braces are at `open` and `close` in
+  /// the source, and whose contents start on line `first`. Empty blocks without comments are
+  /// written as `{}` on the same line. Returns whether the block was left open, in which case
+  /// [`Self::close_block`] closes it.
+  fn open_block(&mut self, first: Option<u32>, open: Pos, close: Pos) -> bool {
+    if first.is_none() && self.comments.front().is_none_or(|(span, _)| span.start >= close) {
+      self.out.push_str(" {}");
+      return false;
+    }
+    self.out.push_str(" {");
+    if first == Some(open.line) {
+      // a comment at the end of the line belongs to what's after the brace
+      self.out.push('\n');
+      self.line = open.line;
+    } else {
+      self.end_line(open.line);
+    }
+    self.indent += 1;
+    true
+  }
+
+  fn close_block(&mut self, close: Pos) {
+    self.comments_before(close);
+    self.indent -= 1;
+    self.out.push_str(&INDENT.repeat(self.indent));
+    self.out.push('}');
+  }
+}
+
+fn first_line(stmts: &[(Stmt, Span)]) -> Option<u32> {
+  stmts.first().map(|(_, span)| span.start.line)
+}
+
+/// Prints a statement other than a trigger, without its semicolon.
+fn print_stmt(stmt: &Stmt) -> String {
+  match stmt {
+    Stmt::MemDecl {
+      name,
+      signal_class,
+      init,
+      reset,
+    } => {
+      let mut out = format!("mem {} {}", signal_class, name);
+      if let Some(init) = init {
+        out.push_str(&format!(" = {}", init));
+      }
+      if let Some(reset) = reset {
+        out.push_str(&format!(" reset {}", reset));
+      }
+      out
+    }
+    Stmt::MemArrayDecl {
+      name,
+      signal_class,
+      len,
+    } => format!("mem {} {}[{}]", signal_class, name, len),
+    Stmt::Set {
+      name,
+      assign_type,
+      expr,
+    } => format!("set {} {} {}", name, assign_type, print_expr(expr)),
+    Stmt::RomDecl {
+      name,
+      signal_class,
+      data,
+    } => format!("rom {} {} = {}", signal_class, name, print_rom_data(data)),
+    Stmt::SetIndexed {
+      name,
+      index,
+      assign_type,
+      expr,
+    } => format!("set {}[{}] {} {}", name, print_expr(index), assign_type, print_expr(expr)),
+    Stmt::WireDecl {
+      name,
+      signal_class,
+      expr,
+    } => match expr {
+      Some(expr) => format!("wire {} {} = {}", signal_class, name, print_expr(expr)),
+      None => format!("wire {} {}", signal_class, name),
+    },
+    Stmt::BundleWireDecl { name, bundle, expr } => match expr {
+      Some(expr) => format!("wire {} {} = {}", bundle, name, print_expr(expr)),
+      None => format!("wire {} {}", bundle, name),
+    },
+    Stmt::ModuleInst { module, args } => {
+      let args = match args {
+        ModuleArgs::Positional(args) => args.iter().map(print_expr).collect::<Vec<_>>(),
+        ModuleArgs::Named(args) => args
+          .iter()
+          .map(|(port, v)| format!("{}: {}", port, print_expr(v)))
+          .collect(),
+      };
+      format!("inst {}({})", module, args.join(", "))
+    }
+    Stmt::Trigger { .. } => unreachable!("triggers are printed over several lines"),
+  }
+}
+
+fn print_port(port: &PortDecl) -> String {
+  match &port.bundle {
+    Some(bundle) => format!("{} {} {}", port.port_class, bundle, port.name),
+    None => format!("{} {} {}", port.port_class, port.signal_class, port.name),
+  }
+}
+
+fn print_rom_data(data: &RomData) -> String {
+  match data {
+    RomData::Inline(entries) => {
+      let entries = entries
+        .iter()
+        .map(|entry| match entry {
+          RomEntry::Single(v) => v.to_string(),
+          RomEntry::Mixed(signals) => {
+            let signals = signals
+              .iter()
+              .map(|(signal, v)| format!("\"{}\" = {}", signal, v))
+              .collect::<Vec<_>>();
+            format!("{{{}}}", signals.join(", "))
+          }
+        })
+        .collect::<Vec<_>>();
+      format!("[{}]", entries.join(", "))
+    }
+    RomData::File(path) => format!("\"{}\"", path),
+  }
+}
+
+fn print_expr(expr: &Expr) -> String {
+  match expr {
+    Expr::Identifier { name, .. } => name.clone(),
+    // literals can't be negative in expressions, but hex literals can overflow into negatives
+    Expr::Literal { val, .. } if *val < 0 => format!("0x{:X}", *val as u32),
+    Expr::Literal { val, .. } => val.to_string(),
+    Expr::StringLiteral { str, .. } => format!("\"{}\"", str),
+    Expr::FnCall { func, args, .. } => {
+      let args = args.iter().map(print_expr).collect::<Vec<_>>();
+      format!("{}({})", func, args.join(", "))
+    }
+    Expr::Index { name, index, .. } => format!("{}[{}]", name, print_expr(index)),
+    Expr::BinaryOps { car, cdr, .. } => {
+      let prec = precedence(expr);
+      let mut out = print_operand(car, prec);
+      for (op, v) in cdr {
+        out.push_str(&format!(" {} {}", op, print_operand(v, prec)));
+      }
+      out
+    }
+  }
+}
+
+/// Prints an operand of operators of precedence `prec`. It's put in parentheses if its own
+/// operators don't bind tighter, since it would be parsed differently otherwise.
+fn print_operand(expr: &Expr, prec: u32) -> String {
+  match expr {
+    Expr::BinaryOps { .. } if precedence(expr) >= prec => format!("({})", print_expr(expr)),
+    _ => print_expr(expr),
+  }
+}
+
+/// The precedence of the operators of an expression, which is 0 for anything but operators.
+fn precedence(expr: &Expr) -> u32 {
+  match expr {
+    Expr::BinaryOps { cdr, .. } => cdr.first().map_or(0, |(op, _)| op.precedence()),
+    _ => 0,
+  }
+}
@@ -0,0 +1,187 @@
+//! Loads programs that are split across several files.
+//!
+//! A file imports another with `import "lib/ltn.fhdl";`. The path is looked up relative to the
+//! importing file first, and then in each directory of the search path. Every file is only
+//! loaded once, no matter how often it's imported, and modules of all files share one namespace.
+//! Imports that aren't found anywhere else may refer to the built-in standard library.
+
+use crate::diagnostic::Diagnostic;
+use crate::err::{Cerr, CerrSpan};
+use crate::parse::ast::Program;
+use crate::parse::rom::load_rom_files;
+use crate::parse::span::{FileId, Span};
+use crate::parse::stdlib;
+use crate::parse::tokenizer::tokenize;
+use crate::parse::tokenstream::TokenStream;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct SourceFile {
+  pub path: PathBuf,
+  pub src: String,
+  /// The parsed file, or `None` if it couldn't be read or tokenized. A file with syntax errors
+  /// has everything that could be parsed around them.
+  pub program: Option<Program>,
+}
+
+/// A root file and every file it imports, directly or indirectly.
+#[derive(Debug)]
+pub struct Project {
+  /// The loaded files, indexed by [`FileId`]. The root file is always file 0.
+  pub files: Vec<SourceFile>,
+}
+
+impl Project {
+  /// Loads the root file and all of its imports. Errors don't stop loading,
+  /// so that as many as possible are reported at once.
+  pub fn load(root: &Path, search_path: &[PathBuf]) -> (Project, Vec<CerrSpan>) {
+    Project::load_with(root, search_path, &HashMap::new())
+  }
+
+  /// Like [`Project::load`], but files in `open` are read from there instead of from disk,
+  /// such as files with unsaved changes in an editor.
+  pub fn load_with(root: &Path, search_path: &[PathBuf], open: &HashMap<PathBuf, String>) -> (Project, Vec<CerrSpan>) {
+    let open = open
+      .iter()
+      .map(|(path, src)| (path.canonicalize().unwrap_or_else(|_| path.clone()), src))
+      .collect();
+    let mut loader = Loader {
+      search_path,
+      open,
+      files: vec![],
+      ids: HashMap::new(),
+      stack: vec![],
+      errors: vec![],
+    };
+    loader.load(root.to_path_buf());
+    (Project { files: loader.files }, loader.errors)
+  }
+
+  /// The programs of all files that were parsed successfully, along with their file ids.
+  pub fn programs(&self) -> impl Iterator<Item = (FileId, &Program)> {
+    self.files
+      .iter()
+      .enumerate()
+      .filter_map(|(id, file)| Some((id, file.program.as_ref()?)))
+  }
+
+  /// Formats an error with the source lines of the file it's in.
+  pub fn format_err(&self, err: &CerrSpan) -> String {
+    self.render(&Diagnostic::error(err), false)
+  }
+
+  /// Renders a diagnostic with the source code of its file, see [`Diagnostic::render`].
+  pub fn render(&self, diag: &Diagnostic, color: bool) -> String {
+    match self.files.get(diag.file) {
+      Some(file) => diag.render(&file.path.display().to_string(), &file.src, color),
+      None => diag.render("<unknown>", "", color),
+    }
+  }
+
+  /// Serializes a diagnostic along with the path of its file, see [`Diagnostic::to_json`].
+  pub fn render_json(&self, diag: &Diagnostic) -> String {
+    match self.files.get(diag.file) {
+      Some(file) => diag.to_json(&file.path.display().to_string()),
+      None => diag.to_json("<unknown>"),
+    }
+  }
+}
+
+struct Loader<'a> {
+  search_path: &'a [PathBuf],
+  /// Sources that are used instead of the files at their canonical paths
+  open: HashMap<PathBuf, &'a String>,
+  files: Vec<SourceFile>,
+  /// Maps canonical paths to the files loaded from them
+  ids: HashMap<PathBuf, FileId>,
+  /// Files whose imports are still being loaded, to detect cycles
+  stack: Vec<FileId>,
+  errors: Vec<CerrSpan>,
+}
+
+impl Loader<'_> {
+  /// Loads a file, or returns the id it was already loaded as.
+  fn load(&mut self, path: PathBuf) -> FileId {
+    let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if let Some(id) = self.ids.get(&key) {
+      return *id;
+    }
+    let id = self.files.len();
+    self.ids.insert(key.clone(), id);
+    let read = match (self.open.get(&key), stdlib::source(&path)) {
+      (Some(src), _) => Ok(src.to_string()),
+      (None, Some(src)) => Ok(src.to_string()),
+      (None, None) => std::fs::read_to_string(&path),
+    };
+    let (src, program) = match read {
+      Ok(src) => {
+        let (program, errors) = parse(&src);
+        self.errors.extend(errors.into_iter().map(|e| e.in_file(id)));
+        (src, program)
+      }
+      Err(e) => {
+        let cerr = Cerr::ReadFile(path.display().to_string(), e.to_string());
+        self.errors.push(CerrSpan::without_span(cerr).in_file(id));
+        (String::new(), None)
+      }
+    };
+    let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    self.files.push(SourceFile { path, src, program });
+    let Some(program) = &mut self.files[id].program else {
+      return id;
+    };
+    let errors = load_rom_files(&mut program.modules, &base);
+    self.errors.extend(errors.into_iter().map(|e| e.in_file(id)));
+
+    let imports = program.imports.clone();
+    self.stack.push(id);
+    for (import, span) in imports {
+      self.load_import(id, &base, &import, span);
+    }
+    self.stack.pop();
+    id
+  }
+
+  fn load_import(&mut self, importer: FileId, base: &Path, import: &str, span: Span) {
+    let Some(path) = self.resolve(base, import) else {
+      self
+        .errors
+        .push(Cerr::ImportNotFound(import.into()).with(span).in_file(importer));
+      return;
+    };
+    let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if let Some(pos) = self.ids.get(&key).and_then(|id| self.stack.iter().position(|v| v == id)) {
+      let cycle = self.stack[pos..]
+        .iter()
+        .map(|id| self.files[*id].path.display().to_string())
+        .chain(std::iter::once(path.display().to_string()))
+        .collect::<Vec<_>>();
+      self
+        .errors
+        .push(Cerr::ImportCycle(cycle.join(" -> ")).with(span).in_file(importer));
+      return;
+    }
+    self.load(path);
+  }
+
+  /// Finds an imported file relative to the importing file, or else in the search path,
+  /// or else in the standard library.
+  fn resolve(&self, base: &Path, import: &str) -> Option<PathBuf> {
+    std::iter::once(base)
+      .chain(self.search_path.iter().map(|v| v.as_path()))
+      .map(|dir| dir.join(import))
+      .find(|path| path.is_file())
+      .or_else(|| stdlib::resolve(import))
+  }
+}
+
+fn parse(src: &str) -> (Option<Program>, Vec<CerrSpan>) {
+  let tokens = match tokenize(src.chars()).collect::<Result<Vec<_>, _>>() {
+    Ok(tokens) => tokens,
+    Err(err) => return (None, vec![err]),
+  };
+  let token_stream = TokenStream::from_tokens(tokens);
+  let (program, errors) = Program::parse_all(&token_stream.begin());
+  (Some(program), errors)
+}
@@ -0,0 +1,118 @@
+//! Loads the contents of ROMs declared with a file path.
+//!
+//! Single ROMs are read from a CSV file with one value per line, or a JSON array of integers.
+//! Mixed ROMs are read from a CSV file whose first line names the signal of each column, or a
+//! JSON array of objects mapping signals to values.
+
+use crate::err::{Cerr, CerrSpan};
+use crate::parse::ast::{Module, NetType, RomData, RomEntry, Stmt};
+use crate::parse::span::Span;
+use serde_json::Value;
+use std::path::Path;
+
+/// Replaces every ROM file path in the modules with the contents of the file.
+/// Paths are relative to `base`.
+pub fn load_rom_files(modules: &mut [(Module, Span)], base: &Path) -> Vec<CerrSpan> {
+  let mut errors = vec![];
+  for (module, _) in modules {
+    load_stmts(&mut module.stmts, base, &mut errors);
+  }
+  errors
+}
+
+fn load_stmts(stmts: &mut [(Stmt, Span)], base: &Path, errors: &mut Vec<CerrSpan>) {
+  for (stmt, span) in stmts {
+    match stmt {
+      Stmt::RomDecl { signal_class, data, .. } => {
+        if let RomData::File(path) = data {
+          let entries = std::fs::read_to_string(base.join(&*path))
+            .map_err(|e| e.to_string())
+            .and_then(|src| parse_rom_file(path, &src, *signal_class));
+          match entries {
+            Ok(entries) => *data = RomData::Inline(entries),
+            Err(reason) => errors.push(Cerr::RomFile(path.clone(), reason).with(*span)),
+          }
+        }
+      }
+      Stmt::Trigger { statements, else_statements, .. } => {
+        load_stmts(statements, base, errors);
+        load_stmts(else_statements, base, errors);
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Parses the contents of a ROM file, choosing the format by the file extension.
+pub fn parse_rom_file(path: &str, src: &str, ty: NetType) -> Result<Vec<RomEntry>, String> {
+  match Path::new(path).extension().and_then(|v| v.to_str()) {
+    Some("csv") => parse_csv(src, ty),
+    Some("json") => parse_json(src, ty),
+    _ => Err("expected a .csv or .json file".into()),
+  }
+}
+
+pub fn parse_csv(src: &str, ty: NetType) -> Result<Vec<RomEntry>, String> {
+  let mut lines = src
+    .lines()
+    .enumerate()
+    .map(|(i, line)| (i + 1, line.trim()))
+    .filter(|(_, line)| !line.is_empty());
+  let parse_int = |line: usize, s: &str| {
+    s.trim()
+      .parse::<i32>()
+      .map_err(|_| format!("line {}: invalid integer '{}'", line, s.trim()))
+  };
+  match ty {
+    NetType::Single => lines
+      .map(|(i, line)| parse_int(i, line).map(RomEntry::Single))
+      .collect(),
+    NetType::Mixed => {
+      let Some((_, header)) = lines.next() else {
+        return Ok(vec![]);
+      };
+      let signals = header.split(',').map(|v| v.trim().to_owned()).collect::<Vec<_>>();
+      lines
+        .map(|(i, line)| {
+          let cells = line.split(',').collect::<Vec<_>>();
+          if cells.len() != signals.len() {
+            return Err(format!("line {}: expected {} columns", i, signals.len()));
+          }
+          let mut entry = vec![];
+          for (signal, cell) in signals.iter().zip(cells) {
+            // empty cells are zero, so the signal is left out
+            if !cell.trim().is_empty() {
+              entry.push((signal.clone(), parse_int(i, cell)?));
+            }
+          }
+          Ok(RomEntry::Mixed(entry))
+        })
+        .collect()
+    }
+  }
+}
+
+pub fn parse_json(src: &str, ty: NetType) -> Result<Vec<RomEntry>, String> {
+  let value = serde_json::from_str::<Value>(src).map_err(|e| e.to_string())?;
+  let Value::Array(entries) = value else {
+    return Err("expected an array of entries".into());
+  };
+  let int = |val: &Value| val.as_i64().and_then(|v| i32::try_from(v).ok());
+  entries
+    .into_iter()
+    .map(|entry| match (ty, entry) {
+      (NetType::Single, entry) => int(&entry)
+        .map(RomEntry::Single)
+        .ok_or_else(|| "entries of single ROMs must be integers".into()),
+      (NetType::Mixed, Value::Object(fields)) => fields
+        .into_iter()
+        .map(|(signal, val)| match int(&val) {
+          Some(val) => Ok((signal, val)),
+          None => Err(format!("value of signal '{}' must be an integer", signal)),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(RomEntry::Mixed),
+      (NetType::Mixed, _) => Err("entries of mixed ROMs must be objects".into()),
+    })
+    .collect()
+}
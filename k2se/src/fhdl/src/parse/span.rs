@@ -1,7 +1,11 @@
+use serde::Serialize;
 use std::cmp::Ordering;
 
+/// Identifies a source file of a project, see [`crate::parse::project::Project`].
+pub type FileId = usize;
+
 /// A position in source code.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
 pub struct Pos {
   pub line: u32,
   pub col: u32,
@@ -38,7 +42,7 @@ impl Pos {
 
 /// A span represents a section of source code, delimited by two positions.
 /// Spans are inclusive ranges and so include both endpoints.
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize)]
 pub struct Span {
   pub start: Pos,
   pub end: Pos,
@@ -71,6 +75,10 @@ impl Span {
       end: self.end.max(other.end),
     }
   }
+
+  pub fn contains(&self, pos: Pos) -> bool {
+    self.start <= pos && pos <= self.end
+  }
 }
 
 impl From<Pos> for Span {
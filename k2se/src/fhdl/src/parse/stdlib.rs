@@ -0,0 +1,41 @@
+//! The standard library, a set of FHDL files that are built into the compiler.
+//!
+//! Standard library files are imported as `import "std/clock.fhdl";`. They are only used if
+//! neither the importing file's directory nor the search path has a file of the same name.
+
+use std::path::{Path, PathBuf};
+
+/// Import paths starting with this refer to the standard library.
+pub const PREFIX: &str = "std/";
+
+/// Stands in for the directory of the standard library in the paths of its files.
+const ROOT: &str = "<stdlib>";
+
+const FILES: &[(&str, &str)] = &[
+  ("clock.fhdl", include_str!("../../stdlib/clock.fhdl")),
+  ("counter.fhdl", include_str!("../../stdlib/counter.fhdl")),
+  ("latch.fhdl", include_str!("../../stdlib/latch.fhdl")),
+  ("pulse.fhdl", include_str!("../../stdlib/pulse.fhdl")),
+  ("qm.fhdl", include_str!("../../stdlib/qm.fhdl")),
+  ("tdm.fhdl", include_str!("../../stdlib/tdm.fhdl")),
+];
+
+/// Finds the standard library file an import refers to, and returns the path it's loaded as.
+pub fn resolve(import: &str) -> Option<PathBuf> {
+  let name = import.strip_prefix(PREFIX)?;
+  FILES
+    .iter()
+    .any(|(file, _)| *file == name)
+    .then(|| Path::new(ROOT).join(name))
+}
+
+/// The source of a standard library file, given the path that [`resolve`] returned for it.
+pub fn source(path: &Path) -> Option<&'static str> {
+  let name = path.strip_prefix(ROOT).ok()?.to_str()?;
+  FILES.iter().find(|(file, _)| *file == name).map(|(_, src)| *src)
+}
+
+/// The import paths of all standard library files.
+pub fn imports() -> impl Iterator<Item = String> {
+  FILES.iter().map(|(file, _)| format!("{}{}", PREFIX, file))
+}
@@ -16,9 +16,15 @@ pub enum Token {
   RParen,
   LBrace,
   RBrace,
+  LBracket,
+  RBracket,
   Comma,
+  Colon,
   Semicolon,
   Op(BinaryOp),
+  /// A `//` comment, without the slashes. The parser never sees these, see
+  /// [`crate::parse::tokenstream::TokenStream::from_tokens`], but the formatter keeps them.
+  Comment(String),
 }
 
 impl Display for Token {
@@ -31,9 +37,13 @@ impl Display for Token {
       Token::RParen => f.write_char(')'),
       Token::LBrace => f.write_char('{'),
       Token::RBrace => f.write_char('}'),
+      Token::LBracket => f.write_char('['),
+      Token::RBracket => f.write_char(']'),
       Token::Comma => f.write_char(','),
+      Token::Colon => f.write_char(':'),
       Token::Semicolon => f.write_char(';'),
       Token::Op(op) => write!(f, "{}", op),
+      Token::Comment(s) => write!(f, "//{}", s),
     }
   }
 }
@@ -74,6 +84,8 @@ impl Token {
 /// - 4: Shl, Shr
 /// - 5: And, Or, Xor
 /// - 6: Eq, Ne, Lt, Gt, Le, Ge
+/// - 7: LAnd
+/// - 8: LOr
 /// - None (99): Assign, AddAssign
 ///
 /// The last two operators are not allowed in general exprs
@@ -97,11 +109,13 @@ pub enum BinaryOp {
   Gt,
   Le,
   Ge,
+  LAnd,
+  LOr,
   Assign,
   AddAssign,
 }
 
-const HIGHEST_PREC: u32 = 6;
+pub const HIGHEST_PREC: u32 = 8;
 
 impl Display for BinaryOp {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -123,6 +137,8 @@ impl Display for BinaryOp {
       BinaryOp::Gt => ">",
       BinaryOp::Le => "<=",
       BinaryOp::Ge => ">=",
+      BinaryOp::LAnd => "&&",
+      BinaryOp::LOr => "||",
       BinaryOp::Assign => "=",
       BinaryOp::AddAssign => "+=",
     })
@@ -149,6 +165,8 @@ impl BinaryOp {
       ">" => BinaryOp::Gt,
       "<=" => BinaryOp::Le,
       ">=" => BinaryOp::Ge,
+      "&&" => BinaryOp::LAnd,
+      "||" => BinaryOp::LOr,
       "=" => BinaryOp::Assign,
       "+=" => BinaryOp::AddAssign,
       _ => return Err(Cerr::InvalidOperator),
@@ -174,10 +192,25 @@ impl BinaryOp {
       BinaryOp::Gt => 6,
       BinaryOp::Le => 6,
       BinaryOp::Ge => 6,
+      BinaryOp::LAnd => 7,
+      BinaryOp::LOr => 8,
       BinaryOp::Assign => 99,
       BinaryOp::AddAssign => 99,
     }
   }
+
+  /// Whether the op is a comparison, which always outputs 0 or 1.
+  pub fn is_comparison(self) -> bool {
+    matches!(
+      self,
+      BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge
+    )
+  }
+
+  /// Whether the op is a logical op, which normalizes its operands to 0 or 1.
+  pub fn is_logical(self) -> bool {
+    matches!(self, BinaryOp::LAnd | BinaryOp::LOr)
+  }
 }
 
 pub struct Tokenize<I: Iterator<Item = char>> {
@@ -262,17 +295,19 @@ impl<I: Iterator<Item = char>> Tokenize<I> {
     ))
   }
 
-  /// Reads an operator or skips a comment. Returns None if a comment was matched.
-  fn parse_op_or_comment(&mut self) -> Option<Result<WithSpan<Token>, CerrSpan>> {
+  /// Reads an operator, or a comment up to the end of the line.
+  fn parse_op_or_comment(&mut self) -> Result<WithSpan<Token>, CerrSpan> {
     let (span, s) = self.take_while_span(|c| is_op(c)).expect("Not an operator");
-    if s.starts_with("//") {
-      self.take_while_span(|c| c != '\n');
-      None
+    if let Some(text) = s.strip_prefix("//") {
+      let mut text = text.to_owned();
+      let mut end = span.end;
+      if let Some((rest_span, rest)) = self.take_while_span(|c| c != '\n') {
+        text.push_str(&rest);
+        end = rest_span.end;
+      }
+      Ok(WithSpan::new(Span { start: span.start, end }, Token::Comment(text)))
     } else {
-      Some(util_inject_span(
-        BinaryOp::parse_raw(&s).map(Token::Op),
-        span,
-      ))
+      util_inject_span(BinaryOp::parse_raw(&s).map(Token::Op), span)
     }
   }
 }
@@ -297,21 +332,20 @@ impl<I: Iterator<Item = char>> Iterator for Tokenize<I> {
         return Some(self.parse_string_literal());
       }
       if is_op(peek) {
-        if let Some(op) = self.parse_op_or_comment() {
-          return Some(op);
-        } else {
-          continue;
-        }
+        return Some(self.parse_op_or_comment());
       }
       self._next().unwrap();
       return Some(
         match peek {
           '{' => Ok(Token::LBrace),
           '}' => Ok(Token::RBrace),
+          '[' => Ok(Token::LBracket),
+          ']' => Ok(Token::RBracket),
           '(' => Ok(Token::LParen),
           ')' => Ok(Token::RParen),
           ';' => Ok(Token::Semicolon),
           ',' => Ok(Token::Comma),
+          ':' => Ok(Token::Colon),
           _ => Err(CerrSpan::new(pos.into(), Cerr::InvalidChar)),
         }
         .map(|v| WithSpan::new(pos.into(), v)),
@@ -332,8 +366,9 @@ fn is_ident_start(c: char) -> bool {
 }
 
 /// Matches characters that can be in an identifier.
+/// Dots separate bundle members, as in `req.item`.
 fn is_ident(c: char) -> bool {
-  c.is_ascii_alphanumeric() || c == '_'
+  c.is_ascii_alphanumeric() || c == '_' || c == '.'
 }
 
 /// Matches characters that make operators.
@@ -11,7 +11,9 @@ pub struct TokenStream {
 }
 
 impl TokenStream {
-  pub fn from_tokens(tokens: Vec<WithSpan<Token>>) -> Self {
+  /// Makes a stream of the tokens of a file. Comments are left out, since they can be anywhere.
+  pub fn from_tokens(mut tokens: Vec<WithSpan<Token>>) -> Self {
+    tokens.retain(|v| !matches!(v.t, Token::Comment(_)));
     TokenStream { tokens }
   }
 
@@ -79,6 +81,26 @@ impl<'a> Cursor<'a> {
     Ok((id.to_owned(), span))
   }
 
+  /// Gets the current position, which the cursor can be moved back to with [`Cursor::reset`].
+  pub fn mark(&self) -> usize {
+    self.position.get()
+  }
+
+  pub fn reset(&self, mark: usize) {
+    self.position.set(mark);
+  }
+
+  /// Gets the span of the token that was taken last, e.g. to find where a list ends.
+  pub fn prev_span(&self) -> Span {
+    self
+      .position
+      .get()
+      .checked_sub(1)
+      .and_then(|pos| self.parent.tokens.get(pos))
+      .map(|v| v.span)
+      .unwrap_or_default()
+  }
+
   /// Peeks the next token without taking it.
   pub fn peek(&self) -> Result<(&Token, Span), CerrSpan> {
     self.peek_or_eof().ok_or(Cerr::UnexpectedEOF.into())
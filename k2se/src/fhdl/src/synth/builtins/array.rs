@@ -0,0 +1,51 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::{constants, register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
+use crate::synth::combinator::{CCSignalRef, SignalWithCount};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+use crate::synth::virt_signals::SLOT_SIGNALS;
+
+/// Implements `$array_index(len)`, which outputs the index table of a memory array:
+/// the signal of slot `i` with a value of `i + 1`.
+///
+/// Memory arrays are lowered during transformation. The table minus the address is 1 only on
+/// the addressed slot's signal, which gives a mask that selects that slot.
+#[derive(Debug)]
+pub struct ArrayIndexFunc;
+
+impl ArrayIndexFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "$array_index", ArrayIndexFunc);
+  }
+}
+
+impl BuiltinFunction for ArrayIndexFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[FunctionArgReq::Count]
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    Some(NetType::Mixed)
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let len = match inputs[0] {
+      SynthRef::Value(len) => len as usize,
+      _ => panic!("Array length must be a literal"),
+    };
+    let signals = SLOT_SIGNALS[..len]
+      .iter()
+      .enumerate()
+      .map(|(i, signal)| CCSignalRef::Signal(SignalWithCount {
+        signal: signal.clone(),
+        count: i as i32 + 1,
+      }))
+      .collect::<Vec<_>>();
+    constants(state, signals, output);
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
@@ -3,7 +3,7 @@ use crate::parse::ast::NetType;
 use crate::parse::tokenizer::BinaryOp;
 use crate::synth::builtins::{register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
 use crate::synth::combinator::{Combinator, SignalRef, VanillaCombinator, VanillaCombinatorOp};
-use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState, SingleMixedStrategy};
 
 /// Builtin functions for binary ops.
 /// Unlike most builtin functions, these cannot be referred to by users directly
@@ -40,12 +40,68 @@ impl BinaryOpFunc {
   }
 }
 
+impl BinaryOpFunc {
+  /// Applies the op to each signal of the mixed net in one combinator.
+  /// This leaks the single net's signal into the output.
+  fn synthesize_dirty(
+    &self,
+    state: &mut ModuleSynthState,
+    mixed_net: IncompleteNetID,
+    single: &SynthRef,
+    output: IncompleteNetID,
+  ) {
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      // unwrap: assign type ops are forbidden in expressions
+      op: self.op.try_into().unwrap(),
+      input_signals: [SignalRef::Each, single.as_signal_ref().unwrap()],
+      output_signal: SignalRef::Each,
+      output_count: false,
+      .. Default::default()
+    }), Some(mixed_net), single.get_net(), output);
+  }
+
+  /// Applies the op to each signal of the mixed net, then removes the single net's signal from the output.
+  /// If the mixed net also has the single net's signal on it, that signal is removed as well.
+  fn synthesize_clean(
+    &self,
+    state: &mut ModuleSynthState,
+    mixed_net: IncompleteNetID,
+    single_net: IncompleteNetID,
+    output: IncompleteNetID,
+  ) {
+    let single = SynthRef::Net(single_net);
+    if !leaks_single_signal(self.op) {
+      self.synthesize_dirty(state, mixed_net, &single, output);
+      return;
+    }
+    // otherwise, filter the leaked signal out afterwards
+    let dirty = state.new_net_builder()
+      .net_type(NetType::Mixed)
+      .build(state);
+    self.synthesize_dirty(state, mixed_net, &single, dirty);
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Add,
+      input_signals: [SignalRef::Each, SignalRef::Const(0)],
+      output_signal: SignalRef::Each,
+      output_count: false,
+      .. Default::default()
+    }), Some(dirty), None, output);
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Mul,
+      input_signals: [SignalRef::IncompleteSignal(single_net), SignalRef::Const(-1)],
+      output_signal: SignalRef::IncompleteSignal(single_net),
+      output_count: false,
+      .. Default::default()
+    }), Some(dirty), None, output);
+  }
+}
+
 impl BuiltinFunction for BinaryOpFunc {
   fn arg_ty(&self) -> &[FunctionArgReq] {
     panic!("binary ops bypass typechecking")
   }
 
-  fn return_ty(&self) -> Option<NetType> {
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
     panic!("binary ops bypass typechecking")
   }
 
@@ -89,20 +145,23 @@ impl BuiltinFunction for BinaryOpFunc {
       }), Some(net1), Some(net2), output);
     } else if ty1 == NetType::Mixed || ty2 == NetType::Mixed {
       // case 2: single-mixed op
-      // warning: single-mixed ops are "dirty": they leak virtual signals into the output
+      // the single net's own signal also ends up in the output,
+      // unless the clean strategy is used to remove it
       let (mixed_net, single) = if ty1 == NetType::Mixed {
         (inputs[0].get_net().unwrap(), &inputs[1])
       } else {
         (inputs[1].get_net().unwrap(), &inputs[0])
       };
-      state.new_combinator(Combinator::Vanilla(VanillaCombinator {
-        // unwrap: assign type ops are forbidden in expressions
-        op: self.op.try_into().unwrap(),
-        input_signals: [SignalRef::Each, single.as_signal_ref().unwrap()],
-        output_signal: SignalRef::Each,
-        output_count: false,
-        .. Default::default()
-      }), Some(mixed_net), single.get_net(), output);
+      let strategy = state.settings().single_mixed_strategy;
+      match (strategy, single.get_net()) {
+        (SingleMixedStrategy::Clean, Some(single_net)) => {
+          self.synthesize_clean(state, mixed_net, single_net, output);
+        }
+        _ => {
+          // literals have no signal, so they never leak
+          self.synthesize_dirty(state, mixed_net, single, output);
+        }
+      }
     } else {
       // case3: single-single op
       // this is relatively simple
@@ -123,6 +182,13 @@ impl BuiltinFunction for BinaryOpFunc {
   }
 }
 
+/// Whether the op between a single and a mixed net outputs something on the single net's signal
+/// when synthesized with one combinator.
+pub fn leaks_single_signal(op: BinaryOp) -> bool {
+  // `x op x` is 0 or false for these, so nothing leaks
+  !matches!(op, BinaryOp::Sub | BinaryOp::Mod | BinaryOp::Xor | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Gt)
+}
+
 pub fn binary_op_to_func_name(op: BinaryOp) -> &'static str {
   match op {
     BinaryOp::Add => "$op_add",
@@ -145,3 +211,26 @@ pub fn binary_op_to_func_name(op: BinaryOp) -> &'static str {
     _ => panic!("Invalid BinaryOp"),
   }
 }
+
+pub fn func_name_to_binary_op(name: &str) -> Option<BinaryOp> {
+  Some(match name {
+    "$op_add" => BinaryOp::Add,
+    "$op_sub" => BinaryOp::Sub,
+    "$op_mul" => BinaryOp::Mul,
+    "$op_div" => BinaryOp::Div,
+    "$op_mod" => BinaryOp::Mod,
+    "$op_pow" => BinaryOp::Pow,
+    "$op_and" => BinaryOp::And,
+    "$op_or" => BinaryOp::Or,
+    "$op_xor" => BinaryOp::Xor,
+    "$op_shl" => BinaryOp::Shl,
+    "$op_shr" => BinaryOp::Shr,
+    "$op_eq" => BinaryOp::Eq,
+    "$op_ne" => BinaryOp::Ne,
+    "$op_lt" => BinaryOp::Lt,
+    "$op_gt" => BinaryOp::Gt,
+    "$op_le" => BinaryOp::Le,
+    "$op_ge" => BinaryOp::Ge,
+    _ => return None,
+  })
+}
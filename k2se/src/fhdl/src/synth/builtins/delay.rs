@@ -0,0 +1,58 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::passthrough::Passthrough;
+use crate::synth::builtins::{delay, register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+/// Implements `delay(x, n)`, which outputs `x` as it was `n` ticks ago.
+///
+/// This is synthesized as a chain of `n` passthrough combinators. `delay(x, 0)` is
+/// replaced by `x` during transformation.
+#[derive(Debug)]
+pub struct DelayFunc;
+
+impl DelayFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "delay", DelayFunc);
+  }
+}
+
+impl BuiltinFunction for DelayFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[FunctionArgReq::Any, FunctionArgReq::Count]
+  }
+
+  fn return_ty(&self, arg_ty: &[Option<NetType>]) -> Option<NetType> {
+    arg_ty.first().copied().flatten()
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let ticks = match inputs[1] {
+      SynthRef::Value(ticks) => ticks,
+      _ => panic!("Tick count must be a literal"),
+    };
+    match &inputs[0] {
+      SynthRef::Net(net) => {
+        // the last passthrough writes to the output
+        let last = (1..ticks).fold(*net, |prev, _| delay(state, prev));
+        Passthrough.synthesize(state, &[SynthRef::Net(last)], output)
+      }
+      // constants look the same on every tick
+      value => Passthrough.synthesize(state, std::slice::from_ref(value), output),
+    }
+  }
+
+  fn constant_fold(&self, args: &[SynthRef]) -> Option<i32> {
+    match args {
+      [SynthRef::Value(val), _] => Some(*val),
+      _ => None,
+    }
+  }
+
+  fn fold_to_arg(&self, lits: &[Option<i32>]) -> Option<usize> {
+    match lits {
+      [_, Some(0)] => Some(0),
+      _ => None,
+    }
+  }
+}
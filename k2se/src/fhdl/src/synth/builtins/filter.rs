@@ -0,0 +1,138 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::{delay, register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
+use crate::synth::combinator::{Combinator, Signal, SignalRef, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+/// Offset added to masked signals by `filter`, so that they can be told apart from unmasked signals.
+const FILTER_OFFSET: i32 = 1 << 30;
+/// Masked signals are always at least this large after the offset is added,
+/// as long as the filtered values are in `(-FILTER_THRESHOLD, FILTER_THRESHOLD)`.
+/// Outside of that, an unmasked value can pass, or a masked one is dropped while its offset
+/// is still removed, which leaves `-FILTER_OFFSET` on the output.
+const FILTER_THRESHOLD: i32 = 1 << 29;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum FilterKind {
+  /// `extract(mixed, signal) -> single`
+  Extract,
+  /// `filter(mixed, mixed) -> mixed`
+  Filter,
+  /// `exclude(mixed, signal) -> mixed`
+  Exclude,
+}
+
+/// Builtin functions for selecting signals out of mixed nets.
+#[derive(Debug)]
+pub struct FilterFunc {
+  kind: FilterKind,
+}
+
+impl FilterFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "extract", FilterFunc { kind: FilterKind::Extract });
+    register(b, "filter", FilterFunc { kind: FilterKind::Filter });
+    register(b, "exclude", FilterFunc { kind: FilterKind::Exclude });
+  }
+}
+
+impl BuiltinFunction for FilterFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    match self.kind {
+      FilterKind::Extract | FilterKind::Exclude => &[FunctionArgReq::Net(NetType::Mixed), FunctionArgReq::Signal],
+      FilterKind::Filter => &[FunctionArgReq::Net(NetType::Mixed), FunctionArgReq::Net(NetType::Mixed)],
+    }
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    match self.kind {
+      FilterKind::Extract => Some(NetType::Single),
+      FilterKind::Filter | FilterKind::Exclude => Some(NetType::Mixed),
+    }
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let input_net = inputs[0].get_net().unwrap();
+    match self.kind {
+      FilterKind::Extract => {
+        // unwrap: signals are validated by the typechecker
+        let signal = Signal::parse(inputs[1].get_string().unwrap()).unwrap();
+        // arithmetic combinators can read one signal and output it as another
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Add,
+          input_signals: [SignalRef::Signal(signal), SignalRef::Const(0)],
+          output_signal: SignalRef::IncompleteSignal(output),
+          output_count: false,
+          .. Default::default()
+        }), Some(input_net), None, output);
+      }
+      FilterKind::Exclude => {
+        let signal = Signal::parse(inputs[1].get_string().unwrap()).unwrap();
+        // pass everything through, then cancel out the excluded signal on the same tick
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Add,
+          input_signals: [SignalRef::Each, SignalRef::Const(0)],
+          output_signal: SignalRef::Each,
+          output_count: false,
+          .. Default::default()
+        }), Some(input_net), None, output);
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Mul,
+          input_signals: [SignalRef::Signal(signal.clone()), SignalRef::Const(-1)],
+          output_signal: SignalRef::Signal(signal),
+          output_count: false,
+          .. Default::default()
+        }), Some(input_net), None, output);
+      }
+      FilterKind::Filter => {
+        let mask_net = inputs[1].get_net().unwrap();
+        // normalize the mask so that every signal on it is 1
+        let normalized = state.new_net_builder()
+          .net_type(NetType::Mixed)
+          .build(state);
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Ne,
+          input_signals: [SignalRef::Each, SignalRef::Const(0)],
+          output_signal: SignalRef::Each,
+          output_count: false,
+          .. Default::default()
+        }), Some(mask_net), None, normalized);
+        // scale it up to the offset
+        let offset = state.new_net_builder()
+          .net_type(NetType::Mixed)
+          .build(state);
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Mul,
+          input_signals: [SignalRef::Each, SignalRef::Const(FILTER_OFFSET)],
+          output_signal: SignalRef::Each,
+          output_count: false,
+          .. Default::default()
+        }), Some(normalized), None, offset);
+        // the input is delayed to arrive together with the offset mask
+        let delayed = delay(state, input_net);
+        let delayed = delay(state, delayed);
+        // masked signals now sit above the threshold, so pass them through along with the offset
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Ge,
+          input_signals: [SignalRef::Each, SignalRef::Const(FILTER_THRESHOLD)],
+          output_signal: SignalRef::Each,
+          output_count: true,
+          .. Default::default()
+        }), Some(delayed), Some(offset), output);
+        // and remove the offset on the same tick
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Mul,
+          input_signals: [SignalRef::Each, SignalRef::Const(-1)],
+          output_signal: SignalRef::Each,
+          output_count: false,
+          .. Default::default()
+        }), Some(offset), None, output);
+      }
+    }
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
@@ -0,0 +1,109 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::passthrough::Passthrough;
+use crate::synth::builtins::{constants, register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
+use crate::synth::combinator::{CCSignalRef, Combinator, Signal, SignalRef, SignalWithCount, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+/// Implements `gate(bus, signal, value, data)`, which outputs `data` on the tick after `bus`
+/// has `value` on `signal`, and nothing otherwise.
+///
+/// Gating usually takes a tick to compare and another to select, but the gate reads `bus`
+/// directly so that it reacts on the next tick, as transmitters in RFC 7+1i do. A decider that
+/// reads `bus` can't leave it out of its output, so the gate writes:
+/// - `-bus`, from an arithmetic combinator on every tick.
+/// - `bus + data - value`, from a decider reading `bus` and `data - value` that passes if
+///   `signal` sums to 0.
+/// - `bus - value`, from a decider reading `bus` and `-value` that passes otherwise.
+/// - `value`, to cancel out the `-value` left on `signal` by either decider.
+///
+/// Exactly one of the deciders passes, so the sum is `data` if `bus` had `value` on `signal`,
+/// and 0 otherwise. `data` must not contain `signal`, and takes a tick longer than `bus` to reach
+/// the output, as does `value`.
+#[derive(Debug)]
+pub struct GateFunc;
+
+impl GateFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "gate", GateFunc);
+  }
+}
+
+/// Writes `-value` on `signal` to `output`.
+fn negated(state: &mut ModuleSynthState, value: &SynthRef, signal: &Signal, output: IncompleteNetID) {
+  match value {
+    SynthRef::Net(net) => {
+      state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+        op: VanillaCombinatorOp::Mul,
+        input_signals: [SignalRef::IncompleteSignal(*net), SignalRef::Const(-1)],
+        output_signal: SignalRef::Signal(signal.clone()),
+        output_count: false,
+        .. Default::default()
+      }), Some(*net), None, output);
+    }
+    SynthRef::Value(value) => {
+      constants(state, vec![CCSignalRef::Signal(SignalWithCount {
+        signal: signal.clone(),
+        count: value.wrapping_neg(),
+      })], output);
+    }
+    SynthRef::String(_) => panic!("Unexpected string"),
+  }
+}
+
+impl BuiltinFunction for GateFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[
+      FunctionArgReq::Net(NetType::Mixed),
+      FunctionArgReq::Signal,
+      FunctionArgReq::SingleOrLit,
+      FunctionArgReq::Net(NetType::Mixed),
+    ]
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    Some(NetType::Mixed)
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let bus = inputs[0].get_net().unwrap();
+    // unwrap: signals are validated by the typechecker
+    let signal = Signal::parse(inputs[1].get_string().unwrap()).unwrap();
+    let data = inputs[3].get_net().unwrap();
+    // `data - value` and `-value`, which are added to the bus by the deciders
+    let active = state.new_net_builder().net_type(NetType::Mixed).build(state);
+    Passthrough.synthesize(state, &[SynthRef::Net(data)], active)?;
+    negated(state, &inputs[2], &signal, active);
+    let inactive = state.new_net_builder().net_type(NetType::Mixed).build(state);
+    negated(state, &inputs[2], &signal, inactive);
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Mul,
+      input_signals: [SignalRef::Each, SignalRef::Const(-1)],
+      output_signal: SignalRef::Each,
+      output_count: false,
+      .. Default::default()
+    }), Some(bus), None, output);
+    for (op, input) in [(VanillaCombinatorOp::Eq, active), (VanillaCombinatorOp::Ne, inactive)] {
+      state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+        op,
+        input_signals: [SignalRef::Signal(signal.clone()), SignalRef::Const(0)],
+        output_signal: SignalRef::Everything,
+        output_count: true,
+        .. Default::default()
+      }), Some(bus), Some(input), output);
+    }
+    // reading `-value` back keeps it in step with what the deciders compared against
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Mul,
+      input_signals: [SignalRef::Each, SignalRef::Const(-1)],
+      output_signal: SignalRef::Each,
+      output_count: false,
+      .. Default::default()
+    }), Some(inactive), None, output);
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
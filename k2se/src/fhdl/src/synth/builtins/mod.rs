@@ -1,6 +1,15 @@
 pub mod binaryop;
 mod trigger;
 mod passthrough;
+mod select;
+mod filter;
+mod reduce;
+mod delay;
+mod reg;
+mod array;
+mod rom;
+mod gate;
+mod quality;
 
 use crate::err::Cerr;
 use crate::parse::ast::NetType;
@@ -8,9 +17,18 @@ use crate::synth::builtins::binaryop::BinaryOpFunc;
 use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
 use std::collections::HashMap;
 use std::fmt::Debug;
+use crate::synth::builtins::array::ArrayIndexFunc;
+use crate::synth::builtins::delay::DelayFunc;
+use crate::synth::builtins::filter::FilterFunc;
+use crate::synth::builtins::gate::GateFunc;
 use crate::synth::builtins::passthrough::Passthrough;
-use crate::synth::builtins::trigger::TriggerFunc;
-use crate::synth::combinator::{SignalRef};
+use crate::synth::builtins::quality::QualityFunc;
+use crate::synth::builtins::reduce::ReduceFunc;
+use crate::synth::builtins::reg::RegFunc;
+use crate::synth::builtins::rom::{RomEntryFunc, RomLookupFunc};
+use crate::synth::builtins::select::SelectFunc;
+use crate::synth::builtins::trigger::{PulseFunc, TriggerFunc};
+use crate::synth::combinator::{CCSignalRef, Combinator, ConstantCombinator, SignalRef, VanillaCombinator, VanillaCombinatorOp};
 
 /// Defines the requirements placed on a function argument during type checking.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -19,6 +37,15 @@ pub enum FunctionArgReq {
   Net(NetType),
   SingleOrLit,
   String,
+  /// String literal naming a signal, such as `"item=iron-plate"`.
+  Signal,
+  /// String literal naming a quality, such as `"rare"`.
+  Quality,
+  /// Net or literal with the same type as the argument at the given index.
+  /// Literals count as single nets.
+  SameAs(usize),
+  /// Non-negative integer literal, such as a number of ticks.
+  Count,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -36,6 +63,13 @@ impl SynthRef {
     }
   }
   
+  pub fn get_string(&self) -> Option<&str> {
+    match self {
+      SynthRef::String(str) => Some(str),
+      _ => None,
+    }
+  }
+
   pub fn as_signal_ref(&self) -> Option<SignalRef> {
     match self {
       SynthRef::Net(net) => Some(SignalRef::IncompleteSignal(*net)),
@@ -48,9 +82,16 @@ impl SynthRef {
 /// Trait implemented for each built-in function.
 pub trait BuiltinFunction: Debug {
   fn arg_ty(&self) -> &[FunctionArgReq];
-  fn return_ty(&self) -> Option<NetType>;
+  /// Gets the return type of the function, given the types of its arguments.
+  /// String arguments are passed as `None`.
+  fn return_ty(&self, arg_ty: &[Option<NetType>]) -> Option<NetType>;
   fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr>;
   fn constant_fold(&self, args: &[SynthRef]) -> Option<i32>;
+  /// Checks if the call is equivalent to one of its arguments, given the values of its
+  /// literal arguments (`None` for anything else). If so, the call is replaced by that argument.
+  fn fold_to_arg(&self, _lits: &[Option<i32>]) -> Option<usize> {
+    None
+  }
 }
 
 pub type Builtins = HashMap<String, Box<dyn BuiltinFunction>>;
@@ -59,10 +100,110 @@ pub fn collect_builtins() -> Builtins {
   let mut b = Builtins::new();
   BinaryOpFunc::collect(&mut b);
   TriggerFunc::collect(&mut b);
+  PulseFunc::collect(&mut b);
   Passthrough::collect(&mut b);
+  SelectFunc::collect(&mut b);
+  FilterFunc::collect(&mut b);
+  ReduceFunc::collect(&mut b);
+  DelayFunc::collect(&mut b);
+  RegFunc::collect(&mut b);
+  ArrayIndexFunc::collect(&mut b);
+  RomEntryFunc::collect(&mut b);
+  RomLookupFunc::collect(&mut b);
+  GateFunc::collect(&mut b);
+  QualityFunc::collect(&mut b);
   b
 }
 
 fn register<T: BuiltinFunction + 'static>(b: &mut Builtins, name: &str, t: T) {
   b.insert(name.into(), Box::new(t));
 }
+
+/// Converts a `SynthRef` into a net. Nets are returned as-is, while values
+/// are emitted from a constant combinator onto a new single net.
+pub fn materialize(state: &mut ModuleSynthState, r: &SynthRef) -> IncompleteNetID {
+  match r {
+    SynthRef::Net(net) => *net,
+    SynthRef::Value(val) => {
+      let anon = state.new_net_builder()
+        .net_type(NetType::Single)
+        .build(state);
+      state.new_combinator(Combinator::Constant(ConstantCombinator {
+        enabled: true,
+        output_nets: [None, None],
+        output_signals: vec![CCSignalRef::IncompleteSignal(anon, *val)],
+        .. Default::default()
+      }), None, None, anon);
+      anon
+    }
+    SynthRef::String(_) => panic!("Unexpected string"),
+  }
+}
+
+/// Number of signals that fit in one constant combinator.
+const CONSTANT_COMBINATOR_SLOTS: usize = 18;
+
+/// Outputs a list of constant signals, split across as many constant combinators as needed.
+fn constants(state: &mut ModuleSynthState, signals: Vec<CCSignalRef>, output: IncompleteNetID) {
+  if signals.is_empty() {
+    // still write something to the net
+    state.new_combinator(Combinator::Constant(ConstantCombinator {
+      enabled: true,
+      output_nets: [None, None],
+      output_signals: vec![],
+      .. Default::default()
+    }), None, None, output);
+  }
+  for chunk in signals.chunks(CONSTANT_COMBINATOR_SLOTS) {
+    state.new_combinator(Combinator::Constant(ConstantCombinator {
+      enabled: true,
+      output_nets: [None, None],
+      output_signals: chunk.to_vec(),
+      .. Default::default()
+    }), None, None, output);
+  }
+}
+
+/// Passes a net through a combinator onto a new net of the same type.
+fn delay(state: &mut ModuleSynthState, net: IncompleteNetID) -> IncompleteNetID {
+  let anon = state.new_net_builder()
+    .net_type(state.net_info(net).ty)
+    .build(state);
+  // unwrap: passthrough of a net never fails
+  Passthrough.synthesize(state, &[SynthRef::Net(net)], anon).unwrap();
+  anon
+}
+
+/// Value a condition is scaled to when deciders read it summed with mixed data.
+const MIXED_CONDITION: i32 = 1 << 30;
+
+/// Scales a single condition to `MIXED_CONDITION` if it's nonzero, on its own signal. Takes two ticks.
+///
+/// A decider that reads the condition summed with mixed data may also see the data's value on the
+/// condition's signal. Comparing the scaled condition against `MIXED_CONDITION / 2` instead holds
+/// as long as that value stays within `±2^29`.
+fn scale_condition(state: &mut ModuleSynthState, cond: IncompleteNetID) -> IncompleteNetID {
+  let normalized = state.new_net_builder()
+    .net_type(NetType::Single)
+    .build(state);
+  state.require_same_signal(normalized, cond);
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Ne,
+    input_signals: [SignalRef::IncompleteSignal(cond), SignalRef::Const(0)],
+    output_signal: SignalRef::IncompleteSignal(normalized),
+    output_count: false,
+    .. Default::default()
+  }), Some(cond), None, normalized);
+  let scaled = state.new_net_builder()
+    .net_type(NetType::Single)
+    .build(state);
+  state.require_same_signal(scaled, cond);
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Mul,
+    input_signals: [SignalRef::IncompleteSignal(normalized), SignalRef::Const(MIXED_CONDITION)],
+    output_signal: SignalRef::IncompleteSignal(scaled),
+    output_count: false,
+    .. Default::default()
+  }), Some(normalized), None, scaled);
+  scaled
+}
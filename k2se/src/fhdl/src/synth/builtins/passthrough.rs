@@ -18,7 +18,7 @@ impl BuiltinFunction for Passthrough {
     &[FunctionArgReq::Any]
   }
 
-  fn return_ty(&self) -> Option<NetType> {
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
     None
   }
 
@@ -34,6 +34,7 @@ impl BuiltinFunction for Passthrough {
             input_signals: [SignalRef::Each, SignalRef::Const(0)],
             output_signal: SignalRef::Each,
             output_count: false,
+            .. Default::default()
           }), Some(*net), None, output);
         } else {
           state.new_combinator(Combinator::Vanilla(VanillaCombinator {
@@ -43,6 +44,7 @@ impl BuiltinFunction for Passthrough {
             input_signals: [SignalRef::IncompleteSignal(*net), SignalRef::Const(0)],
             output_signal: SignalRef::IncompleteSignal(output),
             output_count: false,
+            .. Default::default()
           }), Some(*net), None, output);
         }
       }
@@ -54,6 +56,7 @@ impl BuiltinFunction for Passthrough {
           enabled: true,
           output_nets: [None, None],
           output_signals: vec![CCSignalRef::IncompleteSignal(output, *val)],
+          .. Default::default()
         }), None, None, output);
       }
       SynthRef::String(_) => panic!("Unexpected string")
@@ -0,0 +1,57 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::{register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
+use crate::synth::combinator::{Combinator, Quality, SelectorCombinator, SelectorCombinatorOp};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum QualityKind {
+  /// `set_quality(mixed, quality) -> mixed`
+  Set,
+  /// `filter_quality(mixed, quality) -> mixed`
+  Filter,
+}
+
+/// Builtin functions for the quality of signals, each of which is a single selector combinator.
+#[derive(Debug)]
+pub struct QualityFunc {
+  kind: QualityKind,
+}
+
+impl QualityFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "set_quality", QualityFunc { kind: QualityKind::Set });
+    register(b, "filter_quality", QualityFunc { kind: QualityKind::Filter });
+  }
+}
+
+impl BuiltinFunction for QualityFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[FunctionArgReq::Net(NetType::Mixed), FunctionArgReq::Quality]
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    Some(NetType::Mixed)
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let input_net = inputs[0].get_net().unwrap();
+    // unwrap: qualities are validated by the typechecker
+    let quality = Quality::parse(inputs[1].get_string().unwrap()).unwrap();
+    let op = match self.kind {
+      QualityKind::Set => SelectorCombinatorOp::QualityTransfer(quality),
+      QualityKind::Filter => SelectorCombinatorOp::QualityFilter(quality),
+    };
+    state.new_combinator(Combinator::Selector(SelectorCombinator {
+      op,
+      input_nets: [None, None],
+      output_nets: [None, None],
+      origin: Default::default(),
+    }), Some(input_net), None, output);
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
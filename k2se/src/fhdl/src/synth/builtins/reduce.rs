@@ -0,0 +1,223 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::passthrough::Passthrough;
+use crate::synth::builtins::{materialize, register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
+use crate::synth::combinator::{Combinator, SignalRef, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+/// Values are offset by this much before searching for the maximum, so that they're all positive.
+/// This limits inputs to `(-SEARCH_OFFSET, SEARCH_OFFSET)`.
+const SEARCH_OFFSET: i32 = 1 << 30;
+/// Each stage of the search decides one bit, starting from this one.
+const SEARCH_TOP_BIT: u32 = 30;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ReduceKind {
+  Sum,
+  Count,
+  Max,
+  Min,
+  ArgMax,
+  ArgMin,
+}
+
+/// Builtin functions that reduce a mixed net to a single value.
+#[derive(Debug)]
+pub struct ReduceFunc {
+  kind: ReduceKind,
+}
+
+impl ReduceFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "sum", ReduceFunc { kind: ReduceKind::Sum });
+    register(b, "count", ReduceFunc { kind: ReduceKind::Count });
+    register(b, "max", ReduceFunc { kind: ReduceKind::Max });
+    register(b, "min", ReduceFunc { kind: ReduceKind::Min });
+    register(b, "argmax", ReduceFunc { kind: ReduceKind::ArgMax });
+    register(b, "argmin", ReduceFunc { kind: ReduceKind::ArgMin });
+  }
+}
+
+impl BuiltinFunction for ReduceFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[FunctionArgReq::Net(NetType::Mixed)]
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    match self.kind {
+      ReduceKind::ArgMax | ReduceKind::ArgMin => Some(NetType::Mixed),
+      _ => Some(NetType::Single),
+    }
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let input = inputs[0].get_net().unwrap();
+    match self.kind {
+      ReduceKind::Sum => {
+        // arithmetic combinators sum up all signals when reading `Each` into one signal
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Add,
+          input_signals: [SignalRef::Each, SignalRef::Const(0)],
+          output_signal: SignalRef::IncompleteSignal(output),
+          output_count: false,
+          .. Default::default()
+        }), Some(input), None, output);
+      }
+      ReduceKind::Count => {
+        // likewise, deciders output 1 for each signal that passes
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Ne,
+          input_signals: [SignalRef::Each, SignalRef::Const(0)],
+          output_signal: SignalRef::IncompleteSignal(output),
+          output_count: false,
+          .. Default::default()
+        }), Some(input), None, output);
+      }
+      ReduceKind::Max => synthesize_search(state, input, false, output, false),
+      ReduceKind::Min => synthesize_search(state, input, true, output, false),
+      ReduceKind::ArgMax => synthesize_search(state, input, false, output, true),
+      ReduceKind::ArgMin => synthesize_search(state, input, true, output, true),
+    }
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
+
+/// Finds the largest value on a mixed net, or the smallest if `negate` is set.
+/// If `want_arg` is set, the output is a mixed net with all signals that have that value set to 1,
+/// otherwise it's the value itself.
+///
+/// This is a binary search over the bits of the value. The candidates are offset by `SEARCH_OFFSET`
+/// so that they're all positive, and are kept as their distance from the current lower bound plus one,
+/// so that they never disappear from the wire by becoming 0. Each stage takes two ticks: on the first,
+/// the candidates above the midpoint are found, and on the second, they're kept if there are any,
+/// otherwise the rest are kept. Whether there are any is decided from the candidates alone, since
+/// a separate condition net would be summed with a candidate on the same signal.
+fn synthesize_search(
+  state: &mut ModuleSynthState,
+  input: IncompleteNetID,
+  negate: bool,
+  output: IncompleteNetID,
+  want_arg: bool,
+) {
+  let sign = if negate { -1 } else { 1 };
+  let input = if negate {
+    let negated = new_mixed_net(state);
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Mul,
+      input_signals: [SignalRef::Each, SignalRef::Const(-1)],
+      output_signal: SignalRef::Each,
+      output_count: false,
+      .. Default::default()
+    }), Some(input), None, negated);
+    negated
+  } else {
+    input
+  };
+  let mut candidates = new_mixed_net(state);
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Add,
+    input_signals: [SignalRef::Each, SignalRef::Const(SEARCH_OFFSET)],
+    output_signal: SignalRef::Each,
+    output_count: false,
+    .. Default::default()
+  }), Some(input), None, candidates);
+  // the sum of the midpoints that were passed so far
+  let mut lower_bound = None;
+  for bit in (0..=SEARCH_TOP_BIT).rev() {
+    let midpoint = 1 << bit;
+    let last = bit == 0;
+
+    // first tick: find the candidates above the midpoint
+    let above = new_mixed_net(state);
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Gt,
+      input_signals: [SignalRef::Each, SignalRef::Const(midpoint)],
+      output_signal: SignalRef::Each,
+      output_count: true,
+      .. Default::default()
+    }), Some(candidates), None, above);
+    let delayed = new_mixed_net(state);
+    Passthrough.synthesize(state, &[SynthRef::Net(candidates)], delayed).unwrap();
+
+    // second tick: keep the candidates above the midpoint, shifted down, or all of them
+    let next = if last && want_arg { output } else { new_mixed_net(state) };
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Sub,
+      input_signals: [SignalRef::Each, SignalRef::Const(midpoint)],
+      output_signal: SignalRef::Each,
+      output_count: false,
+      .. Default::default()
+    }), Some(above), None, next);
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Le,
+      input_signals: [SignalRef::Everything, SignalRef::Const(midpoint)],
+      output_signal: SignalRef::Everything,
+      output_count: true,
+      .. Default::default()
+    }), Some(delayed), None, next);
+
+    if !want_arg {
+      // the count is only read along with a constant, so its signal doesn't matter
+      let above_count = new_single_net(state);
+      state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+        op: VanillaCombinatorOp::Gt,
+        input_signals: [SignalRef::Each, SignalRef::Const(midpoint)],
+        output_signal: SignalRef::IncompleteSignal(above_count),
+        output_count: false,
+        .. Default::default()
+      }), Some(candidates), None, above_count);
+      // the lower bound has to be delayed to stay in step with the candidates
+      let next_bound = if last { output } else { new_single_net(state) };
+      if let Some(bound) = lower_bound {
+        let delayed_bound = new_single_net(state);
+        Passthrough.synthesize(state, &[SynthRef::Net(bound)], delayed_bound).unwrap();
+        Passthrough.synthesize(state, &[SynthRef::Net(delayed_bound)], next_bound).unwrap();
+      }
+      add_if_nonzero(state, sign * midpoint, above_count, next_bound);
+      if last {
+        // the lower bound started out at the offset, but only if there was anything to search
+        let nonempty = new_single_net(state);
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Ne,
+          input_signals: [SignalRef::Each, SignalRef::Const(0)],
+          output_signal: SignalRef::IncompleteSignal(nonempty),
+          output_count: false,
+          .. Default::default()
+        }), Some(candidates), None, nonempty);
+        add_if_nonzero(state, sign * (1 - SEARCH_OFFSET), nonempty, output);
+      }
+      lower_bound = Some(next_bound);
+    }
+    candidates = next;
+  }
+}
+
+/// Adds a constant to a single net if the condition net is nonzero.
+fn add_if_nonzero(state: &mut ModuleSynthState, val: i32, cond: IncompleteNetID, output: IncompleteNetID) {
+  // deciders can only pass values through, so the constant has to be on the output signal
+  let constant = materialize(state, &SynthRef::Value(val));
+  state.require_same_signal(constant, output);
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Ne,
+    input_signals: [SignalRef::IncompleteSignal(cond), SignalRef::Const(0)],
+    output_signal: SignalRef::IncompleteSignal(output),
+    output_count: true,
+    .. Default::default()
+  }), Some(constant), Some(cond), output);
+}
+
+fn new_mixed_net(state: &mut ModuleSynthState) -> IncompleteNetID {
+  state.new_net_builder()
+    .net_type(NetType::Mixed)
+    .build(state)
+}
+
+fn new_single_net(state: &mut ModuleSynthState) -> IncompleteNetID {
+  state.new_net_builder()
+    .net_type(NetType::Single)
+    .build(state)
+}
@@ -0,0 +1,107 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::{delay, materialize, register, scale_condition, BuiltinFunction, Builtins, FunctionArgReq, SynthRef, MIXED_CONDITION};
+use crate::synth::combinator::{Combinator, SignalRef, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+/// Implements `reg(x, enable)`, a latch that samples `x` on every tick where `enable` is nonzero,
+/// and holds the last sampled value otherwise.
+///
+/// This is synthesized as a sampling decider and a holding decider that feeds back into itself,
+/// both gated on `enable`. Like `select`, the sampled single net has to share a signal with the output.
+/// Mixed registers scale the enable first, see `synthesize_mixed`.
+#[derive(Debug)]
+pub struct RegFunc;
+
+impl RegFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "reg", RegFunc);
+  }
+}
+
+impl BuiltinFunction for RegFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[FunctionArgReq::Any, FunctionArgReq::SingleOrLit]
+  }
+
+  fn return_ty(&self, arg_ty: &[Option<NetType>]) -> Option<NetType> {
+    arg_ty.first().copied().flatten()
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let enable = materialize(state, &inputs[1]);
+    if state.net_info(output).ty == NetType::Mixed {
+      synthesize_mixed(state, inputs[0].get_net().unwrap(), enable, output);
+      return Ok(());
+    }
+    let input = materialize(state, &inputs[0]);
+    // the input can't be tied to the output if it's already fixed to another signal,
+    // so it's moved onto a new net, along with the enable signal to keep them aligned
+    let conflict = match (state.group_signal(input), state.group_signal(output)) {
+      (Some(a), Some(b)) => a != b,
+      _ => false,
+    };
+    let (input, enable) = if conflict {
+      (delay(state, input), delay(state, enable))
+    } else {
+      (input, enable)
+    };
+    state.require_same_signal(input, output);
+    // sample the input while enabled
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Ne,
+      input_signals: [SignalRef::IncompleteSignal(enable), SignalRef::Const(0)],
+      output_signal: SignalRef::IncompleteSignal(output),
+      output_count: true,
+      .. Default::default()
+    }), Some(input), Some(enable), output);
+    // hold the output while disabled
+    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Eq,
+      input_signals: [SignalRef::IncompleteSignal(enable), SignalRef::Const(0)],
+      output_signal: SignalRef::IncompleteSignal(output),
+      output_count: true,
+      .. Default::default()
+    }), Some(output), Some(enable), output);
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
+
+/// Synthesizes a mixed register.
+///
+/// The deciders read the enable summed with the data, which may carry the enable's signal too,
+/// so they read the enable through `scale_condition`. The input is delayed along with the scaling,
+/// to keep it aligned with the enable.
+fn synthesize_mixed(state: &mut ModuleSynthState, input: IncompleteNetID, enable: IncompleteNetID, output: IncompleteNetID) {
+  let scaled = scale_condition(state, enable);
+  let input = delay(state, input);
+  let input = delay(state, input);
+  // sample the input while enabled
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Gt,
+    input_signals: [SignalRef::IncompleteSignal(scaled), SignalRef::Const(MIXED_CONDITION / 2)],
+    output_signal: SignalRef::Everything,
+    output_count: true,
+    .. Default::default()
+  }), Some(input), Some(scaled), output);
+  // sampling passes the enable signal through as well, so cancel it out on the same tick
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Mul,
+    input_signals: [SignalRef::IncompleteSignal(scaled), SignalRef::Const(-1)],
+    output_signal: SignalRef::IncompleteSignal(scaled),
+    output_count: false,
+    .. Default::default()
+  }), Some(scaled), None, output);
+  // hold the output while disabled
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Lt,
+    input_signals: [SignalRef::IncompleteSignal(scaled), SignalRef::Const(MIXED_CONDITION / 2)],
+    output_signal: SignalRef::Everything,
+    output_count: true,
+    .. Default::default()
+  }), Some(output), Some(scaled), output);
+}
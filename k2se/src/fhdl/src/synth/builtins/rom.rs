@@ -0,0 +1,154 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::{constants, register, BuiltinFunction, Builtins, FunctionArgReq, SynthRef};
+use crate::synth::combinator::{CCSignalRef, Combinator, Signal, SignalRef, SignalWithCount, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+/// Parses a signal argument. ROM builtins are only called by the transformation stage,
+/// which always passes valid signals.
+fn signal_arg(arg: &SynthRef) -> Signal {
+  arg.get_string()
+    .and_then(Signal::parse)
+    .expect("ROM builtins take valid signals")
+}
+
+fn value_arg(arg: &SynthRef) -> i32 {
+  match arg {
+    SynthRef::Value(val) => *val,
+    _ => panic!("ROM builtins take literal values"),
+  }
+}
+
+/// Implements `$rom_entry(select, k, signal, value, ...)`, which outputs entry `k` of a ROM
+/// as constants, along with the select signal set to `-k`.
+///
+/// The select signal is never used by the entries themselves, so when `$rom_lookup` adds the
+/// index onto it, it's zero exactly for the entry being looked up.
+#[derive(Debug)]
+pub struct RomEntryFunc;
+
+impl RomEntryFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "$rom_entry", RomEntryFunc);
+  }
+}
+
+impl BuiltinFunction for RomEntryFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    panic!("ROM builtins bypass typechecking")
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    Some(NetType::Mixed)
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let select = SignalWithCount {
+      signal: signal_arg(&inputs[0]),
+      count: value_arg(&inputs[1]).wrapping_neg(),
+    };
+    let signals = std::iter::once(select)
+      .chain(inputs[2..].chunks(2).map(|pair| SignalWithCount {
+        signal: signal_arg(&pair[0]),
+        count: value_arg(&pair[1]),
+      }))
+      // zero is the same as not outputting the signal
+      .filter(|v| v.count != 0)
+      .map(CCSignalRef::Signal)
+      .collect::<Vec<_>>();
+    constants(state, signals, output);
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
+
+/// Implements `$rom_lookup(index, select, [value,] entries...)`, which outputs the entry of a
+/// ROM at the given index, or nothing if the index is out of range.
+///
+/// The index is moved onto the select signal, and each entry is gated by a decider that passes
+/// everything while the select signal is zero. Since the entry holds minus its own index on the
+/// select signal, this only happens for the entry being looked up, and the select signal itself
+/// cancels out. Mixed lookups take two ticks. For single lookups, the entries hold their values
+/// on the `value` signal, which is moved onto the output on a third tick.
+#[derive(Debug)]
+pub struct RomLookupFunc;
+
+impl RomLookupFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "$rom_lookup", RomLookupFunc);
+  }
+}
+
+impl BuiltinFunction for RomLookupFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    panic!("ROM builtins bypass typechecking")
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    panic!("ROM builtins bypass typechecking")
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let select = signal_arg(&inputs[1]);
+    let single = state.net_info(output).ty == NetType::Single;
+    let (value, entries) = if single {
+      (Some(signal_arg(&inputs[2])), &inputs[3..])
+    } else {
+      (None, &inputs[2..])
+    };
+    // move the index onto the select signal
+    let selector = state.new_net_builder()
+      .net_type(NetType::Mixed)
+      .build(state);
+    match &inputs[0] {
+      SynthRef::Net(index) => {
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op: VanillaCombinatorOp::Add,
+          input_signals: [SignalRef::IncompleteSignal(*index), SignalRef::Const(0)],
+          output_signal: SignalRef::Signal(select.clone()),
+          output_count: false,
+          .. Default::default()
+        }), Some(*index), None, selector);
+      }
+      index => constants(state, vec![CCSignalRef::Signal(SignalWithCount {
+        signal: select.clone(),
+        count: value_arg(index),
+      })], selector),
+    }
+    let selected = if single {
+      state.new_net_builder()
+        .net_type(NetType::Mixed)
+        .build(state)
+    } else {
+      output
+    };
+    for entry in entries {
+      // unwrap: entries are always nets
+      let entry = entry.get_net().unwrap();
+      state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+        op: VanillaCombinatorOp::Eq,
+        input_signals: [SignalRef::Signal(select.clone()), SignalRef::Const(0)],
+        output_signal: SignalRef::Everything,
+        output_count: true,
+        .. Default::default()
+      }), Some(entry), Some(selector), selected);
+    }
+    if let Some(value) = value {
+      state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+        op: VanillaCombinatorOp::Add,
+        input_signals: [SignalRef::Signal(value), SignalRef::Const(0)],
+        output_signal: SignalRef::IncompleteSignal(output),
+        output_count: false,
+        .. Default::default()
+      }), Some(selected), None, output);
+    }
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    None
+  }
+}
@@ -0,0 +1,113 @@
+use crate::err::Cerr;
+use crate::parse::ast::NetType;
+use crate::synth::builtins::passthrough::Passthrough;
+use crate::synth::builtins::{delay, materialize, register, scale_condition, BuiltinFunction, Builtins, FunctionArgReq, SynthRef, MIXED_CONDITION};
+use crate::synth::combinator::{Combinator, SignalRef, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
+
+/// Implements `select(cond, a, b)`, which outputs `a` if `cond` is nonzero and `b` otherwise.
+///
+/// This is synthesized as two decider combinators gated on the condition, whose outputs are summed
+/// on the output net. Since deciders cannot change the signal of their input, for single nets
+/// `a`, `b`, and the output are constrained to share a signal. Mixed selects scale the condition
+/// first, see `synthesize_mixed`.
+#[derive(Debug)]
+pub struct SelectFunc;
+
+impl SelectFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "select", SelectFunc);
+  }
+}
+
+impl BuiltinFunction for SelectFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[FunctionArgReq::SingleOrLit, FunctionArgReq::Any, FunctionArgReq::SameAs(1)]
+  }
+
+  fn return_ty(&self, arg_ty: &[Option<NetType>]) -> Option<NetType> {
+    arg_ty.get(1).copied().flatten()
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let cond = match &inputs[0] {
+      SynthRef::Net(net) => *net,
+      SynthRef::Value(val) => {
+        // condition is constant, so just pass the chosen branch through
+        let chosen = if *val != 0 { &inputs[1] } else { &inputs[2] };
+        return Passthrough.synthesize(state, std::slice::from_ref(chosen), output);
+      }
+      SynthRef::String(_) => panic!("Unexpected string"),
+    };
+    if state.net_info(output).ty == NetType::Mixed {
+      synthesize_mixed(state, cond, inputs[1].get_net().unwrap(), inputs[2].get_net().unwrap(), output);
+      return Ok(());
+    }
+    let a = materialize(state, &inputs[1]);
+    let b = materialize(state, &inputs[2]);
+    // if both branches are already fixed to different signals, they can't be tied together,
+    // and a branch that is the condition itself would be read twice by its decider,
+    // so all inputs are delayed through passthroughs onto new nets to keep them aligned
+    let out_sig = state.group_signal(output);
+    let sigs = [state.group_signal(a), state.group_signal(b)];
+    let conflict = sigs.iter().flatten().any(|v| sigs.iter().flatten().chain(out_sig.iter()).any(|v2| v != v2))
+      || a == cond
+      || b == cond;
+    let (cond, a, b) = if conflict {
+      (delay(state, cond), delay(state, a), delay(state, b))
+    } else {
+      (cond, a, b)
+    };
+    state.require_same_signal(a, output);
+    state.require_same_signal(b, output);
+    [(a, VanillaCombinatorOp::Ne), (b, VanillaCombinatorOp::Eq)].into_iter()
+      .for_each(|(branch, op)| {
+        state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+          op,
+          input_signals: [SignalRef::IncompleteSignal(cond), SignalRef::Const(0)],
+          output_signal: SignalRef::IncompleteSignal(output),
+          output_count: true,
+          .. Default::default()
+        }), Some(branch), Some(cond), output);
+      });
+    Ok(())
+  }
+
+  fn constant_fold(&self, args: &[SynthRef]) -> Option<i32> {
+    match args {
+      [SynthRef::Value(cond), SynthRef::Value(a), SynthRef::Value(b)] => Some(if *cond != 0 { *a } else { *b }),
+      _ => None,
+    }
+  }
+}
+
+/// Synthesizes a mixed select.
+///
+/// The deciders read the condition summed with a branch, which may carry the condition's signal
+/// too, so they read the condition through `scale_condition`. The branches are delayed along with
+/// the scaling, to keep them aligned with the condition.
+fn synthesize_mixed(state: &mut ModuleSynthState, cond: IncompleteNetID, a: IncompleteNetID, b: IncompleteNetID, output: IncompleteNetID) {
+  let scaled = scale_condition(state, cond);
+  let a = delay(state, a);
+  let a = delay(state, a);
+  let b = delay(state, b);
+  let b = delay(state, b);
+  [(a, VanillaCombinatorOp::Gt), (b, VanillaCombinatorOp::Lt)].into_iter()
+    .for_each(|(branch, op)| {
+      state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+        op,
+        input_signals: [SignalRef::IncompleteSignal(scaled), SignalRef::Const(MIXED_CONDITION / 2)],
+        output_signal: SignalRef::Everything,
+        output_count: true,
+        .. Default::default()
+      }), Some(branch), Some(scaled), output);
+    });
+  // the first branch passes the condition's signal through as well, so cancel it out on the same tick
+  state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Mul,
+    input_signals: [SignalRef::IncompleteSignal(scaled), SignalRef::Const(-1)],
+    output_signal: SignalRef::IncompleteSignal(scaled),
+    output_count: false,
+    .. Default::default()
+  }), Some(scaled), None, output);
+}
@@ -1,6 +1,6 @@
 use crate::err::Cerr;
 use crate::parse::ast::{NetType, TriggerKind};
-use crate::synth::builtins::{BuiltinFunction, Builtins, FunctionArgReq, register, SynthRef};
+use crate::synth::builtins::{delay, BuiltinFunction, Builtins, FunctionArgReq, register, SynthRef};
 use crate::synth::combinator::{Combinator, SignalRef, VanillaCombinator, VanillaCombinatorOp};
 use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
 
@@ -22,7 +22,7 @@ impl BuiltinFunction for TriggerFunc {
     &[FunctionArgReq::Net(NetType::Single)]
   }
 
-  fn return_ty(&self) -> Option<NetType> {
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
     Some(NetType::Single)
   }
 
@@ -34,30 +34,77 @@ impl BuiltinFunction for TriggerFunc {
       TriggerKind::Changed => VanillaCombinatorOp::Eq,
       TriggerKind::Raw => unreachable!(),
     };
-    // anonymous net that is the input but delayed
-    let delayed_input = state.new_net_builder()
+    edge_detector(state, input_net, compare_op, output);
+    Ok(())
+  }
+
+  fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
+    todo!()
+  }
+}
+
+/// Implements `rising_pulse(x)` and `falling_pulse(x)`, which output 1 for exactly one tick
+/// when `x` becomes nonzero or zero, respectively.
+#[derive(Debug)]
+pub struct PulseFunc {
+  rising: bool,
+}
+
+impl PulseFunc {
+  pub fn collect(b: &mut Builtins) {
+    register(b, "rising_pulse", PulseFunc { rising: true });
+    register(b, "falling_pulse", PulseFunc { rising: false });
+  }
+}
+
+impl BuiltinFunction for PulseFunc {
+  fn arg_ty(&self) -> &[FunctionArgReq] {
+    &[FunctionArgReq::Net(NetType::Single)]
+  }
+
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
+    Some(NetType::Single)
+  }
+
+  fn synthesize(&self, state: &mut ModuleSynthState, inputs: &[SynthRef], output: IncompleteNetID) -> Result<(), Cerr> {
+    let input_net = inputs[0].get_net().unwrap();
+    // normalize the input to 0 or 1 so that only changes between zero and nonzero count
+    let normalized = state.new_net_builder()
       .net_type(NetType::Single)
       .build(state);
-    // passthrough combinator
     state.new_combinator(Combinator::Vanilla(VanillaCombinator {
-      op: VanillaCombinatorOp::Add,
-      input_signals: [SignalRef::Each, SignalRef::Const(0)],
-      output_signal: SignalRef::Each,
+      op: VanillaCombinatorOp::Ne,
+      input_signals: [SignalRef::IncompleteSignal(input_net), SignalRef::Const(0)],
+      output_signal: SignalRef::IncompleteSignal(normalized),
       output_count: false,
       .. Default::default()
-    }), Some(input_net), None, output);
-    // compare combinator
-    state.new_combinator(Combinator::Vanilla(VanillaCombinator {
-      op: compare_op,
-      input_signals: [SignalRef::IncompleteSignal(delayed_input), SignalRef::IncompleteSignal(input_net)],
-      output_signal: SignalRef::IncompleteSignal(output),
-      output_count: false,
-      .. Default::default()
-    }), Some(delayed_input), Some(input_net), output);
+    }), Some(input_net), None, normalized);
+    // the previous value is compared against the current one, so this outputs 1 on the edge
+    let compare_op = if self.rising {
+      VanillaCombinatorOp::Lt
+    } else {
+      VanillaCombinatorOp::Gt
+    };
+    edge_detector(state, normalized, compare_op, output);
     Ok(())
   }
 
   fn constant_fold(&self, _: &[SynthRef]) -> Option<i32> {
-    todo!()
+    None
   }
-}
\ No newline at end of file
+}
+
+/// Compares the input from last tick against the input from this tick with `compare_op`,
+/// outputting 1 if the comparison holds.
+fn edge_detector(state: &mut ModuleSynthState, input_net: IncompleteNetID, compare_op: VanillaCombinatorOp, output: IncompleteNetID) {
+  // anonymous net that is the input but delayed
+  let delayed_input = delay(state, input_net);
+  // compare combinator, which must see the two inputs a tick apart
+  state.new_skewed_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: compare_op,
+    input_signals: [SignalRef::IncompleteSignal(delayed_input), SignalRef::IncompleteSignal(input_net)],
+    output_signal: SignalRef::IncompleteSignal(output),
+    output_count: false,
+    .. Default::default()
+  }), Some(delayed_input), Some(input_net), output);
+}
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
 use crate::parse::tokenizer::BinaryOp;
-use crate::synth::netlist::NetID;
+use crate::synth::netlist::{NetID, Origin};
 use crate::synth::synth::IncompleteNetID;
 
 /// Represents any combinator or circuit computational entity.
@@ -11,6 +12,26 @@ pub enum Combinator {
   Vanilla(VanillaCombinator),
   /// Represents a constant combinator.
   Constant(ConstantCombinator),
+  /// Represents a selector combinator.
+  Selector(SelectorCombinator),
+}
+
+impl Combinator {
+  pub fn origin(&self) -> &Origin {
+    match self {
+      Combinator::Vanilla(comb) => &comb.origin,
+      Combinator::Constant(comb) => &comb.origin,
+      Combinator::Selector(comb) => &comb.origin,
+    }
+  }
+
+  pub fn origin_mut(&mut self) -> &mut Origin {
+    match self {
+      Combinator::Vanilla(comb) => &mut comb.origin,
+      Combinator::Constant(comb) => &mut comb.origin,
+      Combinator::Selector(comb) => &mut comb.origin,
+    }
+  }
 }
 
 /// Represents an arithmetic or decider combinator.
@@ -23,6 +44,8 @@ pub struct VanillaCombinator {
   pub output_signal: SignalRef,
   /// Refers to whether the "input count" setting is enabled on a decider combinator.
   pub output_count: bool,
+  /// Set by synthesis, builtins can leave it empty.
+  pub origin: Origin,
 }
 
 impl Default for VanillaCombinator {
@@ -34,16 +57,38 @@ impl Default for VanillaCombinator {
       input_signals: [SignalRef::Each, SignalRef::Const(0)],
       output_signal: SignalRef::Each,
       output_count: false,
+      origin: Default::default(),
     }
   }
 }
 
 /// Represents a constant combinator.
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
 pub struct ConstantCombinator {
   pub enabled: bool,
   pub output_nets: [Option<NetID>; 2],
   pub output_signals: Vec<CCSignalRef>,
+  /// Set by synthesis, builtins can leave it empty.
+  pub origin: Origin,
+}
+
+/// Represents a selector combinator. Only the quality modes are supported.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct SelectorCombinator {
+  pub op: SelectorCombinatorOp,
+  pub input_nets: [Option<NetID>; 2],
+  pub output_nets: [Option<NetID>; 2],
+  /// Set by synthesis, builtins can leave it empty.
+  pub origin: Origin,
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum SelectorCombinatorOp {
+  /// Outputs the input signals of the given quality.
+  QualityFilter(Quality),
+  /// Outputs every input signal at the given quality. Signals that only differ in quality
+  /// are added together.
+  QualityTransfer(Quality),
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -89,6 +134,8 @@ impl TryFrom<BinaryOp> for VanillaCombinatorOp {
       BinaryOp::Gt => VanillaCombinatorOp::Gt,
       BinaryOp::Le => VanillaCombinatorOp::Le,
       BinaryOp::Ge => VanillaCombinatorOp::Ge,
+      BinaryOp::LAnd => return Err(()),
+      BinaryOp::LOr => return Err(()),
       BinaryOp::Assign => return Err(()),
       BinaryOp::AddAssign => return Err(()),
     })
@@ -102,10 +149,88 @@ pub enum SignalType {
   Virtual,
 }
 
+/// Every signal has a quality, and signals that only differ in quality are separate signals.
+#[derive(Debug, Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Quality {
+  #[default]
+  Normal,
+  Uncommon,
+  Rare,
+  Epic,
+  Legendary,
+}
+
+impl Quality {
+  pub const ALL: [Quality; 5] = [
+    Quality::Normal,
+    Quality::Uncommon,
+    Quality::Rare,
+    Quality::Epic,
+    Quality::Legendary,
+  ];
+
+  /// Parses a quality by its name in the game, e.g. `rare`.
+  pub fn parse(s: &str) -> Option<Quality> {
+    Quality::ALL.into_iter().find(|q| q.name() == s)
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Quality::Normal => "normal",
+      Quality::Uncommon => "uncommon",
+      Quality::Rare => "rare",
+      Quality::Epic => "epic",
+      Quality::Legendary => "legendary",
+    }
+  }
+}
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Signal {
   pub ty: SignalType,
   pub name: Cow<'static, str>,
+  pub quality: Quality,
+}
+
+impl Signal {
+  /// Parses a signal written as `<type>=<name>`, e.g. `item=iron-plate`, optionally followed
+  /// by `@<quality>` for signals that aren't of normal quality, e.g. `item=iron-plate@rare`.
+  pub fn parse(s: &str) -> Option<Signal> {
+    let (s, quality) = match s.split_once('@') {
+      Some((s, quality)) => (s, Quality::parse(quality)?),
+      None => (s, Quality::Normal),
+    };
+    let (ty, name) = s.split_once('=')?;
+    let ty = match ty {
+      "item" => SignalType::Item,
+      "fluid" => SignalType::Fluid,
+      "virtual" => SignalType::Virtual,
+      _ => return None,
+    };
+    if name.is_empty() {
+      return None;
+    }
+    Some(Signal {
+      ty,
+      name: name.to_owned().into(),
+      quality,
+    })
+  }
+}
+
+impl Display for Signal {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let ty = match self.ty {
+      SignalType::Item => "item",
+      SignalType::Fluid => "fluid",
+      SignalType::Virtual => "virtual",
+    };
+    write!(f, "{}={}", ty, self.name)?;
+    if self.quality != Quality::Normal {
+      write!(f, "@{}", self.quality.name())?;
+    }
+    Ok(())
+  }
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
@@ -4,11 +4,14 @@
 //! conditions are synthesized. Additionally, type checking is done.
 
 use crate::parse::ast::{NetType, PortDecl};
+use crate::parse::span::{FileId, Span};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct IRModule {
   pub name: String,
+  /// The file the module is declared in.
+  pub file: FileId,
   pub ports: Vec<PortDecl>,
   pub objects: HashMap<String, IRWireMemDecl>,
   pub stmts: Vec<IRStmt>,
@@ -21,6 +24,12 @@ pub struct IRWireMemDecl {
   pub ty: NetType,
   pub mem: bool,
   pub port_idx: Option<usize>,
+  /// For memory cells, the value they start with and are reset to.
+  pub init: i32,
+  /// For memory cells, the net that resets them while it's nonzero.
+  pub reset: Option<String>,
+  /// Where the object is declared, or for anonymous nets, the statement they were created for.
+  pub span: Option<Span>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -28,6 +37,8 @@ pub struct IRStmt {
   pub dest: String,
   pub op: String,
   pub args: Vec<IRValue>,
+  /// The statement this was transformed from.
+  pub span: Option<Span>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -52,10 +63,14 @@ pub struct IRTriggerStmt {
   pub dest: String,
   pub src: String,
   pub on: String,
+  /// The trigger statement that the write is conditioned on.
+  pub span: Option<Span>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct IRModuleInst {
   pub name: String,
   pub args: Vec<String>,
+  /// The statement that instantiates the module.
+  pub span: Option<Span>,
 }
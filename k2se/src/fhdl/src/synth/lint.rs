@@ -0,0 +1,225 @@
+//! Lints flag code that is valid, but likely a mistake.
+//!
+//! They run on the IR once a project transformed without errors, and each one is reported
+//! as a warning unless it's allowed or denied by name, e.g. `--deny unused-wire`.
+//! Latency mismatches are only found in a synthesized netlist, see [`lint_netlist`].
+
+use crate::err::{Lint, Warning};
+use crate::parse::ast::{NetType, PortClass, PortDecl};
+use crate::parse::span::Span;
+use crate::parse::tokenizer::BinaryOp;
+use crate::synth::builtins::binaryop::{func_name_to_binary_op, leaks_single_signal};
+use crate::synth::ir::{IRModule, IRValue};
+use crate::synth::netlist::{Netlist, Origin};
+use crate::synth::synth::SingleMixedStrategy;
+use crate::synth::timing::{analyze_latency, LatencyMismatch};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum LintLevel {
+  /// The lint isn't reported.
+  Allow,
+  /// The lint is reported as a warning.
+  #[default]
+  Warn,
+  /// The lint is reported as an error.
+  Deny,
+}
+
+/// The level of each lint, by name. Lints that weren't set are warnings.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct LintLevels {
+  levels: HashMap<&'static str, LintLevel>,
+}
+
+impl LintLevels {
+  /// Sets the level of a lint, or of every lint if `name` is `warnings`.
+  pub fn set(&mut self, name: &str, level: LintLevel) -> Result<(), String> {
+    if name == "warnings" {
+      for name in Lint::NAMES {
+        self.levels.insert(name, level);
+      }
+      return Ok(());
+    }
+    let name = Lint::NAMES
+      .into_iter()
+      .find(|v| *v == name)
+      .ok_or_else(|| format!("unknown lint '{}'", name))?;
+    self.levels.insert(name, level);
+    Ok(())
+  }
+
+  pub fn level(&self, lint: &Lint) -> LintLevel {
+    self.levels.get(lint.name()).copied().unwrap_or_default()
+  }
+}
+
+/// Checks every module for lints. `strategy` is the one the modules will be synthesized with,
+/// since dirty ops are only a problem with [`SingleMixedStrategy::Dirty`].
+/// Warnings are sorted by where they were found.
+pub fn lint_modules(modules: &[IRModule], strategy: SingleMixedStrategy) -> Vec<Warning> {
+  let ports = modules
+    .iter()
+    .map(|v| (v.name.as_str(), v.ports.as_slice()))
+    .collect::<HashMap<_, _>>();
+  let mut warnings = modules
+    .iter()
+    .flat_map(|module| {
+      lint_module(module, &ports, strategy)
+        .into_iter()
+        .map(|(span, lint)| Warning { span, file: module.file, lint })
+    })
+    .collect::<Vec<_>>();
+  warnings.sort_by_key(|v| (v.file, v.span.map(|v| (v.start.line, v.start.col))));
+  warnings
+}
+
+/// Checks a synthesized netlist for places where paths of different latency are combined,
+/// which are reported where the combinator or net was synthesized from.
+pub fn lint_netlist(netlist: &Netlist) -> Vec<Warning> {
+  let warning = |origin: &Origin, latencies: Vec<u32>| {
+    let min = latencies.iter().copied().min().unwrap_or_default();
+    let max = latencies.iter().copied().max().unwrap_or_default();
+    Warning { span: origin.span, file: origin.file, lint: Lint::LatencyMismatch(min, max) }
+  };
+  let mut warnings = analyze_latency(netlist)
+    .mismatches
+    .into_iter()
+    .map(|mismatch| match mismatch {
+      LatencyMismatch::CombinatorInputs { combinator, latencies } => {
+        warning(netlist.combinators[combinator].origin(), latencies.to_vec())
+      }
+      LatencyMismatch::NetWriters { net, writers } => {
+        // ports aren't declared in the module body, so the slowest writer is shown instead
+        let origin = Some(&netlist.nets[net].origin)
+          .filter(|v| v.span.is_some())
+          .or_else(|| writers.iter().max_by_key(|v| v.1).map(|v| netlist.combinators[v.0].origin()))
+          .unwrap_or(&netlist.nets[net].origin);
+        warning(origin, writers.into_iter().map(|(_, v)| v).collect())
+      }
+    })
+    .collect::<Vec<_>>();
+  warnings.sort_by_key(|v| (v.file, v.span.map(|v| (v.start.line, v.start.col))));
+  // a combinator and the net it writes may be synthesized from the same statement
+  warnings.dedup();
+  warnings
+}
+
+fn lint_module(
+  module: &IRModule,
+  ports: &HashMap<&str, &[PortDecl]>,
+  strategy: SingleMixedStrategy,
+) -> Vec<(Option<Span>, Lint)> {
+  let mut lints = vec![];
+  let mut reads = HashSet::new();
+  let mut writes = HashSet::new();
+  // nets written by something other than a statement, which are never constant
+  let mut driven = HashSet::new();
+  for stmt in &module.stmts {
+    writes.insert(stmt.dest.as_str());
+    reads.extend(stmt.args.iter().filter_map(net_name));
+  }
+  for stmt in &module.trigger_stmt {
+    writes.insert(stmt.dest.as_str());
+    driven.insert(stmt.dest.as_str());
+    reads.insert(stmt.src.as_str());
+    reads.insert(stmt.on.as_str());
+  }
+  for inst in &module.module_inst {
+    for (i, arg) in inst.args.iter().enumerate() {
+      // if the module doesn't exist, the argument is assumed to be both read and written
+      let class = ports.get(inst.name.as_str()).and_then(|v| v.get(i)).map(|v| v.port_class);
+      if class != Some(PortClass::Out) {
+        reads.insert(arg.as_str());
+      }
+      if class != Some(PortClass::In) {
+        writes.insert(arg.as_str());
+        driven.insert(arg.as_str());
+      }
+    }
+  }
+  reads.extend(module.objects.values().filter_map(|v| v.reset.as_deref()));
+
+  for (name, decl) in &module.objects {
+    if name.starts_with('$') || decl.port_idx.is_some() {
+      continue;
+    }
+    let lint = if decl.mem {
+      (!writes.contains(name.as_str())).then(|| Lint::UnwrittenMem(name.clone()))
+    } else if !reads.contains(name.as_str()) {
+      Some(Lint::UnusedWire(name.clone()))
+    } else if !writes.contains(name.as_str()) {
+      Some(Lint::UnwrittenWire(name.clone()))
+    } else {
+      None
+    };
+    lints.extend(lint.map(|v| (decl.span, v)));
+  }
+
+  let constant = constant_nets(module, &driven);
+  // writes to memory with a reset are gated on it like a trigger, which isn't written as one
+  let resets = module.objects.values().filter_map(|v| v.reset.as_deref()).collect::<HashSet<_>>();
+  let mut reported = HashSet::new();
+  for stmt in &module.trigger_stmt {
+    if resets.contains(stmt.on.as_str()) {
+      continue;
+    }
+    if constant.contains(stmt.on.as_str()) && reported.insert(stmt.on.as_str()) {
+      lints.push((stmt.span, Lint::ConstantTrigger));
+    }
+  }
+
+  for stmt in &module.stmts {
+    let Some(op) = func_name_to_binary_op(&stmt.op) else {
+      continue;
+    };
+    if let (BinaryOp::Shl | BinaryOp::Shr, [_, IRValue::Lit(amount)]) = (op, stmt.args.as_slice()) {
+      if *amount >= 32 {
+        lints.push((stmt.span, Lint::ShiftOverflow(*amount)));
+      }
+    }
+    if strategy == SingleMixedStrategy::Dirty && leaks_single_signal(op) {
+      // literals have no signal, so they never leak
+      let ty = |v: &IRValue| net_name(v).and_then(|v| module.objects.get(v)).map(|v| v.ty);
+      let mut types = [ty(&stmt.args[0]), ty(&stmt.args[1])];
+      types.sort();
+      if types == [Some(NetType::Single), Some(NetType::Mixed)] {
+        lints.push((stmt.span, Lint::DirtyOp(op)));
+      }
+    }
+  }
+  lints
+}
+
+fn net_name(value: &IRValue) -> Option<&str> {
+  match value {
+    IRValue::Net(net) => Some(net),
+    _ => None,
+  }
+}
+
+/// Finds the nets that only ever hold one value: those only written by statements that only
+/// read literals and other constant nets. Edge detectors respond to their input being set
+/// when the circuit starts, so they are never constant, and neither are memory cells, ports,
+/// or `driven` nets.
+fn constant_nets<'a>(module: &'a IRModule, driven: &HashSet<&str>) -> HashSet<&'a str> {
+  let mut constant = HashSet::new();
+  loop {
+    let mut writers = HashMap::new();
+    for stmt in &module.stmts {
+      let is_constant = !stmt.op.starts_with("trig_")
+        && stmt.args.iter().all(|v| net_name(v).is_none_or(|v| constant.contains(v)));
+      *writers.entry(stmt.dest.as_str()).or_insert(true) &= is_constant;
+    }
+    let mut changed = false;
+    for (net, is_constant) in writers {
+      let candidate = module.objects.get(net).is_some_and(|v| !v.mem && v.port_idx.is_none());
+      if is_constant && candidate && !driven.contains(net) {
+        changed |= constant.insert(net);
+      }
+    }
+    if !changed {
+      return constant;
+    }
+  }
+}
@@ -3,7 +3,10 @@
 pub mod builtins;
 pub mod combinator;
 pub mod ir;
+pub mod lint;
 pub mod netlist;
+pub mod sim;
 pub mod synth;
+pub mod timing;
 pub mod transform;
 pub mod virt_signals;
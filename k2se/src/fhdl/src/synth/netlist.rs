@@ -1,4 +1,6 @@
+use std::fmt::{Display, Formatter};
 use crate::parse::ast::NetType;
+use crate::parse::span::{FileId, Span};
 use crate::synth::combinator::{Combinator, Signal};
 
 pub type NetID = usize;
@@ -15,6 +17,41 @@ pub struct Netlist {
   pub combinators: Vec<Combinator>,
 }
 
+impl Netlist {
+  /// Finds the nets that were synthesized from a wire, one of each color, given its instance path.
+  pub fn nets_at<'a>(&'a self, path: &'a str) -> impl Iterator<Item = NetID> + 'a {
+    self.nets
+      .iter()
+      .enumerate()
+      .filter(move |(_, net)| net.origin.path == path)
+      .map(|(id, _)| id)
+  }
+}
+
+/// Where a net or combinator came from in the source program.
+#[derive(Debug, Clone, Default, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Origin {
+  /// The instance path, made of the main module's name, the instances leading to the module
+  /// and the wire, such as `top.counter0.reset2`. Instances are named after their module and
+  /// numbered in order within their parent. Anonymous nets only have the path of their instance.
+  pub path: String,
+  pub file: FileId,
+  /// The declaration or statement the net or combinator was synthesized from.
+  pub span: Option<Span>,
+  /// Set on combinators that read inputs from different ticks on purpose, like an edge detector
+  /// comparing a net with its delayed self. Latency analysis leaves their inputs alone.
+  pub skewed: bool,
+}
+
+impl Display for Origin {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self.span {
+      Some(span) => write!(f, "{} ({}:{})", self.path, span.start.line, span.start.col),
+      None => write!(f, "{}", self.path),
+    }
+  }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Net {
   pub ty: NetType,
@@ -26,6 +63,7 @@ pub struct Net {
   /// Named from the perspective of a combinator. This declares
   /// all combinators that write to the net.
   pub out_conn: Vec<(CombinatorID, usize)>,
+  pub origin: Origin,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -0,0 +1,258 @@
+//! Tick-by-tick simulation of a synthesized netlist.
+//!
+//! Every combinator reads the sum of its red and green input nets and writes its result one tick
+//! later, like in the game. A net carries the sum of everything written to it, and the external
+//! connections of the netlist can be driven with values from the outside. Signals with a value of 0
+//! are never stored, just like the game leaves them off the wire.
+
+use std::collections::BTreeMap;
+use crate::synth::combinator::{CCSignalRef, Combinator, SelectorCombinator, SelectorCombinatorOp, Signal, SignalRef, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::netlist::{NetID, Netlist, WireColor};
+
+/// The signals on a net, along with their values.
+pub type SignalValues = BTreeMap<Signal, i32>;
+
+#[derive(Debug, Clone)]
+pub struct Simulator<'a> {
+  netlist: &'a Netlist,
+  /// What each combinator currently outputs.
+  outputs: Vec<SignalValues>,
+  /// What is driven onto each external connection.
+  inputs: Vec<SignalValues>,
+  tick: u64,
+}
+
+impl<'a> Simulator<'a> {
+  /// Creates a simulator where every combinator except the constants outputs nothing yet.
+  pub fn new(netlist: &'a Netlist) -> Self {
+    let outputs = netlist
+      .combinators
+      .iter()
+      .map(|comb| match comb {
+        Combinator::Constant(comb) if comb.enabled => {
+          let mut values = SignalValues::new();
+          comb.output_signals.iter().for_each(|v| match v {
+            CCSignalRef::Signal(v) => add(&mut values, &v.signal, v.count),
+            // constants are always resolved by synthesis
+            CCSignalRef::IncompleteSignal(..) => (),
+          });
+          values
+        }
+        _ => SignalValues::new(),
+      })
+      .collect();
+    Simulator {
+      netlist,
+      outputs,
+      inputs: vec![SignalValues::new(); netlist.net_external_conn.len()],
+      tick: 0,
+    }
+  }
+
+  /// The number of ticks simulated so far.
+  pub fn tick(&self) -> u64 {
+    self.tick
+  }
+
+  /// Drives both wire colors of an external connection with the given signals, until it's set again.
+  pub fn set_input(&mut self, port: usize, values: SignalValues) {
+    self.inputs[port] = values.into_iter().filter(|(_, v)| *v != 0).collect();
+  }
+
+  /// Drives a single external connection with a value on its signal.
+  pub fn set_single(&mut self, port: usize, value: i32) {
+    let signal = self.port_signal(port);
+    self.set_input(port, SignalValues::from([(signal, value)]));
+  }
+
+  /// Reads everything on an external connection. Both wire colors of a connection carry the
+  /// same values, so only the red one is read.
+  pub fn read(&self, port: usize) -> SignalValues {
+    self.net_value(self.netlist.net_external_conn[port].red_net)
+  }
+
+  /// Reads a wire anywhere in the netlist by its instance path, such as `top.counter0.value`.
+  /// Returns `None` if there's no such wire.
+  pub fn probe(&self, path: &str) -> Option<SignalValues> {
+    self
+      .netlist
+      .nets_at(path)
+      .find(|net| self.netlist.nets[*net].color == WireColor::Red)
+      .map(|net| self.net_value(net))
+  }
+
+  /// Reads the value on the signal of a single external connection.
+  pub fn read_single(&self, port: usize) -> i32 {
+    let signal = self.port_signal(port);
+    self.read(port).get(&signal).copied().unwrap_or(0)
+  }
+
+  /// Advances the simulation by one tick.
+  pub fn step(&mut self) {
+    let nets = (0..self.netlist.nets.len()).map(|net| self.net_value(net)).collect::<Vec<_>>();
+    let outputs = self
+      .netlist
+      .combinators
+      .iter()
+      .zip(&self.outputs)
+      .map(|(comb, output)| match comb {
+        Combinator::Vanilla(comb) => {
+          let mut input = SignalValues::new();
+          comb.input_nets.iter().flatten().for_each(|net| {
+            nets[*net].iter().for_each(|(s, v)| add(&mut input, s, *v));
+          });
+          evaluate(comb, &input)
+        }
+        Combinator::Selector(comb) => {
+          let mut input = SignalValues::new();
+          comb.input_nets.iter().flatten().for_each(|net| {
+            nets[*net].iter().for_each(|(s, v)| add(&mut input, s, *v));
+          });
+          select(comb, &input)
+        }
+        Combinator::Constant(_) => output.clone(),
+      })
+      .collect();
+    self.outputs = outputs;
+    self.tick += 1;
+  }
+
+  /// Advances the simulation by several ticks.
+  pub fn run(&mut self, ticks: u64) {
+    (0..ticks).for_each(|_| self.step());
+  }
+
+  fn port_signal(&self, port: usize) -> Signal {
+    self.netlist.net_external_conn[port]
+      .signal
+      .clone()
+      .expect("external connection is not single")
+  }
+
+  fn net_value(&self, id: NetID) -> SignalValues {
+    let net = &self.netlist.nets[id];
+    let mut values = SignalValues::new();
+    net.out_conn.iter().for_each(|(comb, _)| {
+      self.outputs[*comb].iter().for_each(|(s, v)| add(&mut values, s, *v));
+    });
+    self
+      .netlist
+      .net_external_conn
+      .iter()
+      .zip(&self.inputs)
+      .filter(|(conn, _)| conn.red_net == id || conn.green_net == id)
+      .for_each(|(_, input)| input.iter().for_each(|(s, v)| add(&mut values, s, *v)));
+    values
+  }
+}
+
+/// Adds a value to a signal, dropping the signal once it sums to 0.
+fn add(values: &mut SignalValues, signal: &Signal, value: i32) {
+  let sum = values.get(signal).copied().unwrap_or(0).wrapping_add(value);
+  if sum == 0 {
+    values.remove(signal);
+  } else {
+    values.insert(signal.clone(), sum);
+  }
+}
+
+fn evaluate(comb: &VanillaCombinator, input: &SignalValues) -> SignalValues {
+  let mut output = SignalValues::new();
+  let right = value_of(&comb.input_signals[1], input);
+  match comb.op {
+    VanillaCombinatorOp::Eq
+    | VanillaCombinatorOp::Ne
+    | VanillaCombinatorOp::Gt
+    | VanillaCombinatorOp::Lt
+    | VanillaCombinatorOp::Ge
+    | VanillaCombinatorOp::Le => {
+      let passes = |v: i32| compare(&comb.op, v, right);
+      let out_value = |v: i32| if comb.output_count { v } else { 1 };
+      if comb.input_signals[0] == SignalRef::Each {
+        input.iter().filter(|(_, v)| passes(**v)).for_each(|(s, v)| match &comb.output_signal {
+          SignalRef::Signal(o) => add(&mut output, o, out_value(*v)),
+          _ => add(&mut output, s, out_value(*v)),
+        });
+        return output;
+      }
+      let condition = match &comb.input_signals[0] {
+        SignalRef::Everything => input.values().all(|v| passes(*v)),
+        SignalRef::Anything => input.values().any(|v| passes(*v)),
+        left => passes(value_of(left, input)),
+      };
+      if condition {
+        match &comb.output_signal {
+          SignalRef::Signal(o) => add(&mut output, o, out_value(value_of(&comb.output_signal, input))),
+          _ => input.iter().for_each(|(s, v)| add(&mut output, s, out_value(*v))),
+        }
+      }
+    }
+    _ => {
+      if comb.input_signals[0] == SignalRef::Each {
+        input.iter().for_each(|(s, v)| {
+          let result = arithmetic(&comb.op, *v, right);
+          match &comb.output_signal {
+            SignalRef::Signal(o) => add(&mut output, o, result),
+            _ => add(&mut output, s, result),
+          }
+        });
+      } else if let SignalRef::Signal(o) = &comb.output_signal {
+        let left = value_of(&comb.input_signals[0], input);
+        add(&mut output, o, arithmetic(&comb.op, left, right));
+      }
+    }
+  }
+  output
+}
+
+fn select(comb: &SelectorCombinator, input: &SignalValues) -> SignalValues {
+  let mut output = SignalValues::new();
+  match comb.op {
+    SelectorCombinatorOp::QualityFilter(quality) => {
+      input.iter().filter(|(s, _)| s.quality == quality).for_each(|(s, v)| add(&mut output, s, *v));
+    }
+    SelectorCombinatorOp::QualityTransfer(quality) => {
+      input.iter().for_each(|(s, v)| add(&mut output, &Signal { quality, ..s.clone() }, *v));
+    }
+  }
+  output
+}
+
+/// The value of a single signal or constant operand. Wildcards have no single value.
+fn value_of(signal: &SignalRef, input: &SignalValues) -> i32 {
+  match signal {
+    SignalRef::Signal(s) => input.get(s).copied().unwrap_or(0),
+    SignalRef::Const(v) => *v,
+    _ => 0,
+  }
+}
+
+fn arithmetic(op: &VanillaCombinatorOp, a: i32, b: i32) -> i32 {
+  match op {
+    VanillaCombinatorOp::Add => a.wrapping_add(b),
+    VanillaCombinatorOp::Sub => a.wrapping_sub(b),
+    VanillaCombinatorOp::Mul => a.wrapping_mul(b),
+    // the game outputs 0 instead of failing on a division by 0
+    VanillaCombinatorOp::Div => a.checked_div(b).unwrap_or(0),
+    VanillaCombinatorOp::Mod => a.checked_rem(b).unwrap_or(0),
+    VanillaCombinatorOp::Pow => u32::try_from(b).map(|b| a.wrapping_pow(b)).unwrap_or(0),
+    VanillaCombinatorOp::And => a & b,
+    VanillaCombinatorOp::Or => a | b,
+    VanillaCombinatorOp::Xor => a ^ b,
+    VanillaCombinatorOp::Shl => a.wrapping_shl(b as u32),
+    VanillaCombinatorOp::Shr => a.wrapping_shr(b as u32),
+    _ => unreachable!("not an arithmetic op"),
+  }
+}
+
+fn compare(op: &VanillaCombinatorOp, a: i32, b: i32) -> bool {
+  match op {
+    VanillaCombinatorOp::Eq => a == b,
+    VanillaCombinatorOp::Ne => a != b,
+    VanillaCombinatorOp::Gt => a > b,
+    VanillaCombinatorOp::Lt => a < b,
+    VanillaCombinatorOp::Ge => a >= b,
+    VanillaCombinatorOp::Le => a <= b,
+    _ => unreachable!("not a comparison"),
+  }
+}
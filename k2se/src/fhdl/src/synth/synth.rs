@@ -1,14 +1,14 @@
-use crate::err::Cerr;
+use crate::err::{Cerr, CerrSpan};
 use crate::parse::ast::{NetType, PortDecl};
+use crate::parse::span::{FileId, Span};
 use crate::synth::combinator::{CCSignalRef, Combinator, Signal, SignalRef, SignalWithCount, VanillaCombinator, VanillaCombinatorOp};
 use crate::synth::ir::{IRModule, IRModuleInst, IRStmt, IRTriggerStmt, IRValue, IRWireMemDecl};
-use crate::synth::netlist::{Net, NetID, Netlist, WireColor};
-use std::collections::{BTreeSet, HashMap};
+use crate::synth::netlist::{ExternalConn, Net, NetID, Netlist, Origin, WireColor};
+use crate::synth::timing::balance_latency;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use crate::synth::virt_signals::VIRTUAL_SIGNALS;
 
-use super::builtins::{Builtins, SynthRef};
-
-//todo better err reporting for synth errors
+use super::builtins::{materialize, Builtins, SynthRef};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct SynthSettings {
@@ -20,17 +20,39 @@ pub struct SynthSettings {
   pub main_module_conn_names: Vec<[char; 4]>,
   /// For the main module, sets the signals of single-signal ports.
   pub main_module_conn_signals: Vec<Signal>,
+  /// Sets how operations between single and mixed nets are synthesized.
+  pub single_mixed_strategy: SingleMixedStrategy,
+  /// If set, delays are inserted wherever paths of different latency are recombined,
+  /// so that every combinator sees its inputs from the same tick. See `timing::balance_latency`.
+  pub balance_latency: bool,
+}
+
+/// Operations between single and mixed nets are synthesized by reading both nets
+/// into one combinator, which also applies the operation to the single net's own signal.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum SingleMixedStrategy {
+  /// Removes the single net's signal from the output. Depending on the operation,
+  /// this can take up to two extra combinators and one extra tick.
+  #[default]
+  Clean,
+  /// Leaks the single net's signal into the output, but only uses one combinator.
+  Dirty,
 }
 
 #[derive(Debug)]
 pub struct GlobalSynthState<'a> {
+  settings: &'a SynthSettings,
   netlist: Netlist,
   collected_modules: HashMap<String, &'a IRModule>,
   builtin_functions: &'a Builtins,
+  errors: Vec<CerrSpan>,
+  /// For each module, the pairs of single ports that it reads together, which the nets bound
+  /// to them must not share a signal for. See [`port_signal_constraints`].
+  port_constraints: HashMap<String, Vec<(usize, usize)>>,
 }
 
 impl<'a> GlobalSynthState<'a> {
-  pub fn alloc_net(&mut self, ty: NetType, color: WireColor, signal: Option<Signal>) -> usize {
+  pub fn alloc_net(&mut self, ty: NetType, color: WireColor, signal: Option<Signal>, origin: Origin) -> usize {
     let idx = self.netlist.nets.len();
     self.netlist.nets.push(Net {
       ty,
@@ -38,6 +60,7 @@ impl<'a> GlobalSynthState<'a> {
       signal,
       in_conn: vec![],
       out_conn: vec![],
+      origin,
     });
     idx
   }
@@ -55,10 +78,12 @@ pub type IncompleteNetID = usize;
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct IncompleteNet {
   pub different_signal_as_net: Vec<IncompleteNetID>,
+  pub same_signal_as_net: Vec<IncompleteNetID>,
   pub different_color_as_net: Vec<IncompleteNetID>,
   pub resolved_signal: Option<Signal>,
   pub real_net: Option<(NetID, NetID)>,
   pub ty: NetType,
+  pub origin: Origin,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -67,10 +92,18 @@ struct IncompleteCombinator {
   in1: Option<IncompleteNetID>,
   in2: Option<IncompleteNetID>,
   out: IncompleteNetID,
+  origin: Origin,
 }
 
 impl IncompleteCombinator {
-  pub fn complete<'a>(self, mut f_get_net: impl FnMut(IncompleteNetID) -> (NetID, NetID), mut f_get_signal: impl FnMut(IncompleteNetID) -> &'a Option<Signal>) -> Combinator {
+  /// Fills in the nets and signals of the combinator. Fails if it refers to the signal
+  /// of a mixed net, which doesn't have one.
+  pub fn complete<'a>(
+    self,
+    mut f_get_net: impl FnMut(IncompleteNetID) -> (NetID, NetID),
+    mut f_get_signal: impl FnMut(IncompleteNetID) -> &'a Option<Signal>,
+  ) -> Result<Combinator, Cerr> {
+    let mut signal_of = |net_id| f_get_signal(net_id).clone().ok_or(Cerr::SignalOfMixedNet);
     let mut c = self.c;
     match &mut c {
       Combinator::Vanilla(comb2) => {
@@ -78,45 +111,46 @@ impl IncompleteCombinator {
         comb2.input_nets = [self.in1.map(|v2| f_get_net(v2).0), self.in2.map(|v2| f_get_net(v2).1)];
         comb2.output_nets = [Some(f_get_net(self.out).0), Some(f_get_net(self.out).1)];
         // fix signal references
-        comb2.input_signals.iter_mut().for_each(|v| {
+        for v in comb2.input_signals.iter_mut() {
           if let SignalRef::IncompleteSignal(net_id) = v {
-            *v = f_get_signal(*net_id).clone()
-              .map(|v| SignalRef::Signal(v))
-              .expect("Synth error: IncompleteCombinator refers signal of mixed net");
+            *v = SignalRef::Signal(signal_of(*net_id)?);
           }
-        });
+        }
         if let SignalRef::IncompleteSignal(net_id) = comb2.output_signal {
-          comb2.output_signal = f_get_signal(net_id).clone()
-            .map(|v| SignalRef::Signal(v))
-            .expect("Synth error: IncompleteCombinator refers signal of mixed net");
+          comb2.output_signal = SignalRef::Signal(signal_of(net_id)?);
         }
       }
       Combinator::Constant(comb2) => {
         // fix net references
-        comb2.output_nets = [self.in1.map(|v2| f_get_net(v2).0), self.in2.map(|v2| f_get_net(v2).1)];
+        comb2.output_nets = [Some(f_get_net(self.out).0), Some(f_get_net(self.out).1)];
         // fix signal references
-        comb2.output_signals.iter_mut()
-          .for_each(|v| {
-            if let CCSignalRef::IncompleteSignal(net_id, val) = v {
-              *v = f_get_signal(*net_id).clone()
-                .map(|v2| CCSignalRef::Signal(SignalWithCount {
-                  signal: v2,
-                  count: *val
-                }))
-                .expect("Synth error: IncompleteCombinator refers signal of mixed net");
-            }
-          });
-        
+        for v in comb2.output_signals.iter_mut() {
+          if let CCSignalRef::IncompleteSignal(net_id, val) = v {
+            *v = CCSignalRef::Signal(SignalWithCount {
+              signal: signal_of(*net_id)?,
+              count: *val,
+            });
+          }
+        }
+      }
+      Combinator::Selector(comb2) => {
+        // fix net references
+        comb2.input_nets = [self.in1.map(|v2| f_get_net(v2).0), self.in2.map(|v2| f_get_net(v2).1)];
+        comb2.output_nets = [Some(f_get_net(self.out).0), Some(f_get_net(self.out).1)];
       }
     }
-    c
+    *c.origin_mut() = self.origin;
+    Ok(c)
   }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct IncompleteModule {
   module: String,
+  /// The instance path of the module instance.
+  path: String,
   args: Vec<IncompleteNetID>,
+  span: Option<Span>,
 }
 
 #[derive(Debug)]
@@ -126,16 +160,24 @@ pub struct ModuleSynthState<'a, 'b> {
   inc_net_map: HashMap<String, IncompleteNetID>,
   inc_combinator: Vec<IncompleteCombinator>,
   inc_module: Vec<IncompleteModule>,
+  /// The instance path of the module being synthesized, see [`Origin`].
+  path: String,
+  file: FileId,
+  /// The declaration or statement being synthesized, which new nets and combinators come from.
+  span: Option<Span>,
 }
 
 impl<'a, 'b> ModuleSynthState<'a, 'b> {
-  pub fn new(global_state: &'b mut GlobalSynthState<'a>) -> Self {
+  pub fn new(global_state: &'b mut GlobalSynthState<'a>, path: String, file: FileId) -> Self {
     ModuleSynthState {
       global_state,
       inc_nets: vec![],
       inc_net_map: Default::default(),
       inc_combinator: vec![],
       inc_module: vec![],
+      path,
+      file,
+      span: None,
     }
   }
   
@@ -150,9 +192,77 @@ impl<'a, 'b> ModuleSynthState<'a, 'b> {
     in2: Option<IncompleteNetID>,
     out: IncompleteNetID,
   ) {
+    // the red and green inputs get summed by the combinator,
+    // so two single nets read together cannot share a signal
+    if let (Some(in1), Some(in2)) = (in1, in2) {
+      if in1 != in2
+        && self.net_info(in1).ty == NetType::Single
+        && self.net_info(in2).ty == NetType::Single {
+        self.require_different_signal(in1, in2);
+      }
+    }
+    // combinators are named after the net they write to
+    let origin = Origin {
+      path: self.inc_nets[out].origin.path.clone(),
+      file: self.file,
+      span: self.span,
+      skewed: false,
+    };
     self
       .inc_combinator
-      .push(IncompleteCombinator { c, in1, in2, out })
+      .push(IncompleteCombinator { c, in1, in2, out, origin })
+  }
+
+  /// Like [`Self::new_combinator`], but for a combinator whose inputs are meant to be from
+  /// different ticks, which latency balancing must not line up. See [`Origin::skewed`].
+  pub fn new_skewed_combinator(
+    &mut self,
+    c: Combinator,
+    in1: Option<IncompleteNetID>,
+    in2: Option<IncompleteNetID>,
+    out: IncompleteNetID,
+  ) {
+    self.new_combinator(c, in1, in2, out);
+    // unwrap: the combinator was just added
+    self.inc_combinator.last_mut().unwrap().origin.skewed = true;
+  }
+
+  /// Constrains two single nets to be resolved to different signals.
+  pub fn require_different_signal(&mut self, a: IncompleteNetID, b: IncompleteNetID) {
+    self.inc_nets[a].different_signal_as_net.push(b);
+    self.inc_nets[b].different_signal_as_net.push(a);
+  }
+
+  /// Constrains two single nets to be resolved to the same signal.
+  pub fn require_same_signal(&mut self, a: IncompleteNetID, b: IncompleteNetID) {
+    self.inc_nets[a].same_signal_as_net.push(b);
+    self.inc_nets[b].same_signal_as_net.push(a);
+  }
+
+  /// Returns all nets that are constrained to have the same signal as `id`, including itself.
+  pub fn same_signal_group(&self, id: IncompleteNetID) -> Vec<IncompleteNetID> {
+    let mut group = vec![id];
+    let mut i = 0;
+    while i < group.len() {
+      self.inc_nets[group[i]].same_signal_as_net.iter().for_each(|&v| {
+        if !group.contains(&v) {
+          group.push(v);
+        }
+      });
+      i += 1;
+    }
+    group
+  }
+
+  /// Returns the signal that a net's same-signal group has already been resolved to, if any.
+  pub fn group_signal(&self, id: IncompleteNetID) -> Option<Signal> {
+    self.same_signal_group(id)
+      .into_iter()
+      .find_map(|v| self.inc_nets[v].resolved_signal.clone())
+  }
+
+  pub fn settings(&self) -> &SynthSettings {
+    self.global_state.settings
   }
 
   pub fn net_info(&self, id: IncompleteNetID) -> &IncompleteNet {
@@ -163,6 +273,25 @@ impl<'a, 'b> ModuleSynthState<'a, 'b> {
     self.inc_net_map.get(name).cloned()
   }
 
+  /// Like [`Self::find_net`], but reports an error if the net doesn't exist.
+  fn require_net(&mut self, name: &str) -> Option<IncompleteNetID> {
+    let net = self.find_net(name);
+    if net.is_none() {
+      self.error(Cerr::NetNotFound(name.into()));
+    }
+    net
+  }
+
+  /// Reports an error at the declaration or statement being synthesized.
+  pub fn error(&mut self, cerr: Cerr) {
+    self.error_at(cerr, self.span);
+  }
+
+  /// Reports an error somewhere in the module being synthesized.
+  fn error_at(&mut self, cerr: Cerr, span: Option<Span>) {
+    self.global_state.errors.push(cerr.with_opt(span).in_file(self.file));
+  }
+
   pub fn type_of(&self, r: &SynthRef) -> Option<NetType> {
     match r {
       SynthRef::Net(net) => Some(self.net_info(*net).ty),
@@ -212,12 +341,24 @@ impl IncompleteNetBuilder {
   
   pub fn build(self, mod_state: &mut ModuleSynthState) -> IncompleteNetID {
     let id = mod_state.inc_nets.len();
+    // anonymous nets can't be referred to, so they only get the path of their instance
+    let path = match &self.name {
+      Some(name) if !name.starts_with('$') => format!("{}.{}", mod_state.path, name),
+      _ => mod_state.path.clone(),
+    };
     mod_state.inc_nets.push(IncompleteNet {
       different_signal_as_net: vec![],
+      same_signal_as_net: vec![],
       different_color_as_net: vec![],
       resolved_signal: self.signal,
       real_net: self.real_net,
       ty: self.net_type.expect("Net type not assigned"),
+      origin: Origin {
+        path,
+        file: mod_state.file,
+        span: mod_state.span,
+        skewed: false,
+      },
     });
     if let Some(name) = self.name {
       mod_state.inc_net_map.insert(name, id);
@@ -226,9 +367,12 @@ impl IncompleteNetBuilder {
   }
 }
 
-pub fn synthesize(settings: &SynthSettings, modules: &[IRModule], builtins: &Builtins) -> Result<Netlist, Cerr> {
+/// Synthesizes the main module and everything it instantiates. Errors don't stop synthesis,
+/// so that as many as possible are reported at once.
+pub fn synthesize(settings: &SynthSettings, modules: &[IRModule], builtins: &Builtins) -> Result<Netlist, Vec<CerrSpan>> {
   let collected_modules = collect_modules(modules);
   let mut state = GlobalSynthState {
+    settings,
     netlist: Netlist {
       nets: vec![],
       net_external_conn: vec![],
@@ -236,34 +380,58 @@ pub fn synthesize(settings: &SynthSettings, modules: &[IRModule], builtins: &Bui
     },
     collected_modules,
     builtin_functions: builtins,
+    errors: vec![],
+    port_constraints: HashMap::new(),
   };
   // connect main module to the outside world
-  let ports = &state
+  let main = *state
     .collected_modules
     .get(&settings.main)
-    .ok_or(Cerr::MainNotFound(settings.main.clone()))?
-    .ports;
-  assert_eq!(ports.len(), settings.main_module_conn_names.len());
-  assert_eq!(ports.len(), settings.main_module_conn_signals.len());
+    .ok_or_else(|| vec![CerrSpan::without_span(Cerr::MainNotFound(settings.main.clone()))])?;
+  let ports = &main.ports;
+  let conns = [settings.main_module_conn_names.len(), settings.main_module_conn_signals.len()];
+  if let Some(&count) = conns.iter().find(|&&v| v != ports.len()) {
+    let cerr = Cerr::MainPortCount(settings.main.clone(), ports.len(), count);
+    return Err(vec![CerrSpan::without_span(cerr).in_file(main.file)]);
+  }
   let port_nets = ports
     .iter()
     .zip(settings.main_module_conn_signals.iter())
     .map(|(port, signal)| {
-      if port.signal_class == NetType::Single {
-        (
-          state.alloc_net(port.signal_class, WireColor::Red, Some(signal.clone())),
-          state.alloc_net(port.signal_class, WireColor::Green, Some(signal.clone())),
-        )
-      } else {
-        (
-          state.alloc_net(port.signal_class, WireColor::Red, None),
-          state.alloc_net(port.signal_class, WireColor::Green, None),
-        )
-      }
+      let signal = (port.signal_class == NetType::Single).then(|| signal.clone());
+      let origin = Origin {
+        path: format!("{}.{}", settings.main, port.name),
+        file: main.file,
+        span: None,
+        skewed: false,
+      };
+      (
+        state.alloc_net(port.signal_class, WireColor::Red, signal.clone(), origin.clone()),
+        state.alloc_net(port.signal_class, WireColor::Green, signal, origin),
+      )
     })
     .collect::<Vec<_>>();
+  port_nets.iter()
+    .zip(settings.main_module_conn_names.iter().zip(settings.main_module_conn_signals.iter()))
+    .for_each(|(&(red_net, green_net), (name, signal))| {
+      let signal = state.netlist.nets[red_net].signal.as_ref().map(|_| signal.clone());
+      state.netlist.net_external_conn.push(ExternalConn {
+        red_net,
+        green_net,
+        name: *name,
+        signal,
+      });
+    });
   // now synthesize the main module
-  synthesize_module(&mut state, &settings.main, &port_nets)?;
+  synthesize_module(&mut state, main, settings.main.clone(), &port_nets);
+  if !state.errors.is_empty() {
+    return Err(state.errors);
+  }
+  if settings.balance_latency {
+    // the report only lists the mismatches that were fixed, so there's nothing left to warn about,
+    // while unbalanced netlists are checked by `lint::lint_netlist`
+    balance_latency(&mut state.netlist);
+  }
   Ok(state.netlist)
 }
 
@@ -277,11 +445,12 @@ fn collect_modules(modules: &[IRModule]) -> HashMap<String, &IRModule> {
 
 fn synthesize_module(
   state: &mut GlobalSynthState,
-  name: &str,
+  module: &IRModule,
+  path: String,
   arg_nets: &[(NetID, NetID)],
-) -> Result<(), Cerr> {
-  let mut mod_state = ModuleSynthState::new(state);
-  let module = *mod_state.global_state.collected_modules.get(name).unwrap();
+) {
+  let prior_errors = state.errors.len();
+  let mut mod_state = ModuleSynthState::new(state, path, module.file);
   
   // collect module ports
   presynth_module_inputs(&mut mod_state, &module.ports, arg_nets);
@@ -294,14 +463,19 @@ fn synthesize_module(
 
   // run stmt presynth
   presynth_stmts(&mut mod_state, &module.stmts);
+  presynth_trigger_stmt(&mut mod_state, &module.trigger_stmt, &module.objects);
+  mod_state.span = None;
   
   // resolve signals
   resolve_signals(&mut mod_state);
 
+  // a module with errors can't be completed, and neither can its inner modules
+  if mod_state.global_state.errors.len() > prior_errors {
+    return;
+  }
+
   // convert all `IncompleteNet`s to real nets, and also synth inner modules
   complete_nets(mod_state);
-
-  Ok(())
 }
 
 fn presynth_module_inputs(mod_state: &mut ModuleSynthState, port_decls: &[PortDecl], arg_nets: &[(NetID, NetID)]) -> Vec<IncompleteNetID> {
@@ -320,58 +494,144 @@ fn presynth_module_inputs(mod_state: &mut ModuleSynthState, port_decls: &[PortDe
 
 fn presynth_ir_decls(mod_state: &mut ModuleSynthState, decls: &HashMap<String, IRWireMemDecl>) {
   decls.iter().for_each(|(name, decl)| {
-    let net = mod_state.new_net_builder()
-      .net_type(decl.ty)
-      .name(name.clone())
-      .build(mod_state);
-    if decl.mem {
-      mod_state.new_combinator(
-        Combinator::Vanilla(VanillaCombinator {
-          op: VanillaCombinatorOp::Eq,
-          input_signals: [SignalRef::Const(0), SignalRef::Const(0)],
-          output_signal: SignalRef::Anything,
-          output_count: true,
-          ..Default::default()
-        }),
-        Some(net),
-        None,
-        net,
-      );
+    // ports were already created from the module's connections
+    if decl.port_idx.is_none() {
+      mod_state.span = decl.span;
+      mod_state.new_net_builder()
+        .net_type(decl.ty)
+        .name(name.clone())
+        .build(mod_state);
     }
   });
+  // memory cells can refer to other nets, so they're created once all nets exist
+  decls.iter()
+    .filter(|(_, decl)| decl.mem)
+    .for_each(|(name, decl)| {
+      mod_state.span = decl.span;
+      if let Some(net) = mod_state.require_net(name) {
+        presynth_mem(mod_state, net, decl);
+      }
+    });
+}
+
+/// Creates the feedback loop of a memory cell. If the cell has an initial value or a reset,
+/// the loop is cut while a `load` net is nonzero, and the initial value is fed in instead.
+/// `load` is set by the reset, and by a pulse when the circuit is powered on.
+fn presynth_mem(mod_state: &mut ModuleSynthState, net: IncompleteNetID, decl: &IRWireMemDecl) {
+  if decl.init == 0 && decl.reset.is_none() {
+    mod_state.new_combinator(
+      Combinator::Vanilla(VanillaCombinator {
+        op: VanillaCombinatorOp::Eq,
+        input_signals: [SignalRef::Const(0), SignalRef::Const(0)],
+        output_signal: SignalRef::Anything,
+        output_count: true,
+        ..Default::default()
+      }),
+      Some(net),
+      None,
+      net,
+    );
+    return;
+  }
+  let load = mod_state.new_net_builder()
+    .net_type(NetType::Single)
+    .build(mod_state);
+  if let Some(reset) = &decl.reset {
+    let Some(reset) = mod_state.require_net(reset) else {
+      return;
+    };
+    mod_state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Ne,
+      input_signals: [SignalRef::IncompleteSignal(reset), SignalRef::Const(0)],
+      output_signal: SignalRef::IncompleteSignal(load),
+      output_count: false,
+      .. Default::default()
+    }), Some(reset), None, load);
+  }
+  if decl.init != 0 {
+    // a constant turns on when the circuit is built, which is turned into a one tick pulse
+    let powered = materialize(mod_state, &SynthRef::Value(1));
+    let Some(pulse) = mod_state.global_state.builtin_functions.get("rising_pulse") else {
+      mod_state.error(Cerr::UnknownFunction("rising_pulse".into()));
+      return;
+    };
+    if let Err(cerr) = pulse.synthesize(mod_state, &[SynthRef::Net(powered)], load) {
+      mod_state.error(cerr);
+      return;
+    }
+    // feed the initial value in while loading
+    let init = materialize(mod_state, &SynthRef::Value(decl.init));
+    mod_state.require_same_signal(init, net);
+    mod_state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Ne,
+      input_signals: [SignalRef::IncompleteSignal(load), SignalRef::Const(0)],
+      output_signal: SignalRef::IncompleteSignal(net),
+      output_count: true,
+      .. Default::default()
+    }), Some(init), Some(load), net);
+  }
+  let output_signal = if decl.ty == NetType::Single {
+    SignalRef::IncompleteSignal(net)
+  } else {
+    SignalRef::Everything
+  };
+  mod_state.new_combinator(Combinator::Vanilla(VanillaCombinator {
+    op: VanillaCombinatorOp::Eq,
+    input_signals: [SignalRef::IncompleteSignal(load), SignalRef::Const(0)],
+    output_signal,
+    output_count: true,
+    .. Default::default()
+  }), Some(net), Some(load), net);
 }
 
 fn presynth_stmts(mod_state: &mut ModuleSynthState, stmts: &[IRStmt]) {
   stmts.iter()
     .for_each(|v| {
-      let builtin = mod_state.global_state.builtin_functions.get(&v.op)
-        .expect("Synth error: Invalid builtin");
+      mod_state.span = v.span;
+      let Some(builtin) = mod_state.global_state.builtin_functions.get(&v.op) else {
+        mod_state.error(Cerr::UnknownFunction(v.op.clone()));
+        return;
+      };
       let inputs = v.args.iter()
         .map(|v| {
           match v {
-            IRValue::Net(net) => SynthRef::Net(mod_state.find_net(net).expect("Synth error: Net not found")),
-            IRValue::Lit(lit) => SynthRef::Value(*lit),
-            IRValue::Str(str) => SynthRef::String(str.clone()),
+            IRValue::Net(net) => mod_state.require_net(net).map(SynthRef::Net),
+            IRValue::Lit(lit) => Some(SynthRef::Value(*lit)),
+            IRValue::Str(str) => Some(SynthRef::String(str.clone())),
           }
         })
         .collect::<Vec<_>>();
-      let output = mod_state.find_net(&v.dest)
-        .expect("Synth error: Net not found");
-      builtin.synthesize(mod_state, &inputs, output)
-        .expect("Synth error: Failed to synthesize builtin");
+      let output = mod_state.require_net(&v.dest);
+      // a statement with a missing net is skipped, after reporting every missing net
+      let (Some(inputs), Some(output)) = (inputs.into_iter().collect::<Option<Vec<_>>>(), output) else {
+        return;
+      };
+      if let Err(cerr) = builtin.synthesize(mod_state, &inputs, output) {
+        mod_state.error(cerr);
+      }
     });
 }
 
-fn presynth_trigger_stmt(mod_state: &mut ModuleSynthState, stmts: &[IRTriggerStmt]) {
+fn presynth_trigger_stmt(mod_state: &mut ModuleSynthState, stmts: &[IRTriggerStmt], decls: &HashMap<String, IRWireMemDecl>) {
   stmts.iter()
     .for_each(|v| {
-      let src_net = mod_state.find_net(&v.src).expect("Synth error: Net not found");
-      let dest_net = mod_state.find_net(&v.dest).expect("Synth error: Net not found");
-      let on_net = mod_state.find_net(&v.on).expect("Synth error: Net not found");
+      // the source is created for the statement being triggered
+      mod_state.span = decls.get(&v.src).and_then(|v| v.span);
+      let nets = [&v.src, &v.dest, &v.on].map(|v| mod_state.require_net(v));
+      let [Some(src_net), Some(dest_net), Some(on_net)] = nets else {
+        return;
+      };
+      // deciders pass their inputs through unchanged, so single nets must share a signal
+      let output_signal = if mod_state.net_info(dest_net).ty == NetType::Single {
+        mod_state.require_same_signal(src_net, dest_net);
+        SignalRef::IncompleteSignal(dest_net)
+      } else {
+        SignalRef::Everything
+      };
       mod_state.new_combinator(Combinator::Vanilla(VanillaCombinator {
         op: VanillaCombinatorOp::Eq,
         input_signals: [SignalRef::IncompleteSignal(on_net), SignalRef::Const(0)],
-        output_signal: SignalRef::Everything,
+        output_signal,
         output_count: true,
         .. Default::default()
       }), Some(src_net), Some(on_net), dest_net);
@@ -379,17 +639,84 @@ fn presynth_trigger_stmt(mod_state: &mut ModuleSynthState, stmts: &[IRTriggerStm
 }
 
 fn presynth_modules(mod_state: &mut ModuleSynthState, modules: &[IRModuleInst]) {
+  let mut instances = HashMap::<&str, usize>::new();
   modules.iter()
     .for_each(|v| {
-      mod_state.inc_module.push(IncompleteModule {
-        module: v.name.clone(),
-        args: v.args.iter().map(|v| {
-          mod_state.find_net(v).expect("Synth error: Net not found")
-        }).collect::<Vec<_>>(),
-      })
+      // instances are numbered per module, e.g. `counter0`, `counter1`
+      let count = instances.entry(&v.name).or_default();
+      let path = format!("{}.{}{}", mod_state.path, v.name, count);
+      *count += 1;
+      mod_state.span = v.span;
+      let args = v.args.iter()
+        .map(|v| mod_state.require_net(v))
+        .collect::<Vec<_>>();
+      if let Some(args) = args.into_iter().collect::<Option<Vec<_>>>() {
+        // the module's ports are resolved to the signals of the nets bound to them, so ports
+        // that it reads together need different signals here already
+        port_signal_constraints(mod_state.global_state, &v.name)
+          .into_iter()
+          .for_each(|(a, b)| {
+            if args[a] != args[b] {
+              mod_state.require_different_signal(args[a], args[b]);
+            }
+          });
+        mod_state.inc_module.push(IncompleteModule {
+          module: v.name.clone(),
+          path,
+          args,
+          span: v.span,
+        })
+      }
     })
 }
 
+/// Finds the pairs of single ports of a module that must be resolved to different signals,
+/// because the module, or a module it instantiates, reads them together.
+///
+/// The module is synthesized without completing it, with unresolved ports, and any errors are
+/// left to the actual synthesis. Results are cached per module.
+fn port_signal_constraints(state: &mut GlobalSynthState, name: &str) -> Vec<(usize, usize)> {
+  if let Some(pairs) = state.port_constraints.get(name) {
+    return pairs.clone();
+  }
+  let Some(module) = state.collected_modules.get(name).copied() else {
+    return vec![];
+  };
+  // a module that instantiates itself doesn't constrain its own ports
+  state.port_constraints.insert(name.to_owned(), vec![]);
+  let prior_errors = state.errors.len();
+  let mut mod_state = ModuleSynthState::new(state, name.to_owned(), module.file);
+  let ports = module.ports
+    .iter()
+    .map(|port| {
+      mod_state.new_net_builder()
+        .net_type(port.signal_class)
+        .name(port.name.clone())
+        .build(&mut mod_state)
+    })
+    .collect::<Vec<_>>();
+  presynth_ir_decls(&mut mod_state, &module.objects);
+  presynth_modules(&mut mod_state, &module.module_inst);
+  presynth_stmts(&mut mod_state, &module.stmts);
+  presynth_trigger_stmt(&mut mod_state, &module.trigger_stmt, &module.objects);
+  let mut pairs = vec![];
+  for (i, &a) in ports.iter().enumerate() {
+    let group = mod_state.same_signal_group(a);
+    let different = group.iter()
+      .flat_map(|&v| mod_state.inc_nets[v].different_signal_as_net.iter())
+      .flat_map(|&v| mod_state.same_signal_group(v))
+      .collect::<HashSet<_>>();
+    ports.iter()
+      .enumerate()
+      .skip(i + 1)
+      .filter(|(_, b)| different.contains(b))
+      .for_each(|(j, _)| pairs.push((i, j)));
+  }
+  state.errors.truncate(prior_errors);
+  state.port_constraints.insert(name.to_owned(), pairs.clone());
+  pairs
+}
+
 fn resolve_signals(mod_state: &mut ModuleSynthState) {
   let unresolved_list = mod_state.inc_nets
     .iter()
@@ -401,33 +728,49 @@ fn resolve_signals(mod_state: &mut ModuleSynthState) {
         None
       }
     }).collect::<Vec<_>>();
-  unresolved_list.into_iter().for_each(|id| {
-    let net = &mod_state.inc_nets[id];
-    let resolved_refs = net.different_signal_as_net.iter()
-      .filter_map(|&v| {
-        mod_state.inc_nets[v].resolved_signal.clone()
-      })
-      .collect::<BTreeSet<_>>();
-    let virtual_signals = &VIRTUAL_SIGNALS;
-    let resolved_signal = virtual_signals.iter().find(|v| !resolved_refs.contains(v));
+  for id in unresolved_list {
+    if mod_state.inc_nets[id].resolved_signal.is_some() {
+      // already resolved as part of another net's group
+      continue;
+    }
+    // nets that must share a signal are resolved together
+    let group = mod_state.same_signal_group(id);
+    let resolved_signal = mod_state.group_signal(id).or_else(|| {
+      let resolved_refs = group.iter()
+        .flat_map(|&v| mod_state.inc_nets[v].different_signal_as_net.iter())
+        .filter_map(|&v| mod_state.group_signal(v))
+        .collect::<BTreeSet<_>>();
+      let virtual_signals = &VIRTUAL_SIGNALS;
+      virtual_signals.iter().find(|v| !resolved_refs.contains(v)).cloned()
+    });
     if let Some(resolved_signal) = resolved_signal {
-      mod_state.inc_nets[id].resolved_signal = Some(resolved_signal.clone());
+      group.into_iter().for_each(|v| {
+        mod_state.inc_nets[v].resolved_signal = Some(resolved_signal.clone());
+      });
     } else {
-      // no valid signals were found!
-      panic!("Synth error: unable to allocate sufficient unique signals")
+      // no valid signals were found! the remaining nets would run out as well
+      let span = mod_state.inc_nets[id].origin.span;
+      mod_state.error_at(Cerr::OutOfSignals, span);
+      return;
     }
-  });
+  }
 }
 
 // consumes mod_state because it's the last operation and mod_state
 // becomes de-facto invalid after this operation
 fn complete_nets(mod_state: ModuleSynthState) {
   let state = mod_state.global_state;
+  let file = mod_state.file;
   // expand each incomplete net into a netpair
   let completed_nets = mod_state.inc_nets
     .into_iter()
     .map(|v| {
-      (state.alloc_net(v.ty, WireColor::Red, v.resolved_signal.clone()), state.alloc_net(v.ty, WireColor::Green, v.resolved_signal))
+      v.real_net.unwrap_or_else(|| {
+        (
+          state.alloc_net(v.ty, WireColor::Red, v.resolved_signal.clone(), v.origin.clone()),
+          state.alloc_net(v.ty, WireColor::Green, v.resolved_signal, v.origin),
+        )
+      })
     })
     .collect::<Vec<_>>();
   // expand all combinators
@@ -437,17 +780,23 @@ fn complete_nets(mod_state: ModuleSynthState) {
   let f_get_signal = |iid: usize| {
     &state.netlist.nets[completed_nets[iid].0].signal
   };
-  let completed_combs = mod_state.inc_combinator.into_iter().map(|v| {
-    let comb = v.complete(f_get_net, f_get_signal);
+  let mut errors = vec![];
+  let completed_combs = mod_state.inc_combinator.into_iter().filter_map(|v| {
+    let span = v.origin.span;
+    let comb = v.complete(f_get_net, f_get_signal)
+      .map_err(|cerr| errors.push((cerr, span)))
+      .ok()?;
     let comb_id = state.netlist.combinators.len();
     state.netlist.combinators.push(comb);
-    comb_id
+    Some(comb_id)
   }).collect::<Vec<_>>();
+  state.errors.extend(errors.into_iter().map(|(cerr, span)| cerr.with_opt(span).in_file(file)));
   // add backrefs (net->combinator references)
   completed_combs.iter().for_each(|&cid| {
     let in_refs = match &state.netlist.combinators[cid] {
       Combinator::Vanilla(comb) => comb.input_nets.to_vec(),
-      Combinator::Constant(_) => vec![]
+      Combinator::Constant(_) => vec![],
+      Combinator::Selector(comb) => comb.input_nets.to_vec(),
     };
     in_refs.into_iter()
       .enumerate()
@@ -457,7 +806,8 @@ fn complete_nets(mod_state: ModuleSynthState) {
       });
     let out_refs = match &state.netlist.combinators[cid] {
       Combinator::Vanilla(comb) => comb.output_nets,
-      Combinator::Constant(comb) => comb.output_nets
+      Combinator::Constant(comb) => comb.output_nets,
+      Combinator::Selector(comb) => comb.output_nets,
     };
     out_refs.into_iter()
       .enumerate()
@@ -469,11 +819,14 @@ fn complete_nets(mod_state: ModuleSynthState) {
   // initialize submodules
   mod_state.inc_module.into_iter()
     .for_each(|v| {
+      let Some(module) = state.collected_modules.get(&v.module).copied() else {
+        state.errors.push(Cerr::NotDeclared(v.module).with_opt(v.span).in_file(file));
+        return;
+      };
       let arg_nets = v.args.into_iter()
         .map(|v| completed_nets[v])
         .collect::<Vec<_>>();
-      synthesize_module(state, &v.module, &arg_nets)
-        .expect("Synth error: submodule synth failed");
+      synthesize_module(state, module, v.path, &arg_nets);
     });
   // done!
 }
@@ -0,0 +1,418 @@
+//! Tick latency analysis over a synthesized netlist.
+//!
+//! Every combinator takes one tick to propagate its inputs to its outputs, so values that
+//! travel through paths of different depth arrive on different ticks. When such paths are
+//! recombined, either by one combinator reading both or by several combinators writing to the
+//! same net, the result mixes values from different ticks for a while after the inputs change.
+//!
+//! Latencies are counted from the external connections of the netlist. Constants have no latency,
+//! and combinators that are part of a feedback loop (memory cells) start a new path at latency 0.
+//! Combinators whose inputs differ in latency on purpose, such as edge detectors, are marked as
+//! skewed in their origin, and are neither reported nor balanced.
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use crate::parse::ast::NetType;
+use crate::synth::combinator::{Combinator, SignalRef, VanillaCombinator, VanillaCombinatorOp};
+use crate::synth::netlist::{CombinatorID, Net, NetID, Netlist, WireColor};
+
+/// Result of a latency analysis.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LatencyReport {
+  /// For each net, the number of ticks its value lags behind the external connections.
+  /// `None` if the net only carries constants or is never written to.
+  pub net_latency: Vec<Option<u32>>,
+  /// Places where paths of different latency are recombined.
+  pub mismatches: Vec<LatencyMismatch>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LatencyMismatch {
+  /// A combinator reads two nets with different latencies, given as `[red, green]`.
+  CombinatorInputs {
+    combinator: CombinatorID,
+    latencies: [u32; 2],
+  },
+  /// A net is written to by combinators with different latencies.
+  NetWriters {
+    net: NetID,
+    writers: Vec<(CombinatorID, u32)>,
+  },
+}
+
+impl Display for LatencyMismatch {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LatencyMismatch::CombinatorInputs { combinator, latencies } => write!(
+        f,
+        "Combinator {} reads a red input with latency {} and a green input with latency {}",
+        combinator, latencies[0], latencies[1]
+      ),
+      LatencyMismatch::NetWriters { net, writers } => {
+        write!(f, "Net {} is written to with latencies", net)?;
+        writers.iter().try_for_each(|(comb, lat)| write!(f, " {} (combinator {})", lat, comb))
+      }
+    }
+  }
+}
+
+/// Computes the latency of every net in the netlist and reports mismatched reconvergent paths.
+pub fn analyze_latency(netlist: &Netlist) -> LatencyReport {
+  LatencyPass::new(&mut netlist.clone(), false).run()
+}
+
+/// Like `analyze_latency`, but balances every mismatch it finds by delaying the faster paths
+/// with passthrough combinators. The returned report contains the latencies after balancing,
+/// and the mismatches that were fixed.
+pub fn balance_latency(netlist: &mut Netlist) -> LatencyReport {
+  LatencyPass::new(netlist, true).run()
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Latency {
+  /// Not computed yet.
+  Pending,
+  /// Only depends on constants.
+  Constant,
+  Ticks(u32),
+}
+
+impl Latency {
+  fn ticks(self) -> Option<u32> {
+    match self {
+      Latency::Ticks(v) => Some(v),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug)]
+struct LatencyPass<'a> {
+  netlist: &'a mut Netlist,
+  balance: bool,
+  /// Output latency of each combinator.
+  comb_latency: Vec<Latency>,
+  net_latency: Vec<Latency>,
+  /// Combinators that are part of a feedback loop.
+  cyclic: Vec<bool>,
+  mismatches: Vec<LatencyMismatch>,
+  /// Writer sets of nets that were already reported, so that red and green nets of the same
+  /// pair are only reported once.
+  reported_writers: HashSet<Vec<CombinatorID>>,
+}
+
+impl<'a> LatencyPass<'a> {
+  fn new(netlist: &'a mut Netlist, balance: bool) -> Self {
+    let combs = netlist.combinators.len();
+    let nets = netlist.nets.len();
+    LatencyPass {
+      netlist,
+      balance,
+      comb_latency: vec![Latency::Pending; combs],
+      net_latency: vec![Latency::Pending; nets],
+      cyclic: vec![false; combs],
+      mismatches: vec![],
+      reported_writers: HashSet::new(),
+    }
+  }
+
+  fn run(mut self) -> LatencyReport {
+    let order = self.topological_order();
+    order.into_iter().for_each(|cid| self.visit_combinator(cid));
+    (0..self.netlist.nets.len()).for_each(|net| {
+      self.visit_net(net);
+    });
+    LatencyReport {
+      net_latency: self.net_latency.iter().map(|v| v.ticks()).collect(),
+      mismatches: self.mismatches,
+    }
+  }
+
+  /// Orders the combinators so that every combinator comes after the ones writing to its inputs,
+  /// marking combinators in feedback loops along the way.
+  fn topological_order(&mut self) -> Vec<CombinatorID> {
+    let sccs = Tarjan::run(self.netlist);
+    sccs.iter().for_each(|scc| {
+      let cyclic = scc.len() > 1 || self.successors(scc[0]).contains(&scc[0]);
+      if cyclic {
+        scc.iter().for_each(|&cid| self.cyclic[cid] = true);
+      }
+    });
+    // tarjan's algorithm finds components in reverse topological order
+    sccs.into_iter().rev().flatten().collect()
+  }
+
+  fn successors(&self, cid: CombinatorID) -> Vec<CombinatorID> {
+    successors(self.netlist, cid)
+  }
+
+  fn visit_combinator(&mut self, cid: CombinatorID) {
+    if self.cyclic[cid] {
+      // memory cells hold state, so their output starts a new path
+      self.comb_latency[cid] = Latency::Ticks(0);
+      return;
+    }
+    let input_nets = match &self.netlist.combinators[cid] {
+      Combinator::Vanilla(comb) => comb.input_nets,
+      Combinator::Selector(comb) => comb.input_nets,
+      Combinator::Constant(_) => {
+        self.comb_latency[cid] = Latency::Constant;
+        return;
+      }
+    };
+    let mut latencies = input_nets.map(|net| net.map(|net| self.visit_net(net)).and_then(Latency::ticks));
+    // skewed combinators compare a value against an older copy of itself on purpose
+    let skewed = self.netlist.combinators[cid].origin().skewed;
+    if let ([Some(red), Some(green)], false) = (latencies, skewed) {
+      if red != green {
+        self.mismatches.push(LatencyMismatch::CombinatorInputs {
+          combinator: cid,
+          latencies: [red, green],
+        });
+        if self.balance {
+          let (port, lag) = if red < green { (0, green - red) } else { (1, red - green) };
+          self.delay_input(cid, port, lag);
+          latencies[port] = latencies[1 - port];
+        }
+      }
+    }
+    self.comb_latency[cid] = match latencies.into_iter().flatten().max() {
+      Some(v) => Latency::Ticks(v + 1),
+      None => Latency::Constant,
+    };
+  }
+
+  /// Computes the latency of a net, once all of its writers are known.
+  fn visit_net(&mut self, net: NetID) -> Latency {
+    if self.net_latency[net] != Latency::Pending {
+      return self.net_latency[net];
+    }
+    let writers = self.netlist.nets[net].out_conn.iter()
+      .map(|&(cid, _)| cid)
+      .collect::<Vec<_>>();
+    let latency = if writers.iter().any(|&cid| self.cyclic[cid]) {
+      Latency::Ticks(0)
+    } else if writers.is_empty() {
+      let external = self.netlist.net_external_conn.iter()
+        .any(|v| v.red_net == net || v.green_net == net);
+      if external { Latency::Ticks(0) } else { Latency::Constant }
+    } else {
+      let timed = writers.iter()
+        .filter_map(|&cid| self.comb_latency[cid].ticks().map(|lat| (cid, lat)))
+        .collect::<Vec<_>>();
+      match timed.iter().map(|&(_, lat)| lat).max() {
+        None => Latency::Constant,
+        Some(max) => {
+          if timed.iter().any(|&(_, lat)| lat != max) {
+            if self.reported_writers.insert(writers.clone()) {
+              self.mismatches.push(LatencyMismatch::NetWriters {
+                net,
+                writers: timed.clone(),
+              });
+            }
+            if self.balance {
+              timed.iter()
+                .filter(|&&(_, lat)| lat != max)
+                .for_each(|&(cid, lat)| self.delay_output(cid, max - lat));
+            }
+          }
+          Latency::Ticks(max)
+        }
+      }
+    };
+    self.net_latency[net] = latency;
+    latency
+  }
+
+  /// Delays one input of a combinator by inserting a chain of passthroughs in front of it.
+  fn delay_input(&mut self, cid: CombinatorID, port: usize, lag: u32) {
+    let src = match &self.netlist.combinators[cid] {
+      Combinator::Vanilla(comb) => comb.input_nets[port],
+      Combinator::Selector(comb) => comb.input_nets[port],
+      Combinator::Constant(_) => unreachable!(),
+    }.unwrap();
+    let base = self.visit_net(src).ticks().unwrap();
+    let last = (1..=lag).fold(src, |prev, i| {
+      let next = self.alloc_net_like(prev, Latency::Ticks(base + i));
+      let color = self.netlist.nets[next].color;
+      self.add_passthrough(prev, color_ports(color, next), Latency::Ticks(base + i));
+      next
+    });
+    // rewire the combinator to read the delayed net
+    self.netlist.nets[src].in_conn.retain(|&v| v != (cid, port));
+    self.netlist.nets[last].in_conn.push((cid, port));
+    match &mut self.netlist.combinators[cid] {
+      Combinator::Vanilla(comb) => comb.input_nets[port] = Some(last),
+      Combinator::Selector(comb) => comb.input_nets[port] = Some(last),
+      Combinator::Constant(_) => unreachable!(),
+    }
+  }
+
+  /// Delays every output of a combinator by moving its outputs to the end of a chain of passthroughs.
+  fn delay_output(&mut self, cid: CombinatorID, lag: u32) {
+    let outputs = match &mut self.netlist.combinators[cid] {
+      Combinator::Vanilla(comb) => std::mem::take(&mut comb.output_nets),
+      Combinator::Constant(comb) => std::mem::take(&mut comb.output_nets),
+      Combinator::Selector(comb) => std::mem::take(&mut comb.output_nets),
+    };
+    let base = self.comb_latency[cid].ticks().unwrap();
+    outputs.iter().enumerate().for_each(|(port, net)| {
+      if let Some(net) = net {
+        self.netlist.nets[*net].out_conn.retain(|&v| v != (cid, port));
+      }
+    });
+    // the combinator now writes into the start of the chain
+    let first = self.alloc_net_like(outputs.iter().flatten().next().copied().unwrap(), Latency::Ticks(base));
+    let first_color = self.netlist.nets[first].color;
+    let first_ports = color_ports(first_color, first);
+    match &mut self.netlist.combinators[cid] {
+      Combinator::Vanilla(comb) => comb.output_nets = first_ports,
+      Combinator::Constant(comb) => comb.output_nets = first_ports,
+      Combinator::Selector(comb) => comb.output_nets = first_ports,
+    }
+    self.netlist.nets[first].out_conn.push((cid, color_port(first_color)));
+    let last = (1..lag).fold(first, |prev, i| {
+      let next = self.alloc_net_like(prev, Latency::Ticks(base + i));
+      self.add_passthrough(prev, color_ports(first_color, next), Latency::Ticks(base + i));
+      next
+    });
+    self.add_passthrough(last, outputs, Latency::Ticks(base + lag));
+  }
+
+  fn alloc_net_like(&mut self, net: NetID, latency: Latency) -> NetID {
+    let Net { ty, color, signal, origin, .. } = self.netlist.nets[net].clone();
+    self.netlist.nets.push(Net {
+      ty,
+      color,
+      signal,
+      in_conn: vec![],
+      out_conn: vec![],
+      origin,
+    });
+    self.net_latency.push(latency);
+    self.netlist.nets.len() - 1
+  }
+
+  /// Adds a combinator that copies `src` onto the given output nets.
+  fn add_passthrough(&mut self, src: NetID, outputs: [Option<NetID>; 2], latency: Latency) {
+    let src_net = &self.netlist.nets[src];
+    let port = color_port(src_net.color);
+    // the delay belongs to the wire it delays
+    let origin = src_net.origin.clone();
+    let signal = if src_net.ty == NetType::Single {
+      SignalRef::Signal(src_net.signal.clone().expect("Single nets always have a signal"))
+    } else {
+      SignalRef::Each
+    };
+    let mut input_nets = [None, None];
+    input_nets[port] = Some(src);
+    let cid = self.netlist.combinators.len();
+    self.netlist.combinators.push(Combinator::Vanilla(VanillaCombinator {
+      op: VanillaCombinatorOp::Add,
+      input_nets,
+      output_nets: outputs,
+      input_signals: [signal.clone(), SignalRef::Const(0)],
+      output_signal: signal,
+      output_count: false,
+      origin,
+    }));
+    self.netlist.nets[src].in_conn.push((cid, port));
+    outputs.iter().enumerate().for_each(|(port, net)| {
+      if let Some(net) = net {
+        self.netlist.nets[*net].out_conn.push((cid, port));
+      }
+    });
+    self.comb_latency.push(latency);
+    self.cyclic.push(false);
+  }
+}
+
+fn color_port(color: WireColor) -> usize {
+  match color {
+    WireColor::Red => 0,
+    WireColor::Green => 1,
+  }
+}
+
+fn color_ports(color: WireColor, net: NetID) -> [Option<NetID>; 2] {
+  let mut ports = [None, None];
+  ports[color_port(color)] = Some(net);
+  ports
+}
+
+fn successors(netlist: &Netlist, cid: CombinatorID) -> Vec<CombinatorID> {
+  let outputs = match &netlist.combinators[cid] {
+    Combinator::Vanilla(comb) => comb.output_nets,
+    Combinator::Constant(comb) => comb.output_nets,
+    Combinator::Selector(comb) => comb.output_nets,
+  };
+  outputs.iter()
+    .flatten()
+    .flat_map(|&net| netlist.nets[net].in_conn.iter().map(|&(cid, _)| cid))
+    .collect()
+}
+
+/// Tarjan's strongly connected components algorithm over the combinator graph.
+#[derive(Debug)]
+struct Tarjan<'a> {
+  netlist: &'a Netlist,
+  index: Vec<Option<usize>>,
+  low_link: Vec<usize>,
+  on_stack: Vec<bool>,
+  stack: Vec<CombinatorID>,
+  next_index: usize,
+  sccs: Vec<Vec<CombinatorID>>,
+}
+
+impl<'a> Tarjan<'a> {
+  fn run(netlist: &'a Netlist) -> Vec<Vec<CombinatorID>> {
+    let len = netlist.combinators.len();
+    let mut tarjan = Tarjan {
+      netlist,
+      index: vec![None; len],
+      low_link: vec![0; len],
+      on_stack: vec![false; len],
+      stack: vec![],
+      next_index: 0,
+      sccs: vec![],
+    };
+    (0..len).for_each(|cid| {
+      if tarjan.index[cid].is_none() {
+        tarjan.connect(cid);
+      }
+    });
+    tarjan.sccs
+  }
+
+  fn connect(&mut self, cid: CombinatorID) {
+    self.index[cid] = Some(self.next_index);
+    self.low_link[cid] = self.next_index;
+    self.next_index += 1;
+    self.stack.push(cid);
+    self.on_stack[cid] = true;
+    successors(self.netlist, cid).into_iter().for_each(|next| {
+      match self.index[next] {
+        None => {
+          self.connect(next);
+          self.low_link[cid] = self.low_link[cid].min(self.low_link[next]);
+        }
+        Some(index) if self.on_stack[next] => {
+          self.low_link[cid] = self.low_link[cid].min(index);
+        }
+        _ => {}
+      }
+    });
+    if Some(self.low_link[cid]) == self.index[cid] {
+      let mut scc = vec![];
+      loop {
+        let v = self.stack.pop().unwrap();
+        self.on_stack[v] = false;
+        scc.push(v);
+        if v == cid {
+          break;
+        }
+      }
+      self.sccs.push(scc);
+    }
+  }
+}
@@ -1,16 +1,23 @@
-use crate::err::{Cerr, CerrSpan, TypeError};
-use crate::parse::ast::{Expr, Module, NetType, PortClass, Stmt, TriggerKind};
-use crate::parse::span::Span;
+use crate::err::{Cerr, CerrSpan, PortDiff, TypeError};
+use crate::parse::ast::{Bundle, Expr, Module, ModuleArgs, NetType, PortClass, PortDecl, Program, RomData, RomEntry, Stmt, TriggerKind};
+use crate::parse::project::Project;
+use crate::parse::span::{FileId, Span};
 use crate::parse::tokenizer::BinaryOp;
-use crate::synth::builtins::binaryop::binary_op_to_func_name;
+use crate::synth::builtins::binaryop::{binary_op_to_func_name, func_name_to_binary_op};
 use crate::synth::builtins::{BuiltinFunction, FunctionArgReq};
+use crate::synth::combinator::{Quality, Signal};
 use crate::synth::ir::{IRModule, IRModuleInst, IRStmt, IRTriggerStmt, IRValue, IRWireMemDecl};
-use std::collections::HashMap;
+use crate::synth::virt_signals::{SLOT_SIGNALS, VIRTUAL_SIGNALS};
+use std::collections::{HashMap, HashSet};
 
 /// Tracks program-wide validation state.
 struct GlobalValidationState<'a> {
   errors: Vec<CerrSpan>,
   modules: HashMap<String, &'a Module>,
+  bundles: HashMap<String, &'a Bundle>,
+  /// Where each module and bundle is declared, to report redeclarations
+  module_locations: HashMap<String, String>,
+  bundle_locations: HashMap<String, String>,
   builtins: &'a HashMap<String, Box<dyn BuiltinFunction>>,
 }
 
@@ -25,6 +32,10 @@ struct ModuleValidationState<'a, 'b> {
   trigger_stmts: Vec<IRTriggerStmt>,
   module_inst: Vec<IRModuleInst>,
   next_anon: u64,
+  /// The statement being transformed, which anonymous nets are created for.
+  span: Option<Span>,
+  /// The innermost trigger statement being transformed.
+  trigger_span: Option<Span>,
 }
 
 impl<'a, 'b> ModuleValidationState<'a, 'b> {
@@ -39,23 +50,33 @@ impl<'a, 'b> ModuleValidationState<'a, 'b> {
     input: bool,
     port_idx: Option<usize>,
   ) {
-    let prev = self.objects.insert(
-      name.clone(),
+    self.create_object(
+      name,
+      span,
       ObjectInfo {
         mem,
         input,
         exclusive_write: false,
+        array_index: None,
+        rom: None,
+        bundle: None,
+        span,
       },
     );
-    if prev.is_some() {
+    self
+      .ir_objects
+      .insert(name.clone(), IRWireMemDecl { ty, mem, port_idx, init: 0, reset: None, span: Some(span) });
+  }
+
+  /// Adds an object to `objects` only, which is enough for objects that aren't nets.
+  pub fn create_object(&mut self, name: &String, span: Span, info: ObjectInfo) {
+    let prev = self.objects.insert(name.clone(), info);
+    if let Some(prev) = prev {
       self
         .global
         .errors
-        .push(Cerr::MultipleDeclarations(name.clone()).with(span))
+        .push(Cerr::MultipleDeclarations(name.clone(), prev.span).with(span))
     }
-    self
-      .ir_objects
-      .insert(name.clone(), IRWireMemDecl { ty, mem, port_idx });
   }
 
   /// Utility method that creates an anonymous net.
@@ -70,6 +91,9 @@ impl<'a, 'b> ModuleValidationState<'a, 'b> {
         ty,
         mem: false,
         port_idx: None,
+        init: 0,
+        reset: None,
+        span: self.span,
       },
     );
     self.next_anon += 1;
@@ -79,24 +103,106 @@ impl<'a, 'b> ModuleValidationState<'a, 'b> {
   /// Either returns the net itself, or an anonymous net
   /// that is connected to a trigger filter
   pub fn set_or_trigger(&mut self, name: &String, on_trigger: Option<String>) -> String {
-    if let Some(on_trigger) = on_trigger {
-      let decl = self.ir_objects.get(name).unwrap();
-      let anon = self.create_anon_net(decl.ty);
+    let decl = self.ir_objects.get(name).unwrap();
+    let (ty, reset) = (decl.ty, decl.reset.clone());
+    let dest = if let Some(on_trigger) = on_trigger {
+      let anon = self.create_anon_net(ty);
       self.trigger_stmts.push(IRTriggerStmt {
         dest: name.clone(),
         src: anon.clone(),
         on: on_trigger,
+        span: self.trigger_span,
       });
       anon
     } else {
       name.clone()
+    };
+    let Some(reset) = reset else {
+      return dest;
+    };
+    // writes to memory with a reset are dropped while it's being reset,
+    // which gates them like a trigger that's active while the reset wire is 0
+    let anon = self.create_anon_net(ty);
+    self.trigger_stmts.push(IRTriggerStmt {
+      dest,
+      src: anon.clone(),
+      on: reset,
+      span: self.span,
+    });
+    anon
+  }
+
+  /// Gets the names of all objects that have been `=`-assigned to.
+  pub fn exclusively_written(&self) -> HashSet<String> {
+    self.objects.iter()
+      .filter(|(_, info)| info.exclusive_write)
+      .map(|(name, _)| name.clone())
+      .collect()
+  }
+
+  pub fn set_exclusively_written(&mut self, name: &str, written: bool) {
+    if let Some(info) = self.objects.get_mut(name) {
+      info.exclusive_write = written;
     }
   }
 
+  /// Looks up a bundle type, or reports an error if it doesn't exist.
+  pub fn bundle(&mut self, name: &String, span: Span) -> Option<&'a Bundle> {
+    let bundle = self.global.bundles.get(name).copied();
+    if bundle.is_none() {
+      self
+        .global
+        .errors
+        .push(Cerr::NotDeclared(name.clone()).with(span));
+    }
+    bundle
+  }
+
+  /// Gets the index table of a memory array, or reports an error if the object isn't one.
+  pub fn array_index(&mut self, name: &String, span: Span) -> Option<String> {
+    match self.objects.get(name) {
+      Some(ObjectInfo { array_index: Some(table), .. }) => Some(table.clone()),
+      Some(_) => {
+        self.global.errors.push(Cerr::NotAnArray(name.clone()).with(span));
+        None
+      }
+      None => {
+        self.global.errors.push(Cerr::NotDeclared(name.clone()).with(span));
+        None
+      }
+    }
+  }
+
+  /// Validates a write to an element of a memory array, and returns its index table.
+  /// Unlike other objects, arrays may be `=`-assigned to any number of times,
+  /// since each write only covers one slot.
+  pub fn validate_set_indexed(&mut self, name: &String, assign_type: BinaryOp, span: Span, trigger: bool) -> Option<String> {
+    if matches!(self.objects.get(name), Some(ObjectInfo { rom: Some(_), .. })) {
+      self.global.errors.push(Cerr::WriteToRom.with(span));
+      return None;
+    }
+    let table = self.array_index(name, span)?;
+    if assign_type == BinaryOp::Assign && !trigger {
+      self
+        .global
+        .errors
+        .push(Cerr::MemAssignOutsideOfTrigger.with(span));
+    }
+    Some(table)
+  }
+
   /// Validates a set statement or wire decl with expression.
   pub fn validate_set(&mut self, name: &String, assign_type: BinaryOp, span: Span, trigger: bool) {
     let info = self.objects.get(name);
     if let Some(info) = info {
+      if info.bundle.is_some() {
+        self.global.errors.push(Cerr::BundleAsValue(name.clone()).with(span));
+        return;
+      }
+      if info.rom.is_some() {
+        self.global.errors.push(Cerr::WriteToRom.with(span));
+        return;
+      }
       if info.input {
         self.global.errors.push(Cerr::WriteToInput.with(span));
       }
@@ -131,27 +237,108 @@ struct ObjectInfo {
   input: bool,
   /// Whether the object has already been `=`-assigned to
   exclusive_write: bool,
+  /// For memory arrays, the net holding the index table
+  array_index: Option<String>,
+  /// For ROMs, how to look up entries
+  rom: Option<RomInfo>,
+  /// For bundles, the bundle type. The bundle itself isn't a net, only its members are.
+  bundle: Option<String>,
+  /// Where the object is declared.
+  span: Span,
+}
+
+/// ROMs aren't nets themselves, just a set of constant entries that lookups read from.
+#[derive(Clone)]
+struct RomInfo {
+  /// Signal that lookups move the index onto, which none of the entries use
+  select: String,
+  /// For single ROMs, the signal the entries hold their values on
+  value: Option<String>,
+  /// One constant net per entry
+  entries: Vec<String>,
+}
+
+/// The bundles and modules declared in one source file.
+struct Source<'a> {
+  file: FileId,
+  /// Path of the file, which is empty for programs that aren't loaded from files
+  path: String,
+  bundles: &'a [(Bundle, Span)],
+  modules: &'a [(Module, Span)],
+}
+
+/// The public method that transforms a project, where all files share one namespace.
+pub fn transform_project(
+  project: &Project,
+  builtins: &HashMap<String, Box<dyn BuiltinFunction>>,
+) -> (Vec<IRModule>, Vec<CerrSpan>) {
+  let sources = project
+    .programs()
+    .map(|(file, program)| Source {
+      file,
+      path: project.files[file].path.display().to_string(),
+      bundles: &program.bundles,
+      modules: &program.modules,
+    })
+    .collect::<Vec<_>>();
+  transform(&sources, builtins)
+}
+
+/// The public method that transforms a program, including its bundle types.
+pub fn transform_program(
+  program: &Program,
+  builtins: &HashMap<String, Box<dyn BuiltinFunction>>,
+) -> (Vec<IRModule>, Vec<CerrSpan>) {
+  transform(&[Source {
+    file: 0,
+    path: String::new(),
+    bundles: &program.bundles,
+    modules: &program.modules,
+  }], builtins)
 }
 
-/// The public method that transforms all modules.
+/// The public method that transforms all modules, without any bundle types.
 pub fn transform_modules(
   modules: &[(Module, Span)],
   builtins: &HashMap<String, Box<dyn BuiltinFunction>>,
+) -> (Vec<IRModule>, Vec<CerrSpan>) {
+  transform(&[Source {
+    file: 0,
+    path: String::new(),
+    bundles: &[],
+    modules,
+  }], builtins)
+}
+
+fn transform<'a>(
+  sources: &'a [Source<'a>],
+  builtins: &'a HashMap<String, Box<dyn BuiltinFunction>>,
 ) -> (Vec<IRModule>, Vec<CerrSpan>) {
   let mut state = GlobalValidationState {
     errors: vec![],
     modules: Default::default(),
+    bundles: Default::default(),
+    module_locations: Default::default(),
+    bundle_locations: Default::default(),
     builtins,
   };
-  collect_modules(&mut state, modules);
-  let ir_modules = modules
-    .into_iter()
-    .map(|v| transform_module(&mut state, v))
-    .collect::<Vec<_>>();
+  sources.iter().for_each(|source| collect_bundles(&mut state, source));
+  sources.iter().for_each(|source| collect_modules(&mut state, source));
+  let mut ir_modules = vec![];
+  for source in sources {
+    for module in source.modules {
+      let first_error = state.errors.len();
+      ir_modules.push(transform_module(&mut state, module, source.file));
+      // the module doesn't know which file it's in
+      state.errors[first_error..]
+        .iter_mut()
+        .for_each(|err| err.file = source.file);
+    }
+  }
   (ir_modules, state.errors)
 }
 
-fn transform_module(state: &mut GlobalValidationState, module: &(Module, Span)) -> IRModule {
+fn transform_module(state: &mut GlobalValidationState, module: &(Module, Span), file: FileId) -> IRModule {
   let mut state = ModuleValidationState {
     global: state,
     ir_objects: Default::default(),
@@ -160,15 +347,18 @@ fn transform_module(state: &mut GlobalValidationState, module: &(Module, Span))
     trigger_stmts: vec![],
     module_inst: vec![],
     next_anon: 0,
+    span: None,
+    trigger_span: None,
   };
-  collect_module_inputs(&mut state, module);
-  collect_decls(&mut state, &module.0.stmts, false);
+  let ports = collect_module_inputs(&mut state, module);
+  collect_decls(&mut state, &module.0.stmts);
   module.0.stmts.iter().for_each(|v| {
     transform_stmt(&mut state, v, None);
   });
   IRModule {
     name: module.0.name.clone(),
-    ports: module.0.ports.clone(),
+    file,
+    ports,
     objects: state.ir_objects,
     stmts: state.stmts,
     trigger_stmt: state.trigger_stmts,
@@ -176,48 +366,248 @@ fn transform_module(state: &mut GlobalValidationState, module: &(Module, Span))
   }
 }
 
-fn collect_modules<'a>(state: &'_ mut GlobalValidationState<'a>, modules: &'a [(Module, Span)]) {
-  modules.into_iter().for_each(|(module, span)| {
-    let prev = state.modules.insert(module.name.clone(), module);
-    if let Some(prev) = prev {
-      state
-        .errors
-        .push(Cerr::MultipleDeclarations(prev.name.clone()).with(*span));
+fn collect_modules<'a>(state: &'_ mut GlobalValidationState<'a>, source: &Source<'a>) {
+  source.modules.iter().for_each(|(module, span)| {
+    if let Some(prev) = state.module_locations.get(&module.name) {
+      state.errors.push(
+        Cerr::AlreadyDeclaredAt(module.name.clone(), prev.clone())
+          .with(*span)
+          .in_file(source.file),
+      );
+      return;
     }
+    state.modules.insert(module.name.clone(), module);
+    state
+      .module_locations
+      .insert(module.name.clone(), location(&source.path, *span));
   })
 }
 
-fn collect_module_inputs(state: &mut ModuleValidationState, module: &(Module, Span)) {
+/// Formats where a declaration is, for errors that point to it from elsewhere.
+fn location(path: &str, span: Span) -> String {
+  if path.is_empty() {
+    format!("{}:{}", span.start.line, span.start.col)
+  } else {
+    format!("{}:{}:{}", path, span.start.line, span.start.col)
+  }
+}
+
+fn collect_bundles<'a>(state: &'_ mut GlobalValidationState<'a>, source: &Source<'a>) {
+  source.bundles.iter().for_each(|(bundle, span)| {
+    if let Some(prev) = state.bundle_locations.get(&bundle.name) {
+      state.errors.push(
+        Cerr::AlreadyDeclaredAt(bundle.name.clone(), prev.clone())
+          .with(*span)
+          .in_file(source.file),
+      );
+      return;
+    }
+    state.bundles.insert(bundle.name.clone(), bundle);
+    state
+      .bundle_locations
+      .insert(bundle.name.clone(), location(&source.path, *span));
+  })
+}
+
+/// Declares the module ports, and returns them with bundle ports
+/// flattened into one port per member.
+fn collect_module_inputs(state: &mut ModuleValidationState, module: &(Module, Span)) -> Vec<PortDecl> {
   let (module, span) = module;
-  module.ports.iter().enumerate().for_each(|(i, v)| {
-    state.create_net(
-      &v.name,
-      *span,
-      v.signal_class,
-      false,
-      v.port_class == PortClass::In,
-      Some(i),
-    );
+  let mut ports = vec![];
+  module.ports.iter().for_each(|v| {
+    let input = v.port_class == PortClass::In;
+    let Some(bundle) = &v.bundle else {
+      state.create_net(&v.name, *span, v.signal_class, false, input, Some(ports.len()));
+      ports.push(v.clone());
+      return;
+    };
+    let Some(bundle) = state.bundle(bundle, *span) else {
+      return;
+    };
+    collect_bundle(state, &v.name, bundle, *span, input);
+    bundle.members.iter().for_each(|member| {
+      let name = format!("{}.{}", v.name, member.name);
+      state.create_net(&name, *span, member.signal_class, false, input, Some(ports.len()));
+      ports.push(PortDecl {
+        port_class: v.port_class,
+        signal_class: member.signal_class,
+        name,
+        bundle: None,
+      });
+    });
+  });
+  ports
+}
+
+/// Declares a bundle object. Its members are declared separately.
+fn collect_bundle(state: &mut ModuleValidationState, name: &String, bundle: &Bundle, span: Span, input: bool) {
+  state.create_object(
+    name,
+    span,
+    ObjectInfo {
+      mem: false,
+      input,
+      exclusive_write: false,
+      array_index: None,
+      rom: None,
+      bundle: Some(bundle.name.clone()),
+      span,
+    },
+  );
+}
+
+fn collect_decls(state: &mut ModuleValidationState, stmts: &[(Stmt, Span)]) {
+  stmts.into_iter().for_each(|(stmt, span)| {
+    state.span = Some(*span);
+    collect_decl(state, stmt, span);
   });
 }
 
-fn collect_decls(state: &mut ModuleValidationState, stmts: &[(Stmt, Span)], in_trigger: bool) {
-  stmts.into_iter().for_each(|(stmt, span)| match stmt {
-    Stmt::MemDecl { name, signal_class } => {
+fn collect_decl(state: &mut ModuleValidationState, stmt: &Stmt, span: &Span) {
+  match stmt {
+    Stmt::MemDecl { name, signal_class, .. } => {
       state.create_net(name, *span, *signal_class, true, false, None);
     }
+    Stmt::MemArrayDecl { name, len, .. } => {
+      // the slots are stored as signals of one mixed memory cell
+      state.create_net(name, *span, NetType::Mixed, true, false, None);
+      if *len == 0 || *len as usize > SLOT_SIGNALS.len() {
+        state
+          .global
+          .errors
+          .push(Cerr::InvalidArrayLength(SLOT_SIGNALS.len()).with(*span));
+        return;
+      }
+      let table = state.create_anon_net(NetType::Mixed);
+      state.stmts.push(IRStmt {
+        dest: table.clone(),
+        op: "$array_index".into(),
+        args: vec![IRValue::Lit(*len as i32)],
+        span: state.span,
+      });
+      // unwrap: the object was just created
+      state.objects.get_mut(name).unwrap().array_index = Some(table);
+    }
     Stmt::WireDecl {
       name, signal_class, ..
     } => {
       state.create_net(name, *span, *signal_class, false, false, None);
     }
-    Stmt::Trigger { statements, .. } => {
-      if !in_trigger {
-        collect_decls(state, &statements, true);
-      }
+    Stmt::BundleWireDecl { name, bundle, .. } => {
+      let Some(bundle) = state.bundle(bundle, *span) else {
+        return;
+      };
+      collect_bundle(state, name, bundle, *span, false);
+      bundle.members.iter().for_each(|member| {
+        let member_name = format!("{}.{}", name, member.name);
+        state.create_net(&member_name, *span, member.signal_class, false, false, None);
+      });
+    }
+    Stmt::RomDecl {
+      name,
+      signal_class,
+      data,
+    } => {
+      collect_rom(state, name, *signal_class, data, *span);
+    }
+    Stmt::Trigger { statements, else_statements, .. } => {
+      collect_decls(state, statements);
+      collect_decls(state, else_statements);
     }
     _ => {}
-  })
+  }
+}
+
+/// Declares a ROM and emits a constant net for each of its entries.
+fn collect_rom(state: &mut ModuleValidationState, name: &String, ty: NetType, data: &RomData, span: Span) {
+  let entries: &[RomEntry] = match data {
+    RomData::Inline(entries) => entries,
+    RomData::File(path) => {
+      state
+        .global
+        .errors
+        .push(Cerr::RomFile(path.clone(), "file was not loaded".into()).with(span));
+      &[]
+    }
+  };
+  // single ROMs always use the same two signals
+  let value = (ty == NetType::Single).then(|| VIRTUAL_SIGNALS[1].clone());
+  let mut signals = vec![];
+  for entry in entries {
+    let entry_signals = match (ty, entry) {
+      (NetType::Single, RomEntry::Single(val)) => vec![(VIRTUAL_SIGNALS[1].clone(), *val)],
+      (NetType::Mixed, RomEntry::Mixed(vals)) => vals
+        .iter()
+        .filter_map(|(signal, val)| match Signal::parse(signal) {
+          Some(signal) => Some((signal, *val)),
+          None => {
+            state
+              .global
+              .errors
+              .push(Cerr::InvalidRomSignal(signal.clone()).with(span));
+            None
+          }
+        })
+        .collect(),
+      (_, entry) => {
+        let src_ty = match entry {
+          RomEntry::Single(_) => "Literal",
+          RomEntry::Mixed(_) => "Mixed",
+        };
+        state.global.errors.push(
+          Cerr::TypeErrorGeneric(TypeError {
+            src_ty: src_ty.into(),
+            dst_ty: format!("{:?}", ty),
+          })
+          .with(span),
+        );
+        vec![]
+      }
+    };
+    signals.push(entry_signals);
+  }
+  let select = VIRTUAL_SIGNALS
+    .iter()
+    .chain(SLOT_SIGNALS.iter())
+    .find(|v| !signals.iter().flatten().any(|(signal, _)| signal == *v))
+    .expect("ROM entries use every signal that could select them")
+    .to_string();
+  let entries = signals
+    .into_iter()
+    .enumerate()
+    .map(|(k, entry_signals)| {
+      let anon = state.create_anon_net(NetType::Mixed);
+      let mut args = vec![IRValue::Str(select.clone()), IRValue::Lit(k as i32)];
+      for (signal, val) in entry_signals {
+        args.push(IRValue::Str(signal.to_string()));
+        args.push(IRValue::Lit(val));
+      }
+      state.stmts.push(IRStmt {
+        dest: anon.clone(),
+        op: "$rom_entry".into(),
+        args,
+        span: state.span,
+      });
+      anon
+    })
+    .collect();
+  state.create_object(
+    name,
+    span,
+    ObjectInfo {
+      mem: false,
+      input: false,
+      exclusive_write: false,
+      array_index: None,
+      rom: Some(RomInfo {
+        select,
+        value: value.map(|v| v.to_string()),
+        entries,
+      }),
+      bundle: None,
+      span,
+    },
+  );
 }
 
 fn transform_stmt(
@@ -227,8 +617,56 @@ fn transform_stmt(
 ) {
   let (stmt, span) = stmt;
   let span = *span;
+  state.span = Some(span);
   match &stmt {
-    Stmt::MemDecl { .. } => {}
+    Stmt::MemDecl {
+      name,
+      signal_class,
+      init,
+      reset,
+    } => {
+      if init.is_some() && signal_class == &NetType::Mixed {
+        state.global.errors.push(
+          Cerr::TypeErrorGeneric(TypeError {
+            src_ty: "Literal".into(),
+            dst_ty: "Mixed".into(),
+          })
+          .with(span),
+        );
+      }
+      if let Some(reset) = reset {
+        match state.ir_objects.get(reset) {
+          None => state
+            .global
+            .errors
+            .push(Cerr::NotDeclared(reset.clone()).with(span)),
+          Some(decl) if decl.ty != NetType::Single => state.global.errors.push(
+            Cerr::TypeErrorGeneric(TypeError {
+              src_ty: format!("{:?}", decl.ty),
+              dst_ty: "Single".into(),
+            })
+            .with(span),
+          ),
+          _ => {}
+        }
+      }
+      // unwrap: all declarations were collected beforehand
+      let decl = state.ir_objects.get_mut(name).unwrap();
+      decl.init = init.unwrap_or(0);
+      decl.reset = reset.clone();
+    }
+
+    Stmt::MemArrayDecl { signal_class, .. } => {
+      if signal_class == &NetType::Mixed {
+        state.global.errors.push(
+          Cerr::TypeErrorGeneric(TypeError {
+            src_ty: "Mixed".into(),
+            dst_ty: "Single".into(),
+          })
+          .with(span),
+        );
+      }
+    }
 
     Stmt::Set {
       name,
@@ -236,8 +674,11 @@ fn transform_stmt(
       expr,
     } => {
       state.validate_set(name, *assign_type, span, trigger.is_some());
-      let ir_obj = state.ir_objects.get(name);
-      if ir_obj.unwrap().mem && assign_type == &BinaryOp::Assign {
+      let Some(ir_obj) = state.ir_objects.get(name) else {
+        // an error has already been reported
+        return;
+      };
+      if ir_obj.mem && assign_type == &BinaryOp::Assign {
         // mem objects that are assigned to need special treatment
         // since they need to add a negated version back into it (since naive-assign actually increments it)
         
@@ -246,7 +687,8 @@ fn transform_stmt(
         let net = state.set_or_trigger(name, trigger.cloned());
         transform_expr_and_assign_to(state, &Expr::BinaryOps {
           car: Box::new(expr.clone()),
-          cdr: vec![(BinaryOp::Sub, Expr::Identifier { name: name.clone() })],
+          cdr: vec![(BinaryOp::Sub, Expr::Identifier { name: name.clone(), span: expr.span() })],
+          span: expr.span(),
         }, span, net);
       } else {
         let net = state.set_or_trigger(name, trigger.cloned());
@@ -254,6 +696,44 @@ fn transform_stmt(
       }
     }
 
+    // ROMs are fully handled while collecting declarations
+    Stmt::RomDecl { .. } => {}
+
+    Stmt::SetIndexed {
+      name,
+      index,
+      assign_type,
+      expr,
+    } => {
+      let Some(table) = state.validate_set_indexed(name, *assign_type, span, trigger.is_some()) else {
+        return;
+      };
+      let (index_value, _) = transform_expr(state, index);
+      let mask = transform_array_mask(state, table, index_value, index.span());
+      let (value, _) = transform_expr(state, expr);
+      expect_single(state, &value, expr.span());
+      // the mask is 1 on the addressed slot, so this only writes to that slot
+      let delta = transform_single_binary_op(state, BinaryOp::Mul, (IRValue::Net(mask.clone()), index.span()), (value, expr.span()));
+      let delta = if assign_type == &BinaryOp::Assign {
+        // like other mem objects, the current value of the slot is subtracted first. It stays on the
+        // slot's signal, and filtering and negating it takes two ticks longer than the product, which
+        // is delayed to match so that both sides are read with the same mask.
+        let current = transform_array_filter(state, name, mask);
+        let delayed = state.create_anon_net(NetType::Mixed);
+        state.stmts.push(IRStmt {
+          dest: delayed.clone(),
+          op: "delay".into(),
+          args: vec![delta, IRValue::Lit(2)],
+          span: state.span,
+        });
+        transform_single_binary_op(state, BinaryOp::Sub, (IRValue::Net(delayed), span), (current, span))
+      } else {
+        delta
+      };
+      let net = state.set_or_trigger(name, trigger.cloned());
+      assign_value(state, delta, true, span, net);
+    }
+
     Stmt::WireDecl { name, expr, .. } => {
       if let Some(expr) = expr {
         state.validate_set(name, BinaryOp::Assign, span, trigger.is_some());
@@ -262,6 +742,21 @@ fn transform_stmt(
       }
     }
 
+    Stmt::BundleWireDecl { name, bundle, expr } => {
+      let (Some(expr), Some(bundle)) = (expr, state.global.bundles.get(bundle).copied()) else {
+        return;
+      };
+      let Some(sources) = bundle_source(state, expr, bundle) else {
+        return;
+      };
+      for (member, source) in bundle.members.iter().zip(sources) {
+        let dest = format!("{}.{}", name, member.name);
+        state.validate_set(&dest, BinaryOp::Assign, span, trigger.is_some());
+        let net = state.set_or_trigger(&dest, trigger.cloned());
+        transform_expr_and_assign_to(state, &Expr::Identifier { name: source, span: expr.span() }, span, net);
+      }
+    }
+
     Stmt::ModuleInst {
       module: module_name,
       args,
@@ -275,50 +770,65 @@ fn transform_stmt(
           .push(Cerr::NotDeclared(module_name.clone()).with(span));
         return;
       }
-      let module = module.unwrap();
+      let module = *module.unwrap();
 
-      // check module has the same number of arguments
-      if args.len() != module.ports.len() {
-        state
-          .global
-          .errors
-          .push(Cerr::WrongNumberOfModuleArgs(module.ports.len()).with(span));
-      }
-
-      let args = args
-        .iter()
+      let args = match_module_args(state, module, args, span)
+        .into_iter()
         .zip(module.ports.iter())
         .enumerate()
         .map(|(i, (expr, port))| {
-          if let Expr::Identifier { name } = expr {
+          // an error has already been reported for missing ports
+          let expr = expr?;
+          if let Some(bundle) = &port.bundle {
+            // bundle arguments are connected member by member
+            let bundle = state.global.bundles.get(bundle).copied()?;
+            let sources = bundle_source(state, expr, bundle)?;
+            if port.port_class == PortClass::Out {
+              sources.iter().for_each(|source| {
+                state.validate_set(source, BinaryOp::Assign, span, trigger.is_some());
+              });
+            }
+            Some(sources)
+          } else if let Expr::Identifier { name, span } = expr {
+            if let Some(ObjectInfo { bundle: Some(bundle), .. }) = state.objects.get(name) {
+              state.global.errors.push(
+                Cerr::TypeErrorGeneric(TypeError {
+                  src_ty: bundle.clone(),
+                  dst_ty: format!("{:?}", port.signal_class),
+                })
+                .with(*span),
+              );
+              return None;
+            }
             if port.port_class == PortClass::Out {
               // validate multiple assign
-              state.validate_set(name, BinaryOp::Assign, span, trigger.is_some());
+              state.validate_set(name, BinaryOp::Assign, *span, trigger.is_some());
             }
-            Some(name.clone())
+            Some(vec![name.clone()])
           } else {
             if port.port_class != PortClass::In {
               state
                 .global
                 .errors
-                .push(Cerr::ExprForOutInoutPort(i).with(span));
+                .push(Cerr::ExprForOutInoutPort(i).with(expr.span()));
             }
-            let arg = transform_expr(state, expr, span).0;
+            let arg = transform_expr(state, expr).0;
             match arg {
               IRValue::Net(net) => Some(net),
               IRValue::Lit(v) => {
                 let anon = state.create_anon_net(NetType::Single);
                 state.stmts.push(IRStmt {
                   dest: anon.clone(),
-                  op: "passthrough".into(),
+                  op: "$passthrough".into(),
                   args: vec![IRValue::Lit(v)],
+                  span: state.span,
                 });
                 Some(anon)
               }
               IRValue::Str(_) => None,
             }
-            .map(|v| v.clone())
-            .ok_or_else(|| Cerr::UnexpectedString.with(span))
+            .map(|v| vec![v])
+            .ok_or_else(|| Cerr::UnexpectedString.with(expr.span()))
             .map_err(|e| state.global.errors.push(e))
             .ok()
           }
@@ -328,7 +838,8 @@ fn transform_stmt(
       if let Some(args) = args {
         state.module_inst.push(IRModuleInst {
           name: module_name.clone(),
-          args,
+          args: args.concat(),
+          span: state.span,
         });
       }
     }
@@ -336,26 +847,52 @@ fn transform_stmt(
     Stmt::Trigger {
       watching,
       trigger_kind,
+      once,
       statements,
+      else_statements,
     } => {
-      if trigger.is_some() {
-        state
-          .global
-          .errors
-          .push(Cerr::NestedTriggerBlocks.with(span));
-        return;
-      }
-      let obj_info = state.objects.get(watching);
-      if obj_info.is_none() {
-        state
-          .global
-          .errors
-          .push(Cerr::NotDeclared(watching.clone()).with(span));
+      let (watched, _) = transform_expr(state, watching);
+      if let Ok(NetType::Mixed) = get_type(state, &watched) {
+        state.global.errors.push(
+          Cerr::TypeErrorGeneric(TypeError {
+            src_ty: "Mixed".into(),
+            dst_ty: "Single".into(),
+          })
+          .with(watching.span()),
+        );
       }
+      let watched = match watched {
+        IRValue::Net(net) => net,
+        IRValue::Lit(val) => {
+          // constant conditions still need a net to watch
+          let anon = state.create_anon_net(NetType::Single);
+          state.stmts.push(IRStmt {
+            dest: anon.clone(),
+            op: "$passthrough".into(),
+            args: vec![IRValue::Lit(val)],
+            span: state.span,
+          });
+          anon
+        }
+        IRValue::Str(_) => {
+          state.global.errors.push(Cerr::UnexpectedString.with(span));
+          return;
+        }
+      };
       // if it's raw mode, use the trigger signal directly
       // or generate a trigger detector
-      let trigger = if trigger_kind == &TriggerKind::Raw {
-        watching.clone()
+      let inner_trigger = if trigger_kind == &TriggerKind::Raw && is_condition(watching) {
+        // a raw trigger fires when its net is 0, but a condition should fire while it's true
+        let anon = state.create_anon_net(NetType::Single);
+        state.stmts.push(IRStmt {
+          dest: anon.clone(),
+          op: binary_op_to_func_name(BinaryOp::Eq).to_owned(),
+          args: vec![IRValue::Net(watched), IRValue::Lit(0)],
+          span: state.span,
+        });
+        anon
+      } else if trigger_kind == &TriggerKind::Raw {
+        watched
       } else {
         let anon = state.create_anon_net(NetType::Single);
         let trigger_fn = match trigger_kind {
@@ -367,53 +904,174 @@ fn transform_stmt(
         state.stmts.push(IRStmt {
           dest: anon.clone(),
           op: trigger_fn.to_owned(),
-          args: vec![IRValue::Net(watching.clone())],
+          args: vec![IRValue::Net(watched)],
+          span: state.span,
         });
         anon
       };
+      let inner_trigger = if *once {
+        transform_trigger_once(state, inner_trigger, *trigger_kind)
+      } else {
+        inner_trigger
+      };
+      // with an else branch, both branches watch a normalized net so that they stay aligned
+      let (then_trigger, else_trigger) = if else_statements.is_empty() {
+        (inner_trigger, None)
+      } else {
+        let [then_trigger, else_trigger] = [BinaryOp::Ne, BinaryOp::Eq].map(|op| {
+          let anon = state.create_anon_net(NetType::Single);
+          state.stmts.push(IRStmt {
+            dest: anon.clone(),
+            op: binary_op_to_func_name(op).to_owned(),
+            args: vec![IRValue::Net(inner_trigger.clone()), IRValue::Lit(0)],
+            span: state.span,
+          });
+          anon
+        });
+        (then_trigger, Some(else_trigger))
+      };
+      // nested triggers only fire when the enclosing trigger does too
+      let combine = |state: &mut ModuleValidationState, inner: String| match trigger {
+        Some(outer) => transform_trigger_and(state, outer, inner),
+        None => inner,
+      };
+      let then_trigger = combine(state, then_trigger);
+      let else_trigger = else_trigger.map(|v| combine(state, v));
+      let outer_span = state.trigger_span.replace(span);
+      let written_before = state.exclusively_written();
       for inner in statements {
-        transform_stmt(state, inner, Some(&trigger));
+        transform_stmt(state, inner, Some(&then_trigger));
       }
+      if let Some(else_trigger) = else_trigger {
+        // only one branch executes at a time, so each branch may write to the same objects once
+        let written_then = state.exclusively_written()
+          .difference(&written_before)
+          .cloned()
+          .collect::<Vec<_>>();
+        written_then.iter().for_each(|v| state.set_exclusively_written(v, false));
+        for inner in else_statements {
+          transform_stmt(state, inner, Some(&else_trigger));
+        }
+        written_then.iter().for_each(|v| state.set_exclusively_written(v, true));
+      }
+      state.trigger_span = outer_span;
     }
   }
 }
 
+/// Combines two trigger nets into one that fires only when both fire.
+/// Trigger nets fire on 0, so each one is normalized to 0 or 1 onto a shared net,
+/// which is 0 only if both are.
+fn transform_trigger_and(state: &mut ModuleValidationState, a: &str, b: String) -> String {
+  let anon = state.create_anon_net(NetType::Single);
+  for v in [a.to_owned(), b] {
+    state.stmts.push(IRStmt {
+      dest: anon.clone(),
+      op: binary_op_to_func_name(BinaryOp::Ne).to_owned(),
+      args: vec![IRValue::Net(v), IRValue::Lit(0)],
+      span: state.span,
+    });
+  }
+  anon
+}
+
+/// Makes a trigger net fire only on the first tick of each run of ticks where it fires.
+/// Trigger nets fire on 0, so this is a falling edge detector on the trigger net.
+fn transform_trigger_once(state: &mut ModuleValidationState, trigger: String, trigger_kind: TriggerKind) -> String {
+  let trigger = if trigger_kind == TriggerKind::Raw {
+    // raw triggers can be any value, so normalize them to 0 or 1 first
+    let anon = state.create_anon_net(NetType::Single);
+    state.stmts.push(IRStmt {
+      dest: anon.clone(),
+      op: binary_op_to_func_name(BinaryOp::Ne).to_owned(),
+      args: vec![IRValue::Net(trigger), IRValue::Lit(0)],
+      span: state.span,
+    });
+    anon
+  } else {
+    trigger
+  };
+  let anon = state.create_anon_net(NetType::Single);
+  state.stmts.push(IRStmt {
+    dest: anon.clone(),
+    op: "trig_dec".to_owned(),
+    args: vec![IRValue::Net(trigger)],
+    span: state.span,
+  });
+  anon
+}
+
+/// Whether an expression is a comparison or logical op, which is 1 while it's true.
+fn is_condition(expr: &Expr) -> bool {
+  // operators of one chain have the same precedence, so checking the first is enough
+  matches!(expr, Expr::BinaryOps { cdr, .. }
+    if cdr.first().is_some_and(|(op, _)| op.is_comparison() || op.is_logical()))
+}
+
 /// Returns a tuple of (result net, is anonymous net?, net type)
 /// The expectation is that if the net was anonymous, then it is safe to change
 /// the stmt that assigns it to a different net.
-fn transform_expr(state: &mut ModuleValidationState, expr: &Expr, span: Span) -> (IRValue, bool) {
+/// Errors point at the sub-expression they're about.
+fn transform_expr(state: &mut ModuleValidationState, expr: &Expr) -> (IRValue, bool) {
+  let span = expr.span();
   match expr {
-    Expr::Identifier { name } => {
-      if !state.objects.contains_key(name) {
-        state
+    Expr::Identifier { name, .. } => {
+      match state.objects.get(name) {
+        None => state
           .global
           .errors
-          .push(Cerr::NotDeclared(name.clone()).with(span));
+          .push(Cerr::NotDeclared(name.clone()).with(span)),
+        Some(ObjectInfo { rom: Some(_), .. }) => state
+          .global
+          .errors
+          .push(Cerr::RomWithoutIndex(name.clone()).with(span)),
+        Some(ObjectInfo { bundle: Some(_), .. }) => state
+          .global
+          .errors
+          .push(Cerr::BundleAsValue(name.clone()).with(span)),
+        _ => {}
       }
       (IRValue::Net(name.clone()), false)
     }
 
-    Expr::BinaryOps { car, cdr } => {
-      let mut resolved_arg = Some(transform_expr(state, car, span).0);
+    Expr::Index { name, index, .. } => {
+      let index_span = index.span();
+      let (index, _) = transform_expr(state, index);
+      if let Some(rom) = state.objects.get(name).and_then(|v| v.rom.clone()) {
+        return (transform_rom_lookup(state, &rom, index, index_span), true);
+      }
+      match state.array_index(name, span) {
+        Some(table) => {
+          let mask = transform_array_mask(state, table, index, index_span);
+          (transform_array_read(state, name, mask), true)
+        }
+        None => (IRValue::Net("$error".into()), false),
+      }
+    }
+
+    Expr::BinaryOps { car, cdr, .. } => {
+      // the left operand spans everything that was folded into it so far
+      let mut resolved_arg = Some((transform_expr(state, car).0, car.span()));
       cdr.iter().for_each(|(op, expr)| {
-        let (irv, _) = transform_expr(state, expr, span);
-        resolved_arg = Some(transform_single_binary_op(
-          state,
-          *op,
-          resolved_arg.take().unwrap(),
-          irv,
-          span,
-        ));
+        let (irv, _) = transform_expr(state, expr);
+        let left = resolved_arg.take().unwrap();
+        let left_span = left.1.union(expr.span());
+        let result = transform_single_binary_op(state, *op, left, (irv, expr.span()));
+        resolved_arg = Some((result, left_span));
       });
-      (resolved_arg.unwrap(), true)
+      (resolved_arg.unwrap().0, true)
     }
 
-    Expr::FnCall { args, func } => {
-      let args = args
+    Expr::FnCall { args: arg_exprs, func, .. } => {
+      let args = arg_exprs
         .iter()
-        .map(|expr| transform_expr(state, expr, span).0)
+        .map(|expr| transform_expr(state, expr).0)
         .collect::<Vec<_>>();
       if let Some(func_box) = state.global.builtins.get(func) {
+        let arg_tys = args
+          .iter()
+          .map(|v| get_type(state, v).ok())
+          .collect::<Vec<_>>();
         if args.len() != func_box.arg_ty().len() {
           state
             .global
@@ -426,9 +1084,15 @@ fn transform_expr(state: &mut ModuleValidationState, expr: &Expr, span: Span) ->
           .zip(args.iter())
           .enumerate()
           .for_each(|(i, (a1, a2))| {
+            // errors about an argument point at the argument
+            let span = arg_exprs[i].span();
             // do type checking
             match a1 {
-              FunctionArgReq::Any => {}
+              FunctionArgReq::Any => {
+                if matches!(a2, &IRValue::Str(_)) {
+                  state.global.errors.push(Cerr::UnexpectedString.with(span));
+                }
+              }
               FunctionArgReq::Net(ty) => {
                 match a2 {
                   IRValue::Net(net) => {
@@ -496,14 +1160,86 @@ fn transform_expr(state: &mut ModuleValidationState, expr: &Expr, span: Span) ->
                     .push(Cerr::ExpectedString(i, func.clone()).with(span))
                 }
               }
+              FunctionArgReq::Signal => match a2 {
+                IRValue::Str(str) => {
+                  if Signal::parse(str).is_none() {
+                    state
+                      .global
+                      .errors
+                      .push(Cerr::InvalidSignal(i, func.clone(), str.clone()).with(span))
+                  }
+                }
+                _ => state
+                  .global
+                  .errors
+                  .push(Cerr::ExpectedString(i, func.clone()).with(span)),
+              },
+              FunctionArgReq::Quality => match a2 {
+                IRValue::Str(str) => {
+                  if Quality::parse(str).is_none() {
+                    state
+                      .global
+                      .errors
+                      .push(Cerr::InvalidQuality(i, func.clone(), str.clone()).with(span))
+                  }
+                }
+                _ => state
+                  .global
+                  .errors
+                  .push(Cerr::ExpectedString(i, func.clone()).with(span)),
+              },
+              FunctionArgReq::SameAs(j) => match a2 {
+                IRValue::Str(_) => {
+                  state.global.errors.push(Cerr::UnexpectedString.with(span));
+                }
+                _ => {
+                  if let (Some(ty), Some(ty2)) = (arg_tys.get(*j).copied().flatten(), arg_tys[i]) {
+                    if ty != ty2 {
+                      state.global.errors.push(
+                        Cerr::TypeErrArgMismatch(
+                          i,
+                          func.clone(),
+                          TypeError {
+                            src_ty: format!("{:?}", ty2),
+                            dst_ty: format!("{:?}", ty),
+                          },
+                        )
+                        .with(span),
+                      );
+                    }
+                  }
+                }
+              },
+              FunctionArgReq::Count => {
+                if !matches!(a2, &IRValue::Lit(v) if v >= 0) {
+                  state
+                    .global
+                    .errors
+                    .push(Cerr::ExpectedCount(i, func.clone()).with(span))
+                }
+              }
             }
           });
+        // calls that are equivalent to one of their arguments don't need to be synthesized
+        let lits = args
+          .iter()
+          .map(|v| match v {
+            IRValue::Lit(v) => Some(*v),
+            _ => None,
+          })
+          .collect::<Vec<_>>();
+        if let Some(i) = func_box.fold_to_arg(&lits) {
+          let is_anon = matches!(&args[i], IRValue::Net(net) if net.starts_with("$anon_"));
+          return (args[i].clone(), is_anon);
+        }
         // create anon net
-        let anon = state.create_anon_net(func_box.return_ty().expect("Non-type checked function used"));
+        // if the return type can't be inferred, an arg error has already been reported
+        let anon = state.create_anon_net(func_box.return_ty(&arg_tys).unwrap_or(NetType::Single));
         state.stmts.push(IRStmt {
           dest: anon.clone(),
           op: func.clone(),
           args,
+          span: state.span,
         });
         (IRValue::Net(anon), true)
       } else {
@@ -515,9 +1251,9 @@ fn transform_expr(state: &mut ModuleValidationState, expr: &Expr, span: Span) ->
       }
     }
 
-    Expr::Literal { val } => (IRValue::Lit(*val), false),
+    Expr::Literal { val, .. } => (IRValue::Lit(*val), false),
 
-    Expr::StringLiteral { str } => (IRValue::Str(str.clone()), false),
+    Expr::StringLiteral { str, .. } => (IRValue::Str(str.clone()), false),
   }
 }
 
@@ -527,7 +1263,18 @@ fn transform_expr_and_assign_to(
   span: Span,
   assign_to: String,
 ) {
-  let (result, is_anon) = transform_expr(state, expr, span);
+  let (result, is_anon) = transform_expr(state, expr);
+  assign_value(state, result, is_anon, span, assign_to);
+}
+
+/// Assigns an already transformed value to a net, checking that the types match.
+fn assign_value(
+  state: &mut ModuleValidationState,
+  result: IRValue,
+  is_anon: bool,
+  span: Span,
+  assign_to: String,
+) {
   let ty = state.ir_objects[&assign_to].ty;
   match get_type(state, &result) {
     Ok(ty2) => {
@@ -548,6 +1295,7 @@ fn transform_expr_and_assign_to(
       dest: assign_to,
       op: "$passthrough".into(),
       args: vec![result],
+      span: state.span,
     });
   } else {
     // unwrap: if is_anon == true, it must be an anonymous net (which is a net)
@@ -562,28 +1310,184 @@ fn transform_expr_and_assign_to(
   }
 }
 
+/// Matches the arguments of a module instantiation to the ports of the module.
+/// Returns one argument per port, in port order, which is `None` for unconnected ports.
+fn match_module_args<'e>(
+  state: &mut ModuleValidationState,
+  module: &Module,
+  args: &'e ModuleArgs,
+  span: Span,
+) -> Vec<Option<&'e Expr>> {
+  let matched = match args {
+    ModuleArgs::Positional(args) => {
+      if args.len() > module.ports.len() {
+        state
+          .global
+          .errors
+          .push(Cerr::WrongNumberOfModuleArgs(module.ports.len()).with(span));
+      }
+      (0..module.ports.len()).map(|i| args.get(i)).collect::<Vec<_>>()
+    }
+    ModuleArgs::Named(args) => {
+      let mut matched = vec![None; module.ports.len()];
+      for (name, expr) in args {
+        match module.ports.iter().position(|port| &port.name == name) {
+          None => state
+            .global
+            .errors
+            .push(Cerr::UnknownPort(module.name.clone(), name.clone()).with(span)),
+          Some(i) if matched[i].is_some() => state
+            .global
+            .errors
+            .push(Cerr::DuplicatePortConnection(name.clone()).with(span)),
+          Some(i) => matched[i] = Some(expr),
+        }
+      }
+      matched
+    }
+  };
+  if matched.iter().any(Option::is_none) {
+    let ports = module.ports
+      .iter()
+      .zip(matched.iter())
+      .map(|(port, arg)| (port.name.clone(), arg.is_some()))
+      .collect();
+    state.global.errors.push(
+      Cerr::MissingPorts(PortDiff {
+        module: module.name.clone(),
+        ports,
+      })
+      .with(span),
+    );
+  }
+  matched
+}
+
+/// Checks that an expression is a bundle that can be connected to a bundle of type `dst`,
+/// which requires both to have the same members. Returns the names of the source members.
+fn bundle_source(state: &mut ModuleValidationState, expr: &Expr, dst: &Bundle) -> Option<Vec<String>> {
+  let span = expr.span();
+  let src_ty = match expr {
+    Expr::Identifier { name, .. } => match state.objects.get(name) {
+      None => {
+        state
+          .global
+          .errors
+          .push(Cerr::NotDeclared(name.clone()).with(span));
+        return None;
+      }
+      Some(ObjectInfo { bundle: Some(bundle), .. }) => {
+        let src = state.global.bundles.get(bundle).copied()?;
+        if src.members == dst.members {
+          return Some(
+            src.members
+              .iter()
+              .map(|member| format!("{}.{}", name, member.name))
+              .collect(),
+          );
+        }
+        src.name.clone()
+      }
+      Some(_) => state
+        .ir_objects
+        .get(name)
+        .map(|decl| format!("{:?}", decl.ty))
+        .unwrap_or_else(|| "Expression".into()),
+    },
+    _ => "Expression".into(),
+  };
+  state.global.errors.push(
+    Cerr::TypeErrorGeneric(TypeError {
+      src_ty,
+      dst_ty: dst.name.clone(),
+    })
+    .with(span),
+  );
+  None
+}
+
+/// Decodes an address into a mask over a memory array, which is 1 on the addressed slot's signal.
+/// The index table holds `i + 1` on slot `i`, so subtracting the address leaves 1 on exactly that slot.
+fn transform_array_mask(state: &mut ModuleValidationState, table: String, index: IRValue, span: Span) -> String {
+  expect_single(state, &index, span);
+  let offset = transform_single_binary_op(state, BinaryOp::Sub, (IRValue::Net(table), span), (index, span));
+  // unwrap: binary ops always output to a net
+  transform_single_binary_op(state, BinaryOp::Eq, (offset, span), (IRValue::Lit(1), span))
+    .into_net()
+    .unwrap()
+}
+
+/// Keeps only the slot of a memory array selected by a mask, on the slot's own signal.
+fn transform_array_filter(state: &mut ModuleValidationState, name: &str, mask: String) -> IRValue {
+  let filtered = state.create_anon_net(NetType::Mixed);
+  state.stmts.push(IRStmt {
+    dest: filtered.clone(),
+    op: "filter".into(),
+    args: vec![IRValue::Net(name.to_owned()), IRValue::Net(mask)],
+    span: state.span,
+  });
+  IRValue::Net(filtered)
+}
+
+/// Reads the slot of a memory array selected by a mask.
+fn transform_array_read(state: &mut ModuleValidationState, name: &str, mask: String) -> IRValue {
+  let filtered = transform_array_filter(state, name, mask);
+  let value = state.create_anon_net(NetType::Single);
+  state.stmts.push(IRStmt {
+    dest: value.clone(),
+    op: "sum".into(),
+    args: vec![filtered],
+    span: state.span,
+  });
+  IRValue::Net(value)
+}
+
+/// Looks up an entry of a ROM. The lookup itself is synthesized by `$rom_lookup`.
+fn transform_rom_lookup(state: &mut ModuleValidationState, rom: &RomInfo, index: IRValue, span: Span) -> IRValue {
+  expect_single(state, &index, span);
+  let ty = if rom.value.is_some() {
+    NetType::Single
+  } else {
+    NetType::Mixed
+  };
+  let mut args = vec![index, IRValue::Str(rom.select.clone())];
+  args.extend(rom.value.iter().map(|v| IRValue::Str(v.clone())));
+  args.extend(rom.entries.iter().map(|v| IRValue::Net(v.clone())));
+  let anon = state.create_anon_net(ty);
+  state.stmts.push(IRStmt {
+    dest: anon.clone(),
+    op: "$rom_lookup".into(),
+    args,
+    span: state.span,
+  });
+  IRValue::Net(anon)
+}
+
+/// Applies a binary op to two operands, each with the span of the expression it came from.
 fn transform_single_binary_op(
   state: &mut ModuleValidationState,
   op: BinaryOp,
-  a: IRValue,
-  b: IRValue,
-  span: Span,
+  (a, a_span): (IRValue, Span),
+  (b, b_span): (IRValue, Span),
 ) -> IRValue {
+  if op.is_logical() {
+    return transform_logical_op(state, op, (a, a_span), (b, b_span));
+  }
   let ty1 = get_type(state, &a)
     .map_err(|v| {
-      state.global.errors.push(v.with(span));
+      state.global.errors.push(v.with(a_span));
     })
     .unwrap_or(NetType::Single);
   let ty2 = get_type(state, &b)
     .map_err(|v| {
-      state.global.errors.push(v.with(span));
+      state.global.errors.push(v.with(b_span));
     })
     .unwrap_or(NetType::Single);
   if ty1 == NetType::Mixed && ty2 == NetType::Mixed && op != BinaryOp::Add && op != BinaryOp::Sub {
     state
       .global
       .errors
-      .push(Cerr::InvalidOpOnMixedNets(op).with(span))
+      .push(Cerr::InvalidOpOnMixedNets(op).with(a_span.union(b_span)))
   }
   let res_ty = if ty1 == NetType::Mixed || ty2 == NetType::Mixed {
     NetType::Mixed
@@ -596,10 +1500,103 @@ fn transform_single_binary_op(
     dest: anon.clone(),
     op: func,
     args: vec![a, b],
+    span: state.span,
+  });
+  IRValue::Net(anon)
+}
+
+/// Logical ops are lowered to comparisons. Both operands are normalized to 0 or 1
+/// onto a shared net, which is then compared against a threshold.
+fn transform_logical_op(
+  state: &mut ModuleValidationState,
+  op: BinaryOp,
+  a: (IRValue, Span),
+  b: (IRValue, Span),
+) -> IRValue {
+  let sum = state.create_anon_net(NetType::Single);
+  for (v, span) in [a, b] {
+    match get_type(state, &v) {
+      Ok(NetType::Mixed) => state
+        .global
+        .errors
+        .push(Cerr::LogicalOpOnMixedNet(op).with(span)),
+      Ok(NetType::Single) => normalize_into(state, v, &sum),
+      Err(err) => state.global.errors.push(err.with(span)),
+    }
+  }
+  let (cmp, threshold) = if op == BinaryOp::LAnd {
+    (BinaryOp::Eq, 2)
+  } else {
+    (BinaryOp::Gt, 0)
+  };
+  let anon = state.create_anon_net(NetType::Single);
+  state.stmts.push(IRStmt {
+    dest: anon.clone(),
+    op: binary_op_to_func_name(cmp).to_owned(),
+    args: vec![IRValue::Net(sum), IRValue::Lit(threshold)],
+    span: state.span,
   });
   IRValue::Net(anon)
 }
 
+/// Writes 1 to `dest` if the value is nonzero, or 0 otherwise.
+/// If the value is the result of a comparison, it's already normalized, so the
+/// comparison is retargeted to write to `dest` directly.
+fn normalize_into(state: &mut ModuleValidationState, v: IRValue, dest: &str) {
+  match v {
+    IRValue::Net(net) => {
+      let comparison = if net.starts_with("$anon_") {
+        state.stmts.iter_mut().rev().find(|v| {
+          v.dest == net
+            && func_name_to_binary_op(&v.op)
+              .map(|op| op.is_comparison())
+              .unwrap_or(false)
+        })
+      } else {
+        None
+      };
+      if let Some(comparison) = comparison {
+        // anonymous nets are only read once, so this one is now unused
+        comparison.dest = dest.to_owned();
+        state.ir_objects.remove(&net);
+      } else {
+        state.stmts.push(IRStmt {
+          dest: dest.to_owned(),
+          op: binary_op_to_func_name(BinaryOp::Ne).to_owned(),
+          args: vec![IRValue::Net(net), IRValue::Lit(0)],
+          span: state.span,
+        });
+      }
+    }
+    IRValue::Lit(val) => {
+      if val != 0 {
+        state.stmts.push(IRStmt {
+          dest: dest.to_owned(),
+          op: "$passthrough".into(),
+          args: vec![IRValue::Lit(1)],
+          span: state.span,
+        });
+      }
+    }
+    IRValue::Str(_) => {}
+  }
+}
+
+/// Reports an error if the value is not a single net or literal.
+fn expect_single(state: &mut ModuleValidationState, irv: &IRValue, span: Span) {
+  match get_type(state, irv) {
+    Ok(NetType::Single) => {}
+    Ok(NetType::Mixed) => state.global.errors.push(
+      Cerr::TypeErrorGeneric(TypeError {
+        src_ty: "Mixed".into(),
+        dst_ty: "Single".into(),
+      })
+      .with(span),
+    ),
+    Err(err) => state.global.errors.push(err.with(span)),
+  }
+}
+
 fn get_type(state: &mut ModuleValidationState, irv: &IRValue) -> Result<NetType, Cerr> {
   Ok(match irv {
     IRValue::Net(name) => state
@@ -1,153 +1,330 @@
 use std::borrow::Cow;
-use crate::synth::combinator::{Signal, SignalType};
+use crate::synth::combinator::{Quality, Signal, SignalType};
 
 const fn cow(s: &'static str) -> Cow<'static, str> {
   Cow::Borrowed(s)
 }
 
+const fn item(s: &'static str) -> Signal {
+  Signal {
+    ty: SignalType::Item,
+    name: cow(s),
+    quality: Quality::Normal,
+  }
+}
+
 pub const VIRTUAL_SIGNALS: [Signal; 36] = [
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-A"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-B"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-C"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-D"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-E"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-F"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-G"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-H"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-I"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-J"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-K"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-L"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-M"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-N"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-O"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-P"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-Q"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-R"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-S"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-T"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-U"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-V"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-W"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-X"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-Y"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-Z"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-0"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-1"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-2"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-3"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-4"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-5"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-6"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-7"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-8"),
+    quality: Quality::Normal,
   },
   Signal {
     ty: SignalType::Virtual,
     name: cow("signal-9"),
+    quality: Quality::Normal,
   },
-];
\ No newline at end of file
+];
+
+/// Signals that hold the slots of memory arrays, one per slot.
+/// These are item signals so that they never collide with the virtual signals given to single nets.
+pub const SLOT_SIGNALS: [Signal; 128] = [
+  item("wooden-chest"),
+  item("iron-chest"),
+  item("steel-chest"),
+  item("storage-tank"),
+  item("transport-belt"),
+  item("fast-transport-belt"),
+  item("express-transport-belt"),
+  item("underground-belt"),
+  item("fast-underground-belt"),
+  item("express-underground-belt"),
+  item("splitter"),
+  item("fast-splitter"),
+  item("express-splitter"),
+  item("burner-inserter"),
+  item("inserter"),
+  item("long-handed-inserter"),
+  item("fast-inserter"),
+  item("filter-inserter"),
+  item("stack-inserter"),
+  item("stack-filter-inserter"),
+  item("small-electric-pole"),
+  item("medium-electric-pole"),
+  item("big-electric-pole"),
+  item("substation"),
+  item("pipe"),
+  item("pipe-to-ground"),
+  item("pump"),
+  item("rail"),
+  item("train-stop"),
+  item("rail-signal"),
+  item("rail-chain-signal"),
+  item("locomotive"),
+  item("cargo-wagon"),
+  item("fluid-wagon"),
+  item("artillery-wagon"),
+  item("car"),
+  item("tank"),
+  item("spidertron"),
+  item("logistic-robot"),
+  item("construction-robot"),
+  item("logistic-chest-active-provider"),
+  item("logistic-chest-passive-provider"),
+  item("logistic-chest-storage"),
+  item("logistic-chest-buffer"),
+  item("logistic-chest-requester"),
+  item("roboport"),
+  item("small-lamp"),
+  item("red-wire"),
+  item("green-wire"),
+  item("arithmetic-combinator"),
+  item("decider-combinator"),
+  item("constant-combinator"),
+  item("power-switch"),
+  item("programmable-speaker"),
+  item("stone-brick"),
+  item("concrete"),
+  item("hazard-concrete"),
+  item("refined-concrete"),
+  item("refined-hazard-concrete"),
+  item("landfill"),
+  item("cliff-explosives"),
+  item("repair-pack"),
+  item("boiler"),
+  item("steam-engine"),
+  item("solar-panel"),
+  item("accumulator"),
+  item("nuclear-reactor"),
+  item("heat-pipe"),
+  item("heat-exchanger"),
+  item("steam-turbine"),
+  item("burner-mining-drill"),
+  item("electric-mining-drill"),
+  item("offshore-pump"),
+  item("pumpjack"),
+  item("stone-furnace"),
+  item("steel-furnace"),
+  item("electric-furnace"),
+  item("assembling-machine-1"),
+  item("assembling-machine-2"),
+  item("assembling-machine-3"),
+  item("oil-refinery"),
+  item("chemical-plant"),
+  item("centrifuge"),
+  item("lab"),
+  item("beacon"),
+  item("speed-module"),
+  item("speed-module-2"),
+  item("speed-module-3"),
+  item("effectivity-module"),
+  item("effectivity-module-2"),
+  item("effectivity-module-3"),
+  item("productivity-module"),
+  item("productivity-module-2"),
+  item("productivity-module-3"),
+  item("wood"),
+  item("coal"),
+  item("stone"),
+  item("iron-ore"),
+  item("copper-ore"),
+  item("uranium-ore"),
+  item("raw-fish"),
+  item("iron-plate"),
+  item("copper-plate"),
+  item("solid-fuel"),
+  item("steel-plate"),
+  item("plastic-bar"),
+  item("sulfur"),
+  item("battery"),
+  item("explosives"),
+  item("crude-oil-barrel"),
+  item("heavy-oil-barrel"),
+  item("light-oil-barrel"),
+  item("lubricant-barrel"),
+  item("petroleum-gas-barrel"),
+  item("sulfuric-acid-barrel"),
+  item("water-barrel"),
+  item("copper-cable"),
+  item("iron-stick"),
+  item("iron-gear-wheel"),
+  item("empty-barrel"),
+  item("electronic-circuit"),
+  item("advanced-circuit"),
+  item("processing-unit"),
+  item("engine-unit"),
+  item("electric-engine-unit"),
+  item("flying-robot-frame"),
+  item("rocket-control-unit"),
+  item("low-density-structure"),
+];
@@ -0,0 +1,94 @@
+use crate::cli::{fmt, run, Args, ColorChoice, ErrorFormat, FmtArgs};
+use crate::synth::lint::{LintLevel, LintLevels};
+use crate::synth::synth::SingleMixedStrategy;
+
+fn args(args: &[&str]) -> Result<Args, String> {
+  Args::parse(args.iter().map(|v| v.to_string()))
+}
+
+#[test]
+pub fn cli_parse_args() {
+  let expected = Args {
+    input: "main.fhdl".into(),
+    search_path: vec!["lib".into(), "vendor".into()],
+    lints: Default::default(),
+    main: Some("top".into()),
+    single_mixed_strategy: SingleMixedStrategy::Dirty,
+    color: ColorChoice::Never,
+    error_format: ErrorFormat::Json,
+  };
+  let parsed = args(&[
+    "-I", "lib", "main.fhdl", "--search-path", "vendor", "-m", "top", "--single-mixed", "dirty", "--color", "never",
+    "--error-format", "json",
+  ]);
+  assert_eq!(parsed, Ok(expected));
+  assert!(args(&[]).is_err());
+  assert!(args(&["main.fhdl", "-I"]).is_err());
+  assert!(args(&["main.fhdl", "--main"]).is_err());
+  assert!(args(&["main.fhdl", "other.fhdl"]).is_err());
+  assert!(args(&["--bogus", "main.fhdl"]).is_err());
+  assert!(args(&["--color", "sometimes", "main.fhdl"]).is_err());
+  assert!(args(&["--single-mixed", "messy", "main.fhdl"]).is_err());
+  assert!(args(&["main.fhdl", "--error-format"]).is_err());
+}
+
+#[test]
+pub fn cli_parse_lint_levels() {
+  let mut lints = LintLevels::default();
+  lints.set("warnings", LintLevel::Deny).unwrap();
+  lints.set("unused-wire", LintLevel::Allow).unwrap();
+  let parsed = args(&["-D", "warnings", "main.fhdl", "--allow", "unused-wire"]).unwrap();
+  assert_eq!(parsed.lints, lints);
+  assert!(args(&["-A", "bogus-lint", "main.fhdl"]).is_err());
+  assert!(args(&["main.fhdl", "--deny"]).is_err());
+}
+
+fn fmt_args(args: &[&str]) -> Result<FmtArgs, String> {
+  FmtArgs::parse(args.iter().map(|v| v.to_string()))
+}
+
+#[test]
+pub fn cli_parse_fmt_args() {
+  let expected = FmtArgs {
+    files: vec!["a.fhdl".into(), "b.fhdl".into()],
+    check: true,
+  };
+  assert_eq!(fmt_args(&["a.fhdl", "--check", "b.fhdl"]), Ok(expected));
+  assert!(fmt_args(&[]).is_err());
+  assert!(fmt_args(&["--check"]).is_err());
+  assert!(fmt_args(&["-I", "a.fhdl"]).is_err());
+}
+
+#[test]
+pub fn cli_fmt() {
+  let dir = std::env::temp_dir().join(format!("fhdl_fmt_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let path = dir.join("main.fhdl");
+  let src = "version 2;\nmodule m(in single a, out single b) { set b=a; }";
+  std::fs::write(&path, src).unwrap();
+  let args = |check| FmtArgs { files: vec![path.clone()], check };
+
+  // checking leaves the file alone
+  assert_eq!(fmt(&args(true)), 1);
+  assert_eq!(std::fs::read_to_string(&path).unwrap(), src);
+  assert_eq!(fmt(&args(false)), 0);
+  let formatted = std::fs::read_to_string(&path).unwrap();
+  assert_eq!(formatted, "version 2;\n\nmodule m(in single a, out single b) {\n  set b = a;\n}\n");
+  assert_eq!(fmt(&args(true)), 0);
+
+  std::fs::write(&path, "version 2; module m( {}").unwrap();
+  assert_eq!(fmt(&args(false)), 1);
+  assert_eq!(fmt(&FmtArgs { files: vec![dir.join("missing.fhdl")], check: false }), 1);
+}
+
+#[test]
+pub fn cli_single_mixed_strategy() {
+  let dir = std::env::temp_dir().join(format!("fhdl_dirty_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let path = dir.join("main.fhdl");
+  std::fs::write(&path, "version 2;\nmodule m(in mixed a, in single b, out mixed c) { set c = a + b; }").unwrap();
+  let args = |strategy| args(&["-D", "dirty-op", "--single-mixed", strategy, path.to_str().unwrap()]).unwrap();
+  // denying `dirty-op` only fails the build when it's synthesized dirty
+  assert_eq!(run(&args("clean")), 0);
+  assert_eq!(run(&args("dirty")), 1);
+}
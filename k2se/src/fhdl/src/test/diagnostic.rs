@@ -0,0 +1,120 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::err::{Cerr, Lint, Warning};
+use crate::parse::ast::Program;
+use crate::parse::span::{Pos, Span};
+use crate::parse::tokenizer::tokenize;
+use crate::parse::tokenstream::TokenStream;
+use crate::synth::builtins::collect_builtins;
+use crate::synth::transform::transform_program;
+use crate::util::ResultExt;
+
+fn span(start: (u32, u32), end: (u32, u32)) -> Span {
+  Span {
+    start: Pos::new(start.0, start.1),
+    end: Pos::new(end.0, end.1),
+  }
+}
+
+#[test]
+pub fn diagnostic_render_last_line() {
+  // the span is on the last line, which has no trailing newline
+  let src = "version 2;\nmodule m() { wire single w = x; }";
+  let err = Cerr::NotDeclared("x".into()).with(span((2, 29), (2, 29)));
+  let expected = "\
+error[E0201]: 'x' not declared
+ --> m.fhdl:2:30
+  |
+2 | module m() { wire single w = x; }
+  |                              ^
+";
+  assert_eq!(err.format_err("m.fhdl", src), expected);
+}
+
+#[test]
+pub fn diagnostic_render_multi_line() {
+  let src = "version 2;\nmodule m() {\n  wire single w = (1\n    + 2);\n}";
+  let err = Cerr::UnexpectedString.with(span((3, 18), (4, 8)));
+  let expected = "\
+error[E0217]: Unexpected string literal
+ --> m.fhdl:3:19
+  |
+3 |   wire single w = (1
+  |                   ^^
+4 |     + 2);
+  |     ^^^^^
+";
+  assert_eq!(err.format_err("m.fhdl", src), expected);
+
+  // long spans leave out the lines in between, and lines after the first skip their indentation
+  let src = "a\n  b\n  c\n  d\n  e\nf";
+  let err = Cerr::InvalidExpr.with(span((1, 0), (6, 0)));
+  let expected = "\
+error[E0104]: Invalid expression
+ --> m.fhdl:1:1
+  |
+1 | a
+  | ^
+2 |   b
+  |   ^
+...
+6 | f
+  | ^
+";
+  assert_eq!(err.format_err("m.fhdl", src), expected);
+}
+
+#[test]
+pub fn diagnostic_render_labels_and_notes() {
+  let src = "version 2;
+module m(in single a) {
+  wire single w = a;
+  wire single w = a;
+}";
+  let tokens = tokenize(src.chars()).collect::<Result<Vec<_>, _>>().pretty_unwrap();
+  let program = Program::parse(&TokenStream::from_tokens(tokens).begin()).pretty_unwrap();
+  let errs = transform_program(&program, &collect_builtins()).1;
+  let expected = "\
+error[E0202]: Multiple declarations for w
+ --> m.fhdl:4:3
+  |
+3 |   wire single w = a;
+  |   ------------------ previously declared here
+4 |   wire single w = a;
+  |   ^^^^^^^^^^^^^^^^^^
+";
+  assert_eq!(errs[0].format_err("m.fhdl", src), expected);
+  let expected_help = "  = help: use '+=' to add several values to a wire, or write them in different branches of a trigger\n";
+  assert!(errs[1].format_err("m.fhdl", src).ends_with(expected_help));
+
+  // warnings are coloured differently, and without a span there's no source to show
+  let warning = Warning {
+    span: None,
+    file: 0,
+    lint: Lint::ConstantTrigger,
+  };
+  let diag = Diagnostic::warning(&warning);
+  assert_eq!(diag.severity, Severity::Warning);
+  assert_eq!(diag.code, "constant-trigger");
+  let rendered = diag.render("m.fhdl", src, true);
+  assert!(rendered.starts_with("\x1b[1;33mwarning[constant-trigger]\x1b[0m"), "{}", rendered);
+  assert!(!diag.render("m.fhdl", src, false).contains('\x1b'));
+  assert!(!rendered.contains('|'), "{}", rendered);
+}
+
+#[test]
+pub fn diagnostic_json() {
+  let err = Cerr::MultipleDeclarations("w".into(), span((3, 2), (3, 19))).with(span((4, 2), (4, 19)));
+  let json = Diagnostic::error(&err).to_json("m.fhdl");
+  assert!(!json.contains('\n'));
+  let value = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+  assert_eq!(value["severity"], "error");
+  assert_eq!(value["code"], "E0202");
+  assert_eq!(value["message"], "Multiple declarations for w");
+  assert_eq!(value["file"], "m.fhdl");
+  assert_eq!(value["labels"][0]["primary"], true);
+  assert_eq!(value["labels"][0]["span"]["start"]["line"], 4);
+  assert_eq!(value["labels"][0]["span"]["end"]["col"], 19);
+  assert_eq!(value["labels"][1]["primary"], false);
+  assert_eq!(value["labels"][1]["message"], "previously declared here");
+  assert_eq!(value["notes"], serde_json::json!([]));
+}
@@ -2,10 +2,10 @@ use crate::parse::ast::Program;
 use crate::parse::tokenizer::tokenize;
 use crate::parse::tokenstream::TokenStream;
 use crate::synth::builtins::collect_builtins;
-use crate::synth::combinator::{Signal, SignalType};
+use crate::synth::combinator::{Quality, Signal, SignalType};
 use crate::synth::netlist::Netlist;
 use crate::synth::synth::{synthesize, SynthSettings};
-use crate::synth::transform::transform_modules;
+use crate::synth::transform::transform_program;
 use crate::util::ResultExt;
 
 fn driver(filename: &str, src: &str, synth_settings: &SynthSettings) -> Netlist {
@@ -14,16 +14,19 @@ fn driver(filename: &str, src: &str, synth_settings: &SynthSettings) -> Netlist
     .pretty_unwrap();
   let token_stream = TokenStream::from_tokens(tokens);
   let program = Program::parse(&token_stream.begin()).pretty_unwrap();
-  let transform = transform_modules(&program.modules, &collect_builtins());
-  if transform.1.is_empty() {
-    synthesize(synth_settings, &transform.0, &collect_builtins()).pretty_unwrap()
-  } else {
-    let splitted = src.lines().collect::<Vec<_>>();
+  let transform = transform_program(&program, &collect_builtins());
+  if !transform.1.is_empty() {
     for v in transform.1 {
-      eprintln!("{}", v.format_err(filename, &splitted).pretty_unwrap());
+      eprintln!("{}", v.format_err(filename, src));
     }
     panic!("Transform failed");
   }
+  synthesize(synth_settings, &transform.0, &collect_builtins()).unwrap_or_else(|errs| {
+    for v in errs {
+      eprintln!("{}", v.format_err(filename, src));
+    }
+    panic!("Synthesis failed");
+  })
 }
 
 #[test]
@@ -41,12 +44,16 @@ pub fn transform_counter() {
         Signal {
           ty: SignalType::Virtual,
           name: "signal-R".into(),
+          quality: Quality::Normal,
         },
         Signal {
           ty: SignalType::Virtual,
           name: "signal-C".into(),
+          quality: Quality::Normal,
         }
       ],
+      single_mixed_strategy: Default::default(),
+      balance_latency: false,
     }
   );
   eprintln!("{:#?}", modules);
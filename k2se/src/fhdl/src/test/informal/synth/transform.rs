@@ -3,7 +3,7 @@ use crate::parse::tokenizer::tokenize;
 use crate::parse::tokenstream::TokenStream;
 use crate::synth::builtins::collect_builtins;
 use crate::synth::ir::IRModule;
-use crate::synth::transform::transform_modules;
+use crate::synth::transform::transform_program;
 use crate::util::ResultExt;
 
 fn driver(filename: &str, src: &str) -> Vec<IRModule> {
@@ -12,13 +12,12 @@ fn driver(filename: &str, src: &str) -> Vec<IRModule> {
     .pretty_unwrap();
   let token_stream = TokenStream::from_tokens(tokens);
   let program = Program::parse(&token_stream.begin()).pretty_unwrap();
-  let transform = transform_modules(&program.modules, &collect_builtins());
+  let transform = transform_program(&program, &collect_builtins());
   if transform.1.is_empty() {
     transform.0
   } else {
-    let splitted = src.lines().collect::<Vec<_>>();
     for v in transform.1 {
-      eprintln!("{}", v.format_err(filename, &splitted).pretty_unwrap());
+      eprintln!("{}", v.format_err(filename, src));
     }
     panic!("Transform failed");
   }
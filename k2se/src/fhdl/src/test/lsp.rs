@@ -0,0 +1,239 @@
+use crate::lsp::rpc::{read_message, write_message};
+use crate::lsp::{path_to_uri, run, uri_to_path};
+use serde_json::{json, Value};
+use std::io::Cursor;
+use std::path::Path;
+
+/// Runs the server on a script of messages from a client, and returns everything it sent back
+/// along with its exit code.
+fn util_lsp_session(script: &[Value]) -> (Vec<Value>, i32) {
+  let mut input = vec![];
+  for message in script {
+    write_message(&mut input, message).unwrap();
+  }
+  let mut output = vec![];
+  let code = run(Cursor::new(input), &mut output);
+  let mut output = Cursor::new(output);
+  let mut messages = vec![];
+  while let Some(message) = read_message(&mut output).unwrap() {
+    messages.push(message);
+  }
+  (messages, code)
+}
+
+fn request(id: i32, method: &str, params: Value) -> Value {
+  json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+  json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn at(uri: &str, line: u32, character: u32) -> Value {
+  json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+}
+
+fn result(messages: &[Value], id: i32) -> &Value {
+  let response = messages.iter().find(|v| v["id"] == id).unwrap();
+  &response["result"]
+}
+
+#[test]
+pub fn lsp_session() {
+  let uri = "file:///tmp/fhdl-lsp-test/main.fhdl";
+  let src = "version 2;
+module main(in single a, out single b) {
+  wire single sum = a + 1;
+  wire single unused = a;
+  inst double(sum, b);
+}
+module double(in single x, out single y) {
+  set y = x * 2;
+}";
+  let changed = src.replace("a + 1", "a + nope");
+  let script = [
+    request(1, "initialize", json!({ "capabilities": {} })),
+    notification("initialized", json!({})),
+    notification("textDocument/didOpen", json!({
+      "textDocument": { "uri": uri, "languageId": "fhdl", "version": 1, "text": src },
+    })),
+    // the declaration of `sum`, and the module `double`
+    request(2, "textDocument/definition", at(uri, 4, 15)),
+    request(3, "textDocument/definition", at(uri, 4, 8)),
+    request(4, "textDocument/hover", at(uri, 2, 15)),
+    request(5, "textDocument/hover", at(uri, 4, 8)),
+    request(6, "textDocument/completion", at(uri, 2, 0)),
+    request(7, "textDocument/rename", json!({
+      "textDocument": { "uri": uri }, "position": { "line": 2, "character": 14 }, "newName": "total",
+    })),
+    request(8, "textDocument/rename", json!({
+      "textDocument": { "uri": uri }, "position": { "line": 1, "character": 22 }, "newName": "c",
+    })),
+    notification("textDocument/didChange", json!({
+      "textDocument": { "uri": uri, "version": 2 }, "contentChanges": [{ "text": changed }],
+    })),
+    request(9, "textDocument/bogus", json!({})),
+    request(10, "shutdown", Value::Null),
+    notification("exit", Value::Null),
+  ];
+  let (messages, code) = util_lsp_session(&script);
+  assert_eq!(code, 0);
+
+  let capabilities = &result(&messages, 1)["capabilities"];
+  assert_eq!(capabilities["textDocumentSync"], 1);
+  assert_eq!(capabilities["renameProvider"], true);
+
+  let diagnostics = messages
+    .iter()
+    .filter(|v| v["method"] == "textDocument/publishDiagnostics")
+    .map(|v| &v["params"]["diagnostics"])
+    .collect::<Vec<_>>();
+  assert_eq!(diagnostics.len(), 2);
+  // a warning when opened, and an error once the document changed
+  assert_eq!(diagnostics[0][0]["severity"], 2);
+  assert_eq!(diagnostics[0][0]["code"], "unused-wire");
+  assert_eq!(diagnostics[0][0]["range"]["start"], json!({ "line": 3, "character": 2 }));
+  assert_eq!(diagnostics[1][0]["severity"], 1);
+  assert_eq!(diagnostics[1][0]["code"], "E0201");
+  assert_eq!(diagnostics[1][0]["range"], json!({
+    "start": { "line": 2, "character": 24 }, "end": { "line": 2, "character": 28 },
+  }));
+
+  let definition = result(&messages, 2);
+  assert_eq!(definition["uri"], uri);
+  assert_eq!(definition["range"]["start"], json!({ "line": 2, "character": 2 }));
+  assert_eq!(result(&messages, 3)["range"]["start"], json!({ "line": 6, "character": 0 }));
+
+  let hover = result(&messages, 4)["contents"]["value"].as_str().unwrap();
+  assert!(hover.starts_with("```fhdl\nwire single sum\n```"), "{}", hover);
+  assert!(hover.contains("signal: `virtual=signal-"), "{}", hover);
+  let hover = result(&messages, 5)["contents"]["value"].as_str().unwrap();
+  assert_eq!(hover, "```fhdl\nmodule double(in single x, out single y)\n```");
+
+  let completions = result(&messages, 6).as_array().unwrap();
+  let labels = completions.iter().map(|v| v["label"].as_str().unwrap()).collect::<Vec<_>>();
+  assert!(labels.contains(&"delay"), "{:?}", labels);
+  assert!(labels.contains(&"double"), "{:?}", labels);
+  assert!(!labels.iter().any(|v| v.starts_with('$')), "{:?}", labels);
+
+  let edits = result(&messages, 7)["changes"][uri].as_array().unwrap();
+  let starts = edits.iter().map(|v| v["range"]["start"].clone()).collect::<Vec<_>>();
+  assert_eq!(starts, vec![json!({ "line": 2, "character": 14 }), json!({ "line": 4, "character": 14 })]);
+  assert!(edits.iter().all(|v| v["newText"] == "total"));
+  // ports can't be renamed
+  let response = messages.iter().find(|v| v["id"] == 8).unwrap();
+  assert_eq!(response["error"]["code"], -32803);
+
+  let response = messages.iter().find(|v| v["id"] == 9).unwrap();
+  assert_eq!(response["error"]["code"], -32601);
+}
+
+#[test]
+pub fn lsp_rename() {
+  let uri = "file:///tmp/fhdl-lsp-test/rename.fhdl";
+  let src = "version 2;
+module main(in single x, out single y) {
+  wire single reset = x * 2;
+  mem single acc reset reset;
+  wire single a = acc + reset;
+  wire single q;
+  inst sub(a: a, b: q);
+  wire single delay = delay(q, 1);
+  set acc += 1;
+  set y = q + delay;
+}
+module sub(in single a, out single b) {
+  set b = a;
+}";
+  let rename = |id: i32, line: u32, character: u32| {
+    request(id, "textDocument/rename", json!({
+      "textDocument": { "uri": uri }, "position": { "line": line, "character": character }, "newName": "renamed",
+    }))
+  };
+  let (messages, _) = util_lsp_session(&[
+    request(1, "initialize", json!({ "capabilities": {} })),
+    notification("textDocument/didOpen", json!({
+      "textDocument": { "uri": uri, "languageId": "fhdl", "version": 1, "text": src },
+    })),
+    rename(2, 4, 14),
+    rename(3, 2, 14),
+    rename(4, 7, 14),
+    // the port of `sub`, not the wire
+    rename(5, 6, 11),
+    request(6, "shutdown", Value::Null),
+    notification("exit", Value::Null),
+  ]);
+  let starts = |id: i32| {
+    result(&messages, id)["changes"][uri]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|v| (v["range"]["start"]["line"].as_u64().unwrap(), v["range"]["start"]["character"].as_u64().unwrap()))
+      .collect::<Vec<_>>()
+  };
+  // named ports keep their label
+  assert_eq!(starts(2), [(4, 14), (6, 14)]);
+  // the keyword stays, and so does the function
+  assert_eq!(starts(3), [(2, 14), (3, 23), (4, 24)]);
+  assert_eq!(starts(4), [(7, 14), (9, 14)]);
+  let response = messages.iter().find(|v| v["id"] == 5).unwrap();
+  assert_eq!(response["error"]["code"], -32803);
+}
+
+#[test]
+pub fn lsp_exit_without_shutdown() {
+  let (messages, code) = util_lsp_session(&[notification("exit", Value::Null)]);
+  assert!(messages.is_empty());
+  assert_eq!(code, 1);
+}
+
+#[test]
+pub fn lsp_uri_paths() {
+  let path = Path::new("/tmp/my project/a%b.fhdl");
+  let uri = path_to_uri(path);
+  assert_eq!(uri, "file:///tmp/my%20project/a%25b.fhdl");
+  assert_eq!(uri_to_path(&uri), path);
+  // invalid escapes are kept as they are
+  assert_eq!(uri_to_path("file:///tmp/%zz%4/%+1%41"), Path::new("/tmp/%zz%4/%+1A"));
+  assert_eq!(uri_to_path("file:///tmp/100%"), Path::new("/tmp/100%"));
+}
+
+#[test]
+pub fn lsp_position_encoding() {
+  let uri = "file:///tmp/fhdl-lsp-test/encoding.fhdl";
+  // `é` is one UTF-16 code unit and `𝔸` is two, so columns after them are 3 more in UTF-16
+  let src = "version 2;
+module main(in mixed m, in single a, out single b) {
+  wire single x = extract(m, \"item=é𝔸𝔸𝔸\") + a + nope;
+  set b = x;
+}";
+  let session = |encodings: Value, col: u32| {
+    let (messages, _) = util_lsp_session(&[
+      request(1, "initialize", json!({ "capabilities": { "general": { "positionEncodings": encodings } } })),
+      notification("textDocument/didOpen", json!({
+        "textDocument": { "uri": uri, "languageId": "fhdl", "version": 1, "text": src },
+      })),
+      // hovers over `a`
+      request(2, "textDocument/hover", at(uri, 2, col)),
+      request(3, "shutdown", Value::Null),
+      notification("exit", Value::Null),
+    ]);
+    let encoding = result(&messages, 1)["capabilities"]["positionEncoding"].clone();
+    let diagnostics = messages.iter().find(|v| v["method"] == "textDocument/publishDiagnostics").unwrap();
+    let range = diagnostics["params"]["diagnostics"][0]["range"].clone();
+    let hover = result(&messages, 2)["contents"]["value"].as_str().unwrap_or_default().to_owned();
+    (encoding, range, hover)
+  };
+  let range = |start, end| json!({ "start": { "line": 2, "character": start }, "end": { "line": 2, "character": end } });
+
+  let (encoding, nope, hover) = session(json!(["utf-16", "utf-32"]), 44);
+  assert_eq!(encoding, "utf-32");
+  assert_eq!(nope, range(48, 52));
+  assert!(hover.contains("in single a"), "{}", hover);
+  for encodings in [json!(["utf-16"]), Value::Null] {
+    let (encoding, nope, hover) = session(encodings, 47);
+    assert_eq!(encoding, "utf-16");
+    assert_eq!(nope, range(51, 55));
+    assert!(hover.contains("in single a"), "{}", hover);
+  }
+}
@@ -1,4 +1,7 @@
+mod cli;
+mod diagnostic;
 mod informal;
+mod lsp;
 mod parse;
 mod synth;
 mod util;
@@ -1,24 +1,23 @@
-use crate::err::CerrSpan;
-use crate::parse::ast::{Expr, Module, NetType, PortClass, PortDecl, Stmt, TriggerKind};
+use crate::err::{Cerr, CerrSpan};
+use crate::parse::ast::{Bundle, BundleMember, Expr, Module, ModuleArgs, NetType, PortClass, PortDecl, Program, RomData, RomEntry, Stmt, TriggerKind};
 use crate::parse::span::{Pos, Span};
 use crate::parse::tokenizer::{tokenize, BinaryOp};
 use crate::parse::tokenstream::{Cursor, TokenStream};
 use std::fmt::Debug;
 
 fn util_test_parser<T: Debug>(s: &str, parser: impl FnOnce(&Cursor) -> Result<T, CerrSpan>) -> T {
-  let spl = s.split("\n").collect::<Vec<_>>();
   let tokens = TokenStream::from_tokens(
     tokenize(s.chars())
       .collect::<Result<Vec<_>, _>>()
       .map_err(|v| {
-        eprintln!("{}", v.format_err("<test input>", &spl).unwrap());
+        eprintln!("{}", v.format_err("<test input>", s));
       })
       .unwrap(),
   );
   let cursor = tokens.begin();
   let parsed = parser(&cursor)
     .map_err(|v| {
-      eprintln!("{}", v.format_err("<test input>", &spl).unwrap());
+      eprintln!("{}", v.format_err("<test input>", s));
     })
     .unwrap();
   parsed
@@ -28,12 +27,11 @@ fn util_test_parser_err<T: Debug>(
   s: &str,
   parser: impl FnOnce(&Cursor) -> Result<T, CerrSpan>,
 ) -> CerrSpan {
-  let spl = s.split("\n").collect::<Vec<_>>();
   let tokens = TokenStream::from_tokens(
     tokenize(s.chars())
       .collect::<Result<Vec<_>, _>>()
       .map_err(|v| {
-        eprintln!("{}", v.format_err("<test input>", &spl).unwrap());
+        eprintln!("{}", v.format_err("<test input>", s));
       })
       .unwrap(),
   );
@@ -42,11 +40,94 @@ fn util_test_parser_err<T: Debug>(
   parsed
 }
 
+/// Clears the spans of an expression, so that it can be compared with an expected
+/// expression that only has default spans.
+fn util_strip_expr(expr: Expr) -> Expr {
+  let span = Span::default();
+  match expr {
+    Expr::Identifier { name, .. } => Expr::Identifier { name, span },
+    Expr::Literal { val, .. } => Expr::Literal { val, span },
+    Expr::StringLiteral { str, .. } => Expr::StringLiteral { str, span },
+    Expr::FnCall { func, args, .. } => Expr::FnCall {
+      func,
+      args: args.into_iter().map(util_strip_expr).collect(),
+      span,
+    },
+    Expr::Index { name, index, .. } => Expr::Index {
+      name,
+      index: Box::new(util_strip_expr(*index)),
+      span,
+    },
+    Expr::BinaryOps { car, cdr, .. } => Expr::BinaryOps {
+      car: Box::new(util_strip_expr(*car)),
+      cdr: cdr.into_iter().map(|(op, v)| (op, util_strip_expr(v))).collect(),
+      span,
+    },
+  }
+}
+
+/// Clears the spans of all expressions in a statement, see [`util_strip_expr`].
+pub fn util_strip_stmt(stmt: Stmt) -> Stmt {
+  match stmt {
+    Stmt::Set { name, assign_type, expr } => Stmt::Set {
+      name,
+      assign_type,
+      expr: util_strip_expr(expr),
+    },
+    Stmt::SetIndexed { name, index, assign_type, expr } => Stmt::SetIndexed {
+      name,
+      index: util_strip_expr(index),
+      assign_type,
+      expr: util_strip_expr(expr),
+    },
+    Stmt::WireDecl { name, signal_class, expr } => Stmt::WireDecl {
+      name,
+      signal_class,
+      expr: expr.map(util_strip_expr),
+    },
+    Stmt::BundleWireDecl { name, bundle, expr } => Stmt::BundleWireDecl {
+      name,
+      bundle,
+      expr: expr.map(util_strip_expr),
+    },
+    Stmt::ModuleInst { module, args } => Stmt::ModuleInst {
+      module,
+      args: match args {
+        ModuleArgs::Positional(args) => ModuleArgs::Positional(args.into_iter().map(util_strip_expr).collect()),
+        ModuleArgs::Named(args) => {
+          ModuleArgs::Named(args.into_iter().map(|(port, v)| (port, util_strip_expr(v))).collect())
+        }
+      },
+    },
+    Stmt::Trigger { watching, trigger_kind, once, statements, else_statements } => Stmt::Trigger {
+      watching: util_strip_expr(watching),
+      trigger_kind,
+      once,
+      statements: util_strip_stmts(statements),
+      else_statements: util_strip_stmts(else_statements),
+    },
+    stmt => stmt,
+  }
+}
+
+fn util_strip_stmts(stmts: Vec<(Stmt, Span)>) -> Vec<(Stmt, Span)> {
+  stmts.into_iter().map(|(stmt, span)| (util_strip_stmt(stmt), span)).collect()
+}
+
+fn util_parse_expr_stripped(tokens: &Cursor) -> Result<Expr, CerrSpan> {
+  Expr::parse(tokens).map(util_strip_expr)
+}
+
+fn util_parse_stmt_stripped(tokens: &Cursor) -> Result<(Stmt, Span), CerrSpan> {
+  Stmt::parse(tokens).map(|(stmt, span)| (util_strip_stmt(stmt), span))
+}
+
 #[test]
 pub fn expr_parse_valid() {
-  let expr = util_test_parser("z == (abs(re(z)) + i * abs(im(z))) ** 2 + c", Expr::parse);
+  let ds = Span::default();
+  let expr = util_test_parser("z == (abs(re(z)) + i * abs(im(z))) ** 2 + c", util_parse_expr_stripped);
   let expected = Expr::BinaryOps {
-    car: Box::new(Expr::Identifier { name: "z".into() }),
+    car: Box::new(Expr::Identifier { name: "z".into(), span: ds }),
     cdr: vec![(
       BinaryOp::Eq,
       Expr::BinaryOps {
@@ -56,53 +137,81 @@ pub fn expr_parse_valid() {
               func: "abs".into(),
               args: vec![Expr::FnCall {
                 func: "re".into(),
-                args: vec![Expr::Identifier { name: "z".into() }],
+                args: vec![Expr::Identifier { name: "z".into(), span: ds }],
+                span: ds,
               }],
+              span: ds,
             }),
             cdr: vec![(
               BinaryOp::Add,
               Expr::BinaryOps {
-                car: Box::new(Expr::Identifier { name: "i".into() }),
+                car: Box::new(Expr::Identifier { name: "i".into(), span: ds }),
                 cdr: vec![(
                   BinaryOp::Mul,
                   Expr::FnCall {
                     func: "abs".into(),
                     args: vec![Expr::FnCall {
                       func: "im".into(),
-                      args: vec![Expr::Identifier { name: "z".into() }],
+                      args: vec![Expr::Identifier { name: "z".into(), span: ds }],
+                      span: ds,
                     }],
+                    span: ds,
                   },
                 )],
+                span: ds,
               },
             )],
+            span: ds,
           }),
-          cdr: vec![(BinaryOp::Pow, Expr::Literal { val: 2 })],
+          cdr: vec![(BinaryOp::Pow, Expr::Literal { val: 2, span: ds })],
+          span: ds,
         }),
-        cdr: vec![(BinaryOp::Add, Expr::Identifier { name: "c".into() })],
+        cdr: vec![(BinaryOp::Add, Expr::Identifier { name: "c".into(), span: ds })],
+        span: ds,
       },
     )],
+    span: ds,
   };
   assert_eq!(expr, expected);
 }
 
+#[test]
+pub fn expr_parse_spans() {
+  let span = |start, end| Span {
+    start: Pos::new(1, start),
+    end: Pos::new(1, end),
+  };
+  let expr = util_test_parser("(a + b) * buf[i]", Expr::parse);
+  assert_eq!(expr.span(), span(0, 15));
+  let Expr::BinaryOps { car, cdr, .. } = expr else {
+    panic!("not a binary op");
+  };
+  // braces are part of the expression they surround
+  assert_eq!(car.span(), span(0, 6));
+  assert_eq!(cdr[0].1.span(), span(10, 15));
+}
+
 #[test]
 pub fn expr_parse_valid_fncalls() {
+  let ds = Span::default();
   let expr = util_test_parser(
     "fn() / fn(1,) % fn(2, fn()) - fn(3, fn(), fn(4),)",
-    Expr::parse,
+    util_parse_expr_stripped,
   );
   let expected = Expr::BinaryOps {
     car: Box::new(Expr::BinaryOps {
       car: Box::new(Expr::FnCall {
         func: "fn".into(),
         args: vec![],
+        span: ds,
       }),
       cdr: vec![
         (
           BinaryOp::Div,
           Expr::FnCall {
             func: "fn".into(),
-            args: vec![Expr::Literal { val: 1 }],
+            args: vec![Expr::Literal { val: 1, span: ds }],
+            span: ds,
           },
         ),
         (
@@ -110,85 +219,130 @@ pub fn expr_parse_valid_fncalls() {
           Expr::FnCall {
             func: "fn".into(),
             args: vec![
-              Expr::Literal { val: 2 },
+              Expr::Literal { val: 2, span: ds },
               Expr::FnCall {
                 func: "fn".into(),
                 args: vec![],
+                span: ds,
               },
             ],
+            span: ds,
           },
         ),
       ],
+      span: ds,
     }),
     cdr: vec![(
       BinaryOp::Sub,
       Expr::FnCall {
         func: "fn".into(),
         args: vec![
-          Expr::Literal { val: 3 },
+          Expr::Literal { val: 3, span: ds },
           Expr::FnCall {
             func: "fn".into(),
             args: vec![],
+            span: ds,
           },
           Expr::FnCall {
             func: "fn".into(),
-            args: vec![Expr::Literal { val: 4 }],
+            args: vec![Expr::Literal { val: 4, span: ds }],
+            span: ds,
           },
         ],
+        span: ds,
       },
     )],
+    span: ds,
   };
   assert_eq!(expr, expected);
 }
 
 #[test]
 pub fn expr_parse_valid_brackets() {
+  let ds = Span::default();
   let expr = util_test_parser(
     "among_us(((3 * ((2) + (((1)) & (((((((((x))))))))) )))) ** 4,)",
-    Expr::parse,
+    util_parse_expr_stripped,
   );
   let expected = Expr::FnCall {
     func: "among_us".into(),
     args: vec![Expr::BinaryOps {
       car: Box::new(Expr::BinaryOps {
-        car: Box::new(Expr::Literal { val: 3 }),
+        car: Box::new(Expr::Literal { val: 3, span: ds }),
         cdr: vec![(
           BinaryOp::Mul,
           Expr::BinaryOps {
-            car: Box::new(Expr::Literal { val: 2 }),
+            car: Box::new(Expr::Literal { val: 2, span: ds }),
             cdr: vec![(
               BinaryOp::Add,
               Expr::BinaryOps {
-                car: Box::new(Expr::Literal { val: 1 }),
-                cdr: vec![(BinaryOp::And, Expr::Identifier { name: "x".into() })],
+                car: Box::new(Expr::Literal { val: 1, span: ds }),
+                cdr: vec![(BinaryOp::And, Expr::Identifier { name: "x".into(), span: ds })],
+                span: ds,
               },
             )],
+            span: ds,
           },
         )],
+        span: ds,
       }),
-      cdr: vec![(BinaryOp::Pow, Expr::Literal { val: 4 })],
+      cdr: vec![(BinaryOp::Pow, Expr::Literal { val: 4, span: ds })],
+      span: ds,
     }],
+    span: ds,
   };
   assert_eq!(expr, expected);
 }
 
 #[test]
 pub fn expr_parse_valid_strings() {
-  let expr = util_test_parser("func(1 +\" strig\")** \"st 2\"", Expr::parse);
+  let ds = Span::default();
+  let expr = util_test_parser("func(1 +\" strig\")** \"st 2\"", util_parse_expr_stripped);
   let expected = Expr::BinaryOps {
     car: Box::new(Expr::FnCall {
       func: "func".into(),
       args: vec![Expr::BinaryOps {
-        car: Box::new(Expr::Literal { val: 1 }),
+        car: Box::new(Expr::Literal { val: 1, span: ds }),
         cdr: vec![(
           BinaryOp::Add,
           Expr::StringLiteral {
             str: " strig".into(),
+            span: ds,
           },
         )],
+        span: ds,
       }],
+      span: ds,
     }),
-    cdr: vec![(BinaryOp::Pow, Expr::StringLiteral { str: "st 2".into() })],
+    cdr: vec![(BinaryOp::Pow, Expr::StringLiteral { str: "st 2".into(), span: ds })],
+    span: ds,
+  };
+  assert_eq!(expr, expected);
+}
+
+#[test]
+pub fn expr_parse_valid_logical() {
+  let ds = Span::default();
+  let expr = util_test_parser("a == 1 && b & 2 || c", util_parse_expr_stripped);
+  let expected = Expr::BinaryOps {
+    car: Box::new(Expr::BinaryOps {
+      car: Box::new(Expr::BinaryOps {
+        car: Box::new(Expr::Identifier { name: "a".into(), span: ds }),
+        cdr: vec![(BinaryOp::Eq, Expr::Literal { val: 1, span: ds })],
+        span: ds,
+      }),
+      cdr: vec![(
+        BinaryOp::LAnd,
+        Expr::BinaryOps {
+          car: Box::new(Expr::Identifier { name: "b".into(), span: ds }),
+          cdr: vec![(BinaryOp::And, Expr::Literal { val: 2, span: ds })],
+          span: ds,
+        },
+      )],
+      span: ds,
+    }),
+    cdr: vec![(BinaryOp::LOr, Expr::Identifier { name: "c".into(), span: ds })],
+    span: ds,
   };
   assert_eq!(expr, expected);
 }
@@ -224,29 +378,46 @@ pub fn stmt_parse_mem_decl() {
   let expected = Stmt::MemDecl {
     name: "reg1".into(),
     signal_class: NetType::Single,
+    init: None,
+    reset: None,
   };
   assert_eq!(stmt, expected);
 }
 
 #[test]
 pub fn stmt_parse_mem_decl_invalid() {
-  util_test_parser_err("mem mixed reg1 = 4;", Stmt::parse);
+  util_test_parser_err("mem mixed reg1 = a;", Stmt::parse);
+}
+
+#[test]
+pub fn stmt_parse_mem_decl_init_reset() {
+  let stmt = util_test_parser("mem single counter = -5 reset rst;", Stmt::parse).0;
+  let expected = Stmt::MemDecl {
+    name: "counter".into(),
+    signal_class: NetType::Single,
+    init: Some(-5),
+    reset: Some("rst".into()),
+  };
+  assert_eq!(stmt, expected);
 }
 
 #[test]
 pub fn stmt_parse_mem_set1() {
-  let stmt = util_test_parser("set reg2 = 4 + wire1;", Stmt::parse).0;
+  let ds = Span::default();
+  let stmt = util_test_parser("set reg2 = 4 + wire1;", util_parse_stmt_stripped).0;
   let expected = Stmt::Set {
     name: "reg2".into(),
     assign_type: BinaryOp::Assign,
     expr: Expr::BinaryOps {
-      car: Box::new(Expr::Literal { val: 4 }),
+      car: Box::new(Expr::Literal { val: 4, span: ds }),
       cdr: vec![(
         BinaryOp::Add,
         Expr::Identifier {
           name: "wire1".into(),
+          span: ds,
         },
       )],
+      span: ds,
     },
   };
   assert_eq!(stmt, expected);
@@ -254,7 +425,8 @@ pub fn stmt_parse_mem_set1() {
 
 #[test]
 pub fn stmt_parse_mem_set2() {
-  let stmt = util_test_parser("set reg3 += delay(wire1,) ^ wire2;", Stmt::parse).0;
+  let ds = Span::default();
+  let stmt = util_test_parser("set reg3 += delay(wire1,) ^ wire2;", util_parse_stmt_stripped).0;
   let expected = Stmt::Set {
     name: "reg3".into(),
     assign_type: BinaryOp::AddAssign,
@@ -263,14 +435,18 @@ pub fn stmt_parse_mem_set2() {
         func: "delay".into(),
         args: vec![Expr::Identifier {
           name: "wire1".into(),
+          span: ds,
         }],
+        span: ds,
       }),
       cdr: vec![(
         BinaryOp::Xor,
         Expr::Identifier {
           name: "wire2".into(),
+          span: ds,
         },
       )],
+      span: ds,
     },
   };
   assert_eq!(stmt, expected);
@@ -281,15 +457,85 @@ pub fn stmt_parse_mem_set_invalid() {
   util_test_parser_err("set reg1 += wire1 = 3;", Stmt::parse);
 }
 
+#[test]
+pub fn stmt_parse_mem_array() {
+  let stmt = util_test_parser("mem single buf[64];", Stmt::parse).0;
+  let expected = Stmt::MemArrayDecl {
+    name: "buf".into(),
+    signal_class: NetType::Single,
+    len: 64,
+  };
+  assert_eq!(stmt, expected);
+  util_test_parser_err("mem single buf[64] = 3;", Stmt::parse);
+  util_test_parser_err("mem single buf[n];", Stmt::parse);
+}
+
+#[test]
+pub fn stmt_parse_mem_array_access() {
+  let ds = Span::default();
+  let stmt = util_test_parser("set buf[addr + 1] = buf[addr];", util_parse_stmt_stripped).0;
+  let expected = Stmt::SetIndexed {
+    name: "buf".into(),
+    index: Expr::BinaryOps {
+      car: Box::new(Expr::Identifier { name: "addr".into(), span: ds }),
+      cdr: vec![(BinaryOp::Add, Expr::Literal { val: 1, span: ds })],
+      span: ds,
+    },
+    assign_type: BinaryOp::Assign,
+    expr: Expr::Index {
+      name: "buf".into(),
+      index: Box::new(Expr::Identifier { name: "addr".into(), span: ds }),
+      span: ds,
+    },
+  };
+  assert_eq!(stmt, expected);
+  util_test_parser_err("set buf[addr = 3;", Stmt::parse);
+}
+
+#[test]
+pub fn stmt_parse_rom() {
+  let stmt = util_test_parser("rom single sizes = [1, -2, 4,];", Stmt::parse).0;
+  let expected = Stmt::RomDecl {
+    name: "sizes".into(),
+    signal_class: NetType::Single,
+    data: RomData::Inline(vec![RomEntry::Single(1), RomEntry::Single(-2), RomEntry::Single(4)]),
+  };
+  assert_eq!(stmt, expected);
+  let stmt = util_test_parser(
+    "rom mixed recipes = [{\"item=iron-plate\" = 2, \"virtual=signal-A\" = -1}, {}];",
+    Stmt::parse,
+  ).0;
+  let expected = Stmt::RomDecl {
+    name: "recipes".into(),
+    signal_class: NetType::Mixed,
+    data: RomData::Inline(vec![
+      RomEntry::Mixed(vec![("item=iron-plate".into(), 2), ("virtual=signal-A".into(), -1)]),
+      RomEntry::Mixed(vec![]),
+    ]),
+  };
+  assert_eq!(stmt, expected);
+  let stmt = util_test_parser("rom mixed recipes = \"recipes.json\";", Stmt::parse).0;
+  let expected = Stmt::RomDecl {
+    name: "recipes".into(),
+    signal_class: NetType::Mixed,
+    data: RomData::File("recipes.json".into()),
+  };
+  assert_eq!(stmt, expected);
+  util_test_parser_err("rom single sizes = [a];", Stmt::parse);
+  util_test_parser_err("rom mixed recipes = [{iron = 2}];", Stmt::parse);
+}
+
 #[test]
 pub fn stmt_parse_wire_decl1() {
-  let stmt = util_test_parser("wire single wire4 = 2 + 2;", Stmt::parse).0;
+  let ds = Span::default();
+  let stmt = util_test_parser("wire single wire4 = 2 + 2;", util_parse_stmt_stripped).0;
   let expected = Stmt::WireDecl {
     name: "wire4".into(),
     signal_class: NetType::Single,
     expr: Some(Expr::BinaryOps {
-      car: Box::new(Expr::Literal { val: 2 }),
-      cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2 })],
+      car: Box::new(Expr::Literal { val: 2, span: ds }),
+      cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2, span: ds })],
+      span: ds,
     }),
   };
   assert_eq!(stmt, expected);
@@ -311,51 +557,83 @@ pub fn stmt_parse_inst1() {
   let stmt = util_test_parser("inst module6();", Stmt::parse).0;
   let expected = Stmt::ModuleInst {
     module: "module6".into(),
-    args: vec![],
+    args: ModuleArgs::Positional(vec![]),
   };
   assert_eq!(stmt, expected);
 }
 
 #[test]
 pub fn stmt_parse_inst2() {
-  let stmt = util_test_parser("inst module7(99, (wire1), (wire2 & 1) + 4,);", Stmt::parse).0;
+  let ds = Span::default();
+  let stmt = util_test_parser("inst module7(99, (wire1), (wire2 & 1) + 4,);", util_parse_stmt_stripped).0;
   let expected = Stmt::ModuleInst {
     module: "module7".into(),
-    args: vec![
-      Expr::Literal { val: 99 },
+    args: ModuleArgs::Positional(vec![
+      Expr::Literal { val: 99, span: ds },
       Expr::Identifier {
         name: "wire1".into(),
+        span: ds,
       },
       Expr::BinaryOps {
         car: Box::new(Expr::BinaryOps {
           car: Box::new(Expr::Identifier {
             name: "wire2".into(),
+            span: ds,
           }),
-          cdr: vec![(BinaryOp::And, Expr::Literal { val: 1 })],
+          cdr: vec![(BinaryOp::And, Expr::Literal { val: 1, span: ds })],
+          span: ds,
         }),
-        cdr: vec![(BinaryOp::Add, Expr::Literal { val: 4 })],
+        cdr: vec![(BinaryOp::Add, Expr::Literal { val: 4, span: ds })],
+        span: ds,
       },
-    ],
+    ]),
+  };
+  assert_eq!(stmt, expected);
+}
+
+#[test]
+pub fn stmt_parse_inst_named() {
+  let ds = Span::default();
+  let stmt = util_test_parser("inst counter(reset: r, output: o + 1,);", util_parse_stmt_stripped).0;
+  let expected = Stmt::ModuleInst {
+    module: "counter".into(),
+    args: ModuleArgs::Named(vec![
+      ("reset".into(), Expr::Identifier { name: "r".into(), span: ds }),
+      (
+        "output".into(),
+        Expr::BinaryOps {
+          car: Box::new(Expr::Identifier { name: "o".into(), span: ds }),
+          cdr: vec![(BinaryOp::Add, Expr::Literal { val: 1, span: ds })],
+          span: ds,
+        },
+      ),
+    ]),
   };
   assert_eq!(stmt, expected);
+  // positional and named arguments can't be mixed
+  util_test_parser_err("inst counter(reset: r, o);", Stmt::parse);
+  util_test_parser_err("inst counter(r, output: o);", Stmt::parse);
 }
 
 #[test]
 pub fn stmt_parse_trigger1() {
+  let ds = Span::default();
   let stmt = util_test_parser(
     "trigger clk changed { set thing = 4; wire mixed unused; };",
-    Stmt::parse,
+    util_parse_stmt_stripped,
   )
   .0;
   let expected = Stmt::Trigger {
-    watching: "clk".to_string(),
+    watching: Expr::Identifier { name: "clk".into(), span: ds },
     trigger_kind: TriggerKind::Changed,
+    once: false,
+    else_statements: vec![],
     statements: vec![
       (
         Stmt::Set {
           name: "thing".to_string(),
           assign_type: BinaryOp::Assign,
-          expr: Expr::Literal { val: 4 },
+          expr: Expr::Literal { val: 4, span: ds },
         },
         Span {
           start: Pos::new(1, 22),
@@ -378,6 +656,62 @@ pub fn stmt_parse_trigger1() {
   assert_eq!(stmt, expected);
 }
 
+#[test]
+pub fn stmt_parse_trigger_once() {
+  let ds = Span::default();
+  let stmt = util_test_parser("trigger clk raw once { };", util_parse_stmt_stripped).0;
+  let expected = Stmt::Trigger {
+    watching: Expr::Identifier { name: "clk".into(), span: ds },
+    trigger_kind: TriggerKind::Raw,
+    once: true,
+    else_statements: vec![],
+    statements: vec![],
+  };
+  assert_eq!(stmt, expected);
+}
+
+#[test]
+pub fn stmt_parse_trigger_expr() {
+  let ds = Span::default();
+  let stmt = util_test_parser("trigger (a > 5) raw { };", util_parse_stmt_stripped).0;
+  let expected = Stmt::Trigger {
+    watching: Expr::BinaryOps {
+      car: Box::new(Expr::Identifier { name: "a".into(), span: ds }),
+      cdr: vec![(BinaryOp::Gt, Expr::Literal { val: 5, span: ds })],
+      span: ds,
+    },
+    trigger_kind: TriggerKind::Raw,
+    once: false,
+    else_statements: vec![],
+    statements: vec![],
+  };
+  assert_eq!(stmt, expected);
+}
+
+#[test]
+pub fn stmt_parse_trigger_else() {
+  let ds = Span::default();
+  let stmt = util_test_parser("trigger clk raw { } else { wire single unused; };", util_parse_stmt_stripped).0;
+  let expected = Stmt::Trigger {
+    watching: Expr::Identifier { name: "clk".into(), span: ds },
+    trigger_kind: TriggerKind::Raw,
+    once: false,
+    statements: vec![],
+    else_statements: vec![(
+      Stmt::WireDecl {
+        name: "unused".to_string(),
+        signal_class: NetType::Single,
+        expr: None,
+      },
+      Span {
+        start: Pos::new(1, 27),
+        end: Pos::new(1, 45),
+      },
+    )],
+  };
+  assert_eq!(stmt, expected);
+}
+
 #[test]
 pub fn stmt_parse_trigger_invalid1() {
   util_test_parser_err(
@@ -412,6 +746,7 @@ pub fn stmt_parse_trigger_invalid4() {
 
 #[test]
 pub fn module_parse_valid() {
+  let ds = Span::default();
   let module = util_test_parser(
     "// commen
 module foo(in single x, inout single y)
@@ -426,6 +761,13 @@ module foo(in single x, inout single y)
 ",
     Module::parse,
   );
+  let module = (
+    Module {
+      stmts: util_strip_stmts(module.0.stmts),
+      ..module.0
+    },
+    module.1,
+  );
   let expected = (
     Module {
       name: "foo".into(),
@@ -434,11 +776,13 @@ module foo(in single x, inout single y)
           port_class: PortClass::In,
           signal_class: NetType::Single,
           name: "x".into(),
+          bundle: None,
         },
         PortDecl {
           port_class: PortClass::InOut,
           signal_class: NetType::Single,
           name: "y".into(),
+          bundle: None,
         },
       ],
       stmts: vec![
@@ -446,6 +790,8 @@ module foo(in single x, inout single y)
           Stmt::MemDecl {
             name: "reg".into(),
             signal_class: NetType::Single,
+            init: None,
+            reset: None,
           },
           Span {
             start: Pos::new(4, 2),
@@ -457,8 +803,9 @@ module foo(in single x, inout single y)
             name: "w".into(),
             signal_class: NetType::Single,
             expr: Some(Expr::BinaryOps {
-              car: Box::new(Expr::Identifier { name: "x".into() }),
-              cdr: vec![(BinaryOp::Add, Expr::Identifier { name: "y".into() })],
+              car: Box::new(Expr::Identifier { name: "x".into(), span: ds }),
+              cdr: vec![(BinaryOp::Add, Expr::Identifier { name: "y".into(), span: ds })],
+              span: ds,
             }),
           },
           Span {
@@ -468,14 +815,16 @@ module foo(in single x, inout single y)
         ),
         (
           Stmt::Trigger {
-            watching: "x".into(),
+            watching: Expr::Identifier { name: "x".into(), span: ds },
             trigger_kind: TriggerKind::Increasing,
+            once: false,
+            else_statements: vec![],
             statements: vec![
               (
                 Stmt::Set {
                   name: "reg".into(),
                   assign_type: BinaryOp::Assign,
-                  expr: Expr::Identifier { name: "y".into() },
+                  expr: Expr::Identifier { name: "y".into(), span: ds },
                 },
                 Span {
                   start: Pos::new(7, 4),
@@ -504,7 +853,7 @@ module foo(in single x, inout single y)
           Stmt::Set {
             name: "y".into(),
             assign_type: BinaryOp::AddAssign,
-            expr: Expr::Identifier { name: "w".into() },
+            expr: Expr::Identifier { name: "w".into(), span: ds },
           },
           Span {
             start: Pos::new(10, 2),
@@ -520,3 +869,99 @@ module foo(in single x, inout single y)
   );
   assert_eq!(module, expected);
 }
+
+#[test]
+pub fn program_parse_bundle() {
+  let ds = Span::default();
+  let program = util_test_parser(
+    "version 2;
+bundle Req { single item; mixed payload; }
+module foo(in Req r, out single q) {
+  wire Req copy = r;
+  set q = copy.item;
+}
+",
+    Program::parse,
+  );
+  let expected = Bundle {
+    name: "Req".into(),
+    members: vec![
+      BundleMember {
+        signal_class: NetType::Single,
+        name: "item".into(),
+      },
+      BundleMember {
+        signal_class: NetType::Mixed,
+        name: "payload".into(),
+      },
+    ],
+  };
+  assert_eq!(program.bundles.len(), 1);
+  assert_eq!(program.bundles[0].0, expected);
+  let module = &program.modules[0].0;
+  assert_eq!(module.ports[0], PortDecl {
+    port_class: PortClass::In,
+    signal_class: NetType::Mixed,
+    name: "r".into(),
+    bundle: Some("Req".into()),
+  });
+  assert_eq!(module.ports[1].bundle, None);
+  let stmts = module.stmts.iter().map(|(stmt, _)| util_strip_stmt(stmt.clone())).collect::<Vec<_>>();
+  assert_eq!(stmts, vec![
+    Stmt::BundleWireDecl {
+      name: "copy".into(),
+      bundle: "Req".into(),
+      expr: Some(Expr::Identifier { name: "r".into(), span: ds }),
+    },
+    Stmt::Set {
+      name: "q".into(),
+      assign_type: BinaryOp::Assign,
+      expr: Expr::Identifier { name: "copy.item".into(), span: ds },
+    },
+  ]);
+  util_test_parser_err("bundle Req { single item, single qty }", Bundle::parse);
+  util_test_parser_err("bundle Req { Other inner; }", Bundle::parse);
+}
+
+#[test]
+pub fn program_parse_import() {
+  let program = util_test_parser("version 2; import \"lib/ltn.fhdl\"; module m() {}", Program::parse);
+  let imports = program.imports.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>();
+  assert_eq!(imports, vec!["lib/ltn.fhdl"]);
+  util_test_parser_err("version 2; import ltn;", Program::parse);
+  util_test_parser_err("version 2; import \"ltn.fhdl\"", Program::parse);
+}
+
+#[test]
+pub fn program_parse_recovery() {
+  let src = "version 2;
+module a(in single x) {
+  wire single w = x +;
+  set w = 1;
+  trigger x raw { set q = ; wire single ok; };
+  mem single 5;
+}
+bundle B { single item, }
+module b() { inst a(1); }
+module c( {
+module d() { wire single v; }";
+  let tokens = TokenStream::from_tokens(tokenize(src.chars()).collect::<Result<Vec<_>, _>>().unwrap());
+  let (program, errs) = Program::parse_all(&tokens.begin());
+  let lines = errs.iter().map(|v| v.span.unwrap().start.line).collect::<Vec<_>>();
+  assert_eq!(lines, vec![3, 5, 6, 8, 10]);
+  assert_eq!(errs[0].cerr, Cerr::InvalidExpr);
+  // everything around the errors is still parsed
+  let modules = program.modules.iter().map(|(v, _)| v.name.as_str()).collect::<Vec<_>>();
+  assert_eq!(modules, vec!["a", "b", "d"]);
+  let stmts = &program.modules[0].0.stmts;
+  assert_eq!(stmts.len(), 2);
+  assert!(matches!(&stmts[1].0, Stmt::Trigger { statements, .. } if statements.len() == 1));
+  // the first error is reported when not recovering
+  assert_eq!(util_test_parser_err(src, Program::parse), errs[0]);
+  // a module that's never closed ends at the next module
+  let src = "version 2; module a() { wire single w; module b() {}";
+  let tokens = TokenStream::from_tokens(tokenize(src.chars()).collect::<Result<Vec<_>, _>>().unwrap());
+  let (program, errs) = Program::parse_all(&tokens.begin());
+  assert_eq!(errs.len(), 1);
+  assert_eq!(program.modules.len(), 1);
+}
@@ -1,4 +1,8 @@
 mod ast;
 mod iter_with_pos;
+mod printer;
+mod project;
+mod rom;
+mod stdlib;
 mod tokenizer;
 mod tokenstream;
@@ -0,0 +1,348 @@
+use crate::parse::ast::{Bundle, BundleMember, Expr, Module, ModuleArgs, NetType, PortClass, PortDecl, Program, RomData, RomEntry, Stmt, TriggerKind, Version};
+use crate::parse::printer::{format_source, print_program};
+use crate::parse::span::Span;
+use crate::parse::stdlib;
+use crate::parse::tokenizer::{tokenize, BinaryOp};
+use crate::parse::tokenstream::TokenStream;
+use crate::test::parse::ast::util_strip_stmt;
+use crate::util::ResultExt;
+
+fn util_parse(src: &str) -> Program {
+  let tokens = tokenize(src.chars()).collect::<Result<Vec<_>, _>>().pretty_unwrap();
+  Program::parse(&TokenStream::from_tokens(tokens).begin()).pretty_unwrap()
+}
+
+/// Clears every span of a program, so that programs parsed from differently formatted sources
+/// can be compared.
+fn util_strip_program(program: Program) -> Program {
+  let ds = Span::default();
+  Program {
+    version: program.version,
+    imports: program.imports.into_iter().map(|(v, _)| (v, ds)).collect(),
+    bundles: program.bundles.into_iter().map(|(v, _)| (v, ds)).collect(),
+    modules: program
+      .modules
+      .into_iter()
+      .map(|(v, _)| {
+        let module = Module {
+          stmts: util_strip_stmt_spans(v.stmts),
+          ..v
+        };
+        (module, ds)
+      })
+      .collect(),
+  }
+}
+
+fn util_strip_stmt_spans(stmts: Vec<(Stmt, Span)>) -> Vec<(Stmt, Span)> {
+  stmts
+    .into_iter()
+    .map(|(stmt, _)| {
+      let stmt = match util_strip_stmt(stmt) {
+        Stmt::Trigger { watching, trigger_kind, once, statements, else_statements } => Stmt::Trigger {
+          watching,
+          trigger_kind,
+          once,
+          statements: util_strip_stmt_spans(statements),
+          else_statements: util_strip_stmt_spans(else_statements),
+        },
+        stmt => stmt,
+      };
+      (stmt, Span::default())
+    })
+    .collect()
+}
+
+#[test]
+pub fn printer_format_source() {
+  let src = "// header
+version   2;
+import \"std/clock.fhdl\";  import \"b.fhdl\";
+bundle Req { single item; // the item
+  mixed payload }
+module m(in single a,
+  // the output
+  out single q) { wire single w = (a + 1) * 2;   // doubled
+  mem single x = -5 reset a;
+
+
+  trigger (a>0) increasing once {
+    set x += w;} else { set x = 0; } ;
+  inst other(x: (a), y: q);
+  rom mixed r = [{\"signal-A\"=1, \"signal-B\" = -2}, {}];
+}
+module other(in single x, out single y) {}
+// trailing";
+  let expected = "// header
+version 2;
+
+import \"std/clock.fhdl\";
+import \"b.fhdl\";
+
+bundle Req {
+  single item; // the item
+  mixed payload;
+}
+
+// the output
+module m(in single a, out single q) {
+  wire single w = (a + 1) * 2; // doubled
+  mem single x = -5 reset a;
+
+  trigger (a > 0) increasing once {
+    set x += w;
+  } else {
+    set x = 0;
+  };
+  inst other(x: a, y: q);
+  rom mixed r = [{\"signal-A\" = 1, \"signal-B\" = -2}, {}];
+}
+
+module other(in single x, out single y) {}
+// trailing
+";
+  let formatted = format_source(src).pretty_unwrap();
+  assert_eq!(formatted, expected);
+  assert_eq!(format_source(&formatted).pretty_unwrap(), expected);
+
+  // operands keep the parentheses they need, and overflowed literals are written in hex
+  let src = "version 2; module m() { trigger x changed { set y = a - (b - c) * (d % 2) ** 2 + 0xFFFFFFFF; }; }";
+  let formatted = format_source(src).pretty_unwrap();
+  assert!(formatted.contains("set y = a - (b - c) * (d % 2) ** 2 + 0xFFFFFFFF;"), "{}", formatted);
+  assert!(format_source("version 2; module m( {}").is_err());
+}
+
+#[test]
+pub fn printer_else_comments() {
+  let src = "version 2;
+module m() {
+  trigger a raw { set x = 1; } else {
+    // nothing yet
+  };
+  trigger b raw {
+    set x = 2;
+  } // after the then block
+  // before the else
+  else { set x = 3; };
+  trigger c raw {} else {};
+}
+";
+  let expected = "version 2;
+
+module m() {
+  trigger a raw {
+    set x = 1;
+  } else {
+    // nothing yet
+  };
+  trigger b raw {
+    set x = 2;
+  } // after the then block
+  // before the else
+  else {
+    set x = 3;
+  };
+  trigger c raw {};
+}
+";
+  let formatted = format_source(src).pretty_unwrap();
+  assert_eq!(formatted, expected);
+  assert_eq!(format_source(&formatted).pretty_unwrap(), expected);
+}
+
+#[test]
+pub fn printer_stdlib_and_examples() {
+  // the standard library is already formatted
+  for import in stdlib::imports() {
+    let src = stdlib::source(&stdlib::resolve(&import).unwrap()).unwrap();
+    assert_eq!(format_source(src).pretty_unwrap(), src, "{}", import);
+  }
+  let src = include_str!("../../../examples/counter.fhdl");
+  let formatted = format_source(src).pretty_unwrap();
+  assert_eq!(util_strip_program(util_parse(&formatted)), util_strip_program(util_parse(src)));
+  assert_eq!(format_source(&formatted).pretty_unwrap(), formatted);
+  assert_eq!(formatted.matches("//").count(), src.matches("//").count());
+}
+
+/// A xorshift generator, so that every run tests the same programs.
+struct Rng(u64);
+
+impl Rng {
+  fn next(&mut self) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0
+  }
+
+  fn below(&mut self, n: usize) -> usize {
+    (self.next() % n as u64) as usize
+  }
+
+  fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+    items[self.below(items.len())].clone()
+  }
+
+  fn name(&mut self) -> String {
+    self.pick(&["a", "b", "count", "req.item", "_tmp2"]).into()
+  }
+
+  fn literal(&mut self) -> i32 {
+    self.pick(&[0, 1, 42, -7, i32::MAX, i32::MIN])
+  }
+
+  fn net_type(&mut self) -> NetType {
+    self.pick(&[NetType::Single, NetType::Mixed])
+  }
+}
+
+fn util_random_expr(rng: &mut Rng, depth: u32) -> Expr {
+  let span = Span::default();
+  let kind = if depth == 0 { rng.below(3) } else { rng.below(6) };
+  match kind {
+    0 => Expr::Identifier { name: rng.name(), span },
+    1 => Expr::Literal { val: rng.literal(), span },
+    2 => Expr::StringLiteral { str: "signal-A".into(), span },
+    3 => Expr::FnCall {
+      func: rng.pick(&["delay", "extract"]).into(),
+      args: (0..rng.below(3)).map(|_| util_random_expr(rng, depth - 1)).collect(),
+      span,
+    },
+    4 => Expr::Index {
+      name: rng.name(),
+      index: Box::new(util_random_expr(rng, depth - 1)),
+      span,
+    },
+    _ => {
+      // every operator of a chain has the same precedence, like the parser makes them
+      let ops = [
+        BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div, BinaryOp::Mod, BinaryOp::Pow, BinaryOp::And,
+        BinaryOp::Or, BinaryOp::Xor, BinaryOp::Shl, BinaryOp::Shr, BinaryOp::Eq, BinaryOp::Ne, BinaryOp::Lt,
+        BinaryOp::Gt, BinaryOp::Le, BinaryOp::Ge, BinaryOp::LAnd, BinaryOp::LOr,
+      ];
+      let prec = rng.pick(&ops).precedence();
+      let ops = ops.iter().filter(|v| v.precedence() == prec).copied().collect::<Vec<_>>();
+      Expr::BinaryOps {
+        car: Box::new(util_random_expr(rng, depth - 1)),
+        cdr: (0..1 + rng.below(3)).map(|_| (rng.pick(&ops), util_random_expr(rng, depth - 1))).collect(),
+        span,
+      }
+    }
+  }
+}
+
+fn util_random_stmts(rng: &mut Rng, depth: u32) -> Vec<(Stmt, Span)> {
+  (0..rng.below(5)).map(|_| (util_random_stmt(rng, depth), Span::default())).collect()
+}
+
+fn util_random_stmt(rng: &mut Rng, depth: u32) -> Stmt {
+  let kind = if depth == 0 { rng.below(8) } else { rng.below(9) };
+  let assign_type = rng.pick(&[BinaryOp::Assign, BinaryOp::AddAssign]);
+  match kind {
+    0 => Stmt::MemDecl {
+      name: rng.name(),
+      signal_class: rng.net_type(),
+      init: (rng.below(2) == 0).then(|| rng.literal()),
+      reset: (rng.below(2) == 0).then(|| rng.name()),
+    },
+    1 => Stmt::MemArrayDecl {
+      name: rng.name(),
+      signal_class: rng.net_type(),
+      len: rng.below(100) as u32,
+    },
+    2 => Stmt::Set {
+      name: rng.name(),
+      assign_type,
+      expr: util_random_expr(rng, 3),
+    },
+    3 => {
+      let entries = (0..rng.below(3))
+        .map(|_| match rng.below(2) {
+          0 => RomEntry::Single(rng.literal()),
+          _ => RomEntry::Mixed((0..rng.below(3)).map(|_| ("signal-A".into(), rng.literal())).collect()),
+        })
+        .collect();
+      Stmt::RomDecl {
+        name: rng.name(),
+        signal_class: rng.net_type(),
+        data: rng.pick(&[RomData::Inline(entries), RomData::File("data.csv".into())]),
+      }
+    }
+    4 => Stmt::SetIndexed {
+      name: rng.name(),
+      index: util_random_expr(rng, 2),
+      assign_type,
+      expr: util_random_expr(rng, 3),
+    },
+    5 => Stmt::WireDecl {
+      name: rng.name(),
+      signal_class: rng.net_type(),
+      expr: (rng.below(2) == 0).then(|| util_random_expr(rng, 3)),
+    },
+    6 => Stmt::BundleWireDecl {
+      name: rng.name(),
+      bundle: "Req".into(),
+      expr: (rng.below(2) == 0).then(|| util_random_expr(rng, 1)),
+    },
+    7 => Stmt::ModuleInst {
+      module: "other".into(),
+      args: match rng.below(2) {
+        0 => ModuleArgs::Positional((0..rng.below(3)).map(|_| util_random_expr(rng, 2)).collect()),
+        _ => ModuleArgs::Named((0..1 + rng.below(2)).map(|_| (rng.name(), util_random_expr(rng, 2))).collect()),
+      },
+    },
+    _ => Stmt::Trigger {
+      watching: util_random_expr(rng, 2),
+      trigger_kind: rng.pick(&[TriggerKind::Increasing, TriggerKind::Decreasing, TriggerKind::Changed, TriggerKind::Raw]),
+      once: rng.below(2) == 0,
+      statements: util_random_stmts(rng, depth - 1),
+      else_statements: util_random_stmts(rng, depth - 1),
+    },
+  }
+}
+
+fn util_random_program(rng: &mut Rng) -> Program {
+  let ds = Span::default();
+  let bundles = (0..rng.below(2))
+    .map(|_| {
+      let members = (0..rng.below(3))
+        .map(|_| BundleMember { signal_class: rng.net_type(), name: rng.name() })
+        .collect();
+      (Bundle { name: "Req".into(), members }, ds)
+    })
+    .collect();
+  let modules = (0..1 + rng.below(2))
+    .map(|_| {
+      let ports = (0..rng.below(3))
+        .map(|_| {
+          let port_class = rng.pick(&[PortClass::In, PortClass::Out, PortClass::InOut]);
+          // bundle ports are always mixed, like the parser makes them
+          match rng.below(3) {
+            0 => PortDecl { port_class, signal_class: NetType::Mixed, name: rng.name(), bundle: Some("Req".into()) },
+            _ => PortDecl { port_class, signal_class: rng.net_type(), name: rng.name(), bundle: None },
+          }
+        })
+        .collect();
+      let module = Module { name: rng.name(), ports, stmts: util_random_stmts(rng, 2) };
+      (module, ds)
+    })
+    .collect();
+  Program {
+    version: Version::V2,
+    imports: (0..rng.below(3)).map(|_| ("lib/a.fhdl".into(), ds)).collect(),
+    bundles,
+    modules,
+  }
+}
+
+#[test]
+pub fn printer_round_trip() {
+  // parse(print(x)) == x, for random programs
+  let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+  for _ in 0..300 {
+    let program = util_random_program(&mut rng);
+    let printed = print_program(&program);
+    assert_eq!(util_strip_program(util_parse(&printed)), program, "{}", printed);
+    assert_eq!(format_source(&printed).pretty_unwrap(), printed);
+  }
+}
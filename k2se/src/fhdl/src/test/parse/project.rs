@@ -0,0 +1,90 @@
+use crate::err::Cerr;
+use crate::parse::project::Project;
+use crate::synth::builtins::collect_builtins;
+use crate::synth::transform::transform_project;
+use std::path::{Path, PathBuf};
+
+/// Writes files into a fresh temporary directory.
+fn util_write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("fhdl_project_{}_{}", name, std::process::id()));
+  for (path, src) in files {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, src).unwrap();
+  }
+  dir
+}
+
+fn file_name(project: &Project, id: usize) -> &str {
+  project.files[id].path.file_name().and_then(|v| v.to_str()).unwrap()
+}
+
+#[test]
+pub fn project_load_imports() {
+  let dir = util_write_files("imports", &[
+    ("main.fhdl", "version 2; import \"util.fhdl\"; import \"ltn.fhdl\";
+      module main(in single a, out single b) { inst double(a, b); }"),
+    ("util.fhdl", "version 2; import \"ltn.fhdl\";
+      module double(in single a, out single b) { inst scale(a, b); }"),
+    ("lib/ltn.fhdl", "version 2; module scale(in single a, out single b) { set b = a * 2; }"),
+  ]);
+  let (project, errs) = Project::load(&dir.join("main.fhdl"), &[dir.join("lib")]);
+  let (modules, transform_errs) = transform_project(&project, &collect_builtins());
+  std::fs::remove_dir_all(&dir).unwrap();
+  assert_eq!(errs, vec![]);
+  assert_eq!(transform_errs, vec![]);
+  // the shared import is only loaded once
+  assert_eq!(project.files.len(), 3);
+  assert_eq!(file_name(&project, 2), "ltn.fhdl");
+  assert_eq!(modules.len(), 3);
+}
+
+#[test]
+pub fn project_load_errors() {
+  let dir = util_write_files("errors", &[
+    ("a.fhdl", "version 2; import \"b.fhdl\"; import \"missing.fhdl\";
+      module m(in single a) {}"),
+    ("b.fhdl", "version 2; import \"a.fhdl\";\nmodule m(in single a) { wire single w = x; }"),
+  ]);
+  let (project, errs) = Project::load(&dir.join("a.fhdl"), &[]);
+  let (_, transform_errs) = transform_project(&project, &collect_builtins());
+  let missing = Project::load(Path::new("missing.fhdl"), &[]).1;
+  std::fs::remove_dir_all(&dir).unwrap();
+
+  assert_eq!(errs.len(), 2);
+  let cycle = dir.join("a.fhdl -> ").display().to_string()
+    + &dir.join("b.fhdl -> ").display().to_string()
+    + &dir.join("a.fhdl").display().to_string();
+  assert_eq!(errs[0].cerr, Cerr::ImportCycle(cycle));
+  assert_eq!(file_name(&project, errs[0].file), "b.fhdl");
+  assert_eq!(errs[1].cerr, Cerr::ImportNotFound("missing.fhdl".into()));
+  assert_eq!(file_name(&project, errs[1].file), "a.fhdl");
+
+  // the redeclaration points back to the first module, and errors are reported in the right file
+  assert_eq!(transform_errs.len(), 2);
+  assert!(matches!(&transform_errs[0].cerr, Cerr::AlreadyDeclaredAt(name, at) if name == "m" && at.ends_with("a.fhdl:2:6")));
+  assert_eq!(transform_errs[1].cerr, Cerr::NotDeclared("x".into()));
+  let formatted = project.format_err(&transform_errs[1]);
+  assert!(formatted.contains("b.fhdl:2:"), "{}", formatted);
+  assert!(formatted.contains("wire single w = x;"), "{}", formatted);
+
+  assert!(matches!(&missing[..], [err] if matches!(err.cerr, Cerr::ReadFile(..))));
+}
+
+#[test]
+pub fn project_load_stdlib() {
+  let dir = util_write_files("stdlib", &[
+    ("main.fhdl", "version 2; import \"std/counter.fhdl\"; import \"std/clock.fhdl\";
+      module main(in single a, out single b) { inst clock_divider(a, b); }"),
+    // files on disk shadow the standard library
+    ("std/clock.fhdl", "version 2; module clock_divider(in single a, out single b) {}"),
+  ]);
+  let (project, errs) = Project::load(&dir.join("main.fhdl"), &[]);
+  let (modules, transform_errs) = transform_project(&project, &collect_builtins());
+  std::fs::remove_dir_all(&dir).unwrap();
+  assert_eq!(errs, vec![]);
+  assert_eq!(transform_errs, vec![]);
+  assert_eq!(project.files[1].path, Path::new("<stdlib>/counter.fhdl"));
+  assert_eq!(project.files[2].path, dir.join("std/clock.fhdl"));
+  assert!(modules.iter().any(|v| v.name == "updown_counter"));
+}
@@ -0,0 +1,73 @@
+use crate::err::Cerr;
+use crate::parse::ast::{Module, NetType, RomData, RomEntry, Stmt};
+use crate::parse::rom::{load_rom_files, parse_csv, parse_json, parse_rom_file};
+use crate::parse::span::Span;
+
+#[test]
+pub fn rom_parse_csv() {
+  let entries = parse_csv("1\n -2 \n\n3\n", NetType::Single).unwrap();
+  assert_eq!(entries, vec![RomEntry::Single(1), RomEntry::Single(-2), RomEntry::Single(3)]);
+  let entries = parse_csv("item=iron-plate, virtual=signal-A\n1, 2\n,-3\n", NetType::Mixed).unwrap();
+  let expected = vec![
+    RomEntry::Mixed(vec![("item=iron-plate".into(), 1), ("virtual=signal-A".into(), 2)]),
+    RomEntry::Mixed(vec![("virtual=signal-A".into(), -3)]),
+  ];
+  assert_eq!(entries, expected);
+  assert!(parse_csv("1\nx\n", NetType::Single).is_err());
+  assert!(parse_csv("item=iron-plate\n1, 2\n", NetType::Mixed).is_err());
+}
+
+#[test]
+pub fn rom_parse_json() {
+  let entries = parse_json(" [1, -2,\n 3] ", NetType::Single).unwrap();
+  assert_eq!(entries, vec![RomEntry::Single(1), RomEntry::Single(-2), RomEntry::Single(3)]);
+  let entries = parse_json(r#"[{"item=iron-plate": 1, "virtual=signal-A": 2}, {}]"#, NetType::Mixed).unwrap();
+  let expected = vec![
+    RomEntry::Mixed(vec![("item=iron-plate".into(), 1), ("virtual=signal-A".into(), 2)]),
+    RomEntry::Mixed(vec![]),
+  ];
+  assert_eq!(entries, expected);
+  assert!(parse_json("[1.5]", NetType::Single).is_err());
+  assert!(parse_json("[3000000000]", NetType::Single).is_err());
+  assert!(parse_json("[{}]", NetType::Single).is_err());
+  assert!(parse_json(r#"[{"item=iron-plate": "1"}]"#, NetType::Mixed).is_err());
+  assert!(parse_rom_file("rom.txt", "[1]", NetType::Single).is_err());
+}
+
+#[test]
+pub fn rom_load_files() {
+  let dir = std::env::temp_dir().join(format!("fhdl_rom_test_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  std::fs::write(dir.join("sizes.json"), "[1, 2]").unwrap();
+  let ds = Span::default();
+  let rom = |name: &str, path: &str| {
+    (
+      Stmt::RomDecl {
+        name: name.into(),
+        signal_class: NetType::Single,
+        data: RomData::File(path.into()),
+      },
+      ds,
+    )
+  };
+  let mut modules = vec![(
+    Module {
+      name: "m".into(),
+      ports: vec![],
+      stmts: vec![rom("a", "sizes.json"), rom("b", "missing.csv")],
+    },
+    ds,
+  )];
+  let errs = load_rom_files(&mut modules, &dir);
+  std::fs::remove_dir_all(&dir).unwrap();
+  assert_eq!(errs.len(), 1);
+  assert!(matches!(&errs[0].cerr, Cerr::RomFile(path, _) if path == "missing.csv"));
+  assert_eq!(
+    modules[0].0.stmts[0].0,
+    Stmt::RomDecl {
+      name: "a".into(),
+      signal_class: NetType::Single,
+      data: RomData::Inline(vec![RomEntry::Single(1), RomEntry::Single(2)]),
+    }
+  );
+}
@@ -0,0 +1,192 @@
+use crate::parse::ast::Program;
+use crate::parse::stdlib;
+use crate::parse::tokenizer::tokenize;
+use crate::parse::tokenstream::TokenStream;
+use crate::synth::builtins::collect_builtins;
+use crate::synth::combinator::Signal;
+use crate::synth::netlist::Netlist;
+use crate::synth::sim::{SignalValues, Simulator};
+use crate::synth::transform::transform_program;
+use crate::test::synth::sim::util_sim_synth;
+use crate::util::ResultExt;
+
+/// Synthesizes a module of a standard library file, along with any extra modules for testing it.
+fn util_stdlib(file: &str, extra: &str, main: &str) -> Netlist {
+  let path = stdlib::resolve(&format!("std/{}", file)).unwrap();
+  util_sim_synth(&format!("{}\n{}", stdlib::source(&path).unwrap(), extra), main)
+}
+
+fn item(name: &str, count: i32) -> SignalValues {
+  signals(&[(&format!("item={}", name), count)])
+}
+
+fn signals(values: &[(&str, i32)]) -> SignalValues {
+  values.iter().map(|(s, v)| (Signal::parse(s).unwrap(), *v)).collect()
+}
+
+/// Steps the simulation once per value, driving a single port with the value and recording
+/// a single output port after each tick.
+fn util_trace(sim: &mut Simulator, input: usize, output: usize, values: &[i32]) -> Vec<i32> {
+  values
+    .iter()
+    .map(|v| {
+      sim.set_single(input, *v);
+      sim.step();
+      sim.read_single(output)
+    })
+    .collect()
+}
+
+#[test]
+pub fn stdlib_files() {
+  for import in stdlib::imports() {
+    let path = stdlib::resolve(&import).unwrap();
+    let src = stdlib::source(&path).unwrap();
+    let tokens = tokenize(src.chars()).collect::<Result<Vec<_>, _>>().pretty_unwrap();
+    let program = Program::parse(&TokenStream::from_tokens(tokens).begin()).pretty_unwrap();
+    assert_eq!(transform_program(&program, &collect_builtins()).1, vec![], "{}", import);
+  }
+  assert_eq!(stdlib::resolve("std/missing.fhdl"), None);
+  assert_eq!(stdlib::resolve("clock.fhdl"), None);
+}
+
+#[test]
+pub fn stdlib_clock_divider() {
+  let netlist = util_stdlib("clock.fhdl", "", "clock_divider");
+  let mut sim = Simulator::new(&netlist);
+  assert_eq!(util_trace(&mut sim, 0, 1, &[3; 9]), [0, 1, 0, 0, 1, 0, 0, 1, 0]);
+  assert_eq!(util_trace(&mut sim, 0, 1, &[0; 4]), [0; 4]);
+  sim.set_single(0, 1);
+  sim.run(2);
+  assert_eq!(util_trace(&mut sim, 0, 1, &[1; 4]), [1; 4]);
+}
+
+#[test]
+pub fn stdlib_sr_latch() {
+  let netlist = util_stdlib("latch.fhdl", "", "sr_latch");
+  let mut sim = Simulator::new(&netlist);
+  // a single tick on `s` sets the latch after 4 ticks, and it stays set
+  assert_eq!(util_trace(&mut sim, 0, 2, &[0, 1, 0, 0, 0, 0, 0, 0]), [0, 0, 0, 0, 0, 1, 1, 1]);
+  assert_eq!(util_trace(&mut sim, 1, 2, &[1, 0, 0, 0, 0]), [1, 1, 0, 0, 0]);
+  // reset wins while both are nonzero, and setting resumes once reset is released
+  sim.set_single(0, 1);
+  assert_eq!(util_trace(&mut sim, 1, 2, &[1, 1, 1, 1, 1, 1]), [0; 6]);
+  assert_eq!(util_trace(&mut sim, 1, 2, &[0, 0, 0, 0, 0]), [0, 0, 0, 0, 1]);
+}
+
+#[test]
+pub fn stdlib_updown_counter() {
+  let netlist = util_stdlib("counter.fhdl", "", "updown_counter");
+  let mut sim = Simulator::new(&netlist);
+  assert_eq!(util_trace(&mut sim, 0, 3, &[1, 1, 1, 0, 5, 0, 0]), [0, 0, 1, 2, 3, 3, 8]);
+  assert_eq!(util_trace(&mut sim, 1, 3, &[2, 0, 0]), [8, 8, 6]);
+  // counting down goes below zero
+  assert_eq!(util_trace(&mut sim, 1, 3, &[10, 0, 0]), [6, 6, -4]);
+  assert_eq!(util_trace(&mut sim, 2, 3, &[1, 0, 0, 0]), [-4, -4, 0, 0]);
+}
+
+#[test]
+pub fn stdlib_pulse_extender() {
+  let netlist = util_stdlib("pulse.fhdl", "", "pulse_extender");
+  let mut sim = Simulator::new(&netlist);
+  sim.set_single(1, 5);
+  // let the length settle first
+  sim.run(4);
+  let start = [0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+  assert_eq!(util_trace(&mut sim, 0, 2, &start), [0, 0, 0, 1, 1, 1, 1, 1, 0, 0]);
+  // holding the input doesn't extend the pulse
+  assert_eq!(util_trace(&mut sim, 0, 2, &[1; 10]), [0, 0, 1, 1, 1, 1, 1, 0, 0, 0]);
+  // rising again while the pulse is going restarts it
+  sim.set_single(1, 3);
+  let start = [0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0];
+  assert_eq!(util_trace(&mut sim, 0, 2, &start), [0, 0, 0, 0, 1, 1, 1, 1, 1, 0, 0]);
+}
+
+#[test]
+pub fn stdlib_tdm() {
+  let extra = "module tdm_test(in single channels, in mixed a, in mixed b, out mixed ra, out mixed rb) {
+    wire mixed bus;
+    inst tdm_controller(channels, bus);
+    inst tdm_transmit(1, a, bus);
+    inst tdm_transmit(2, b, bus);
+    inst tdm_receive(1, bus, ra);
+    inst tdm_receive(2, bus, rb);
+  }";
+  let netlist = util_stdlib("tdm.fhdl", extra, "tdm_test");
+  for channels in [3, 5, 8] {
+    let mut sim = Simulator::new(&netlist);
+    sim.set_single(0, channels);
+    sim.set_input(1, item("iron-plate", 5));
+    sim.set_input(2, item("copper-plate", 7));
+    sim.run(20);
+    assert_eq!(sim.read(3), item("iron-plate", 5), "{} channels", channels);
+    assert_eq!(sim.read(4), item("copper-plate", 7), "{} channels", channels);
+    // receivers hold the last value until their channel comes around again
+    sim.set_input(1, item("iron-plate", 9));
+    sim.step();
+    assert_eq!(sim.read(3), item("iron-plate", 5), "{} channels", channels);
+    sim.run(20);
+    assert_eq!(sim.read(3), item("iron-plate", 9), "{} channels", channels);
+    assert_eq!(sim.read(4), item("copper-plate", 7), "{} channels", channels);
+  }
+}
+
+#[test]
+pub fn stdlib_tdm_transmit() {
+  // a bus driven by hand, following the RFC: grey selects the channel active on the next tick
+  let netlist = util_stdlib("tdm.fhdl", "", "tdm_transmit");
+  let mut sim = Simulator::new(&netlist);
+  sim.set_single(0, 4);
+  sim.set_input(1, item("iron-plate", 5));
+  sim.run(4);
+  let trace = [0, 4, 5, 4, 4, 0].map(|select| {
+    // another node's data is on the bus as well
+    sim.set_input(2, signals(&[("virtual=signal-grey", select), ("item=coal", 3)]));
+    sim.step();
+    sim.read(2).get(&Signal::parse("item=iron-plate").unwrap()).copied().unwrap_or(0)
+  });
+  assert_eq!(trace, [0, 5, 0, 5, 5, 0]);
+  // nothing on the bus is echoed back
+  assert_eq!(sim.read(2), item("coal", 3));
+}
+
+#[test]
+pub fn stdlib_tdm_receive() {
+  let netlist = util_stdlib("tdm.fhdl", "", "tdm_receive");
+  let mut sim = Simulator::new(&netlist);
+  sim.set_single(0, 4);
+  // channel 4 is selected on the second tick, and its data is on the bus on the third
+  let bus = [
+    signals(&[("virtual=signal-grey", 2), ("item=coal", 1)]),
+    signals(&[("virtual=signal-grey", 4), ("item=coal", 2)]),
+    signals(&[("virtual=signal-grey", 1), ("item=coal", 3)]),
+    signals(&[("virtual=signal-grey", 2), ("item=coal", 4)]),
+  ];
+  bus.into_iter().for_each(|values| {
+    sim.set_input(1, values);
+    sim.step();
+  });
+  sim.run(6);
+  assert_eq!(sim.read(2), item("coal", 3));
+}
+
+#[test]
+pub fn stdlib_qm() {
+  let extra = "module qm_test(in mixed a, in mixed b, out mixed ra, out mixed rb, out mixed rc) {
+    wire mixed bus;
+    wire mixed none;
+    wire mixed uncommon;
+    wire mixed legendary;
+    inst qm_transmit(a, none, b, none, none, bus);
+    inst qm_receive(bus, ra, uncommon, rb, rc, legendary);
+  }";
+  let netlist = util_stdlib("qm.fhdl", extra, "qm_test");
+  let mut sim = Simulator::new(&netlist);
+  sim.set_input(0, signals(&[("item=iron-plate", 5), ("item=iron-plate@epic", 2)]));
+  sim.set_input(1, signals(&[("item=iron-plate", 7), ("virtual=signal-grey", 1)]));
+  sim.run(3);
+  // quality is dropped from the data of a channel
+  assert_eq!(sim.read(2), item("iron-plate", 7));
+  assert_eq!(sim.read(3), signals(&[("item=iron-plate", 7), ("virtual=signal-grey", 1)]));
+  assert_eq!(sim.read(4), SignalValues::new());
+}
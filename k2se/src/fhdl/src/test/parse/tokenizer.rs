@@ -49,15 +49,19 @@ pub fn tokenize_valid2() {
     .pretty_unwrap();
   let expected = vec![
     Token::Name("z".into()),
+    Token::Comment(" boo".into()),
     Token::Op(BinaryOp::Eq),
     Token::LParen,
     Token::Name("abs".into()),
+    Token::Comment("".into()),
     Token::LParen,
+    Token::Comment(" comment ".into()),
     Token::Name("re".into()),
     Token::LParen,
     Token::Name("z".into()),
     Token::RParen,
     Token::RParen,
+    Token::Comment("".into()),
     Token::Op(BinaryOp::Add),
     Token::Name("i".into()),
     Token::Op(BinaryOp::Mul),
@@ -75,9 +79,11 @@ pub fn tokenize_valid2() {
     Token::Name("c".into()),
     Token::Semicolon,
     Token::Name("string".into()),
+    Token::Comment("".into()),
     Token::Op(BinaryOp::Assign),
     Token::String("b//\nn".into()),
     Token::Semicolon,
+    Token::Comment(" foo".into()),
   ];
   assert_eq!(tokens, expected)
 }
@@ -89,8 +95,10 @@ pub fn tokenize_valid3() {
   let expected = vec![
     Token::Name("module".into()),
     Token::Name("foo".into()),
+    Token::Comment("".into()),
     Token::LParen,
     Token::Name("inout".into()),
+    Token::Comment("".into()),
     Token::Name("single".into()),
     Token::Name("troll".into()),
     Token::Comma,
@@ -98,6 +106,7 @@ pub fn tokenize_valid3() {
     Token::Name("mixed".into()),
     Token::Name("troll2".into()),
     Token::RParen,
+    Token::Comment("".into()),
     Token::LBrace,
     Token::Name("wire".into()),
     Token::Name("b".into()),
@@ -120,9 +129,24 @@ pub fn tokenize_valid3() {
   assert_eq!(tokens, expected);
 }
 
+#[test]
+pub fn tokenize_valid_logical() {
+  let tokens = util_tokenize("a&&b || c&d").pretty_unwrap();
+  let expected = vec![
+    Token::Name("a".into()),
+    Token::Op(BinaryOp::LAnd),
+    Token::Name("b".into()),
+    Token::Op(BinaryOp::LOr),
+    Token::Name("c".into()),
+    Token::Op(BinaryOp::And),
+    Token::Name("d".into()),
+  ];
+  assert_eq!(tokens, expected);
+}
+
 #[test]
 pub fn tokenize_invalid1() {
-  let err = util_tokenize("//\n[3]");
+  let err = util_tokenize("//\n#3");
   assert_eq!(
     err,
     Err(CerrSpan {
@@ -130,6 +154,7 @@ pub fn tokenize_invalid1() {
         start: Pos::new(2, 0),
         end: Pos::new(2, 0)
       }),
+      file: 0,
       cerr: Cerr::InvalidChar,
     })
   );
@@ -144,6 +169,7 @@ pub fn tokenize_invalid2() {
         start: Pos { line: 1, col: 3 },
         end: Pos { line: 1, col: 7 },
       }),
+    file: 0,
     cerr: Cerr::InvalidInteger(parse_int_err),
   }) = err
   else {
@@ -162,6 +188,7 @@ pub fn tokenize_invalid3() {
         start: Pos::new(1, 2),
         end: Pos::new(1, 3)
       }),
+      file: 0,
       cerr: Cerr::InvalidOperator,
     })
   )
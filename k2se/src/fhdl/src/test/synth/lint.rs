@@ -0,0 +1,142 @@
+use crate::err::Lint;
+use crate::parse::ast::Program;
+use crate::parse::stdlib;
+use crate::parse::tokenizer::{tokenize, BinaryOp};
+use crate::parse::tokenstream::TokenStream;
+use crate::synth::builtins::collect_builtins;
+use crate::synth::lint::{lint_modules, lint_netlist};
+use crate::synth::synth::SingleMixedStrategy;
+use crate::synth::transform::transform_program;
+use crate::test::synth::synth::util_synth;
+use crate::util::ResultExt;
+
+/// Lints a program, returning each lint along with the line it was found on.
+fn util_lint(src: &str, strategy: SingleMixedStrategy) -> Vec<(u32, Lint)> {
+  let tokens = tokenize(src.chars()).collect::<Result<Vec<_>, _>>().pretty_unwrap();
+  let program = Program::parse(&TokenStream::from_tokens(tokens).begin()).pretty_unwrap();
+  let (modules, errs) = transform_program(&program, &collect_builtins());
+  assert_eq!(errs, vec![]);
+  lint_modules(&modules, strategy)
+    .into_iter()
+    .map(|v| (v.span.unwrap().start.line, v.lint))
+    .collect()
+}
+
+#[test]
+pub fn lint_wires() {
+  let src = "version 2;
+    module m(in single a, out single b) {
+      wire single unused = a;
+      wire single unwritten;
+      wire single ok = unwritten + a;
+      mem single never;
+      mem single counter;
+      trigger a increasing { set counter += 1; };
+      set b = ok + never + counter;
+    }";
+  let lints = util_lint(src, SingleMixedStrategy::Clean);
+  assert_eq!(lints, vec![
+    (3, Lint::UnusedWire("unused".into())),
+    (4, Lint::UnwrittenWire("unwritten".into())),
+    (6, Lint::UnwrittenMem("never".into())),
+  ]);
+}
+
+#[test]
+pub fn lint_module_inst_ports() {
+  // outputs of submodules count as writes, and inputs as reads
+  let src = "version 2;
+    module m(in single a, out single b) {
+      wire single from_sub;
+      wire single to_sub = a;
+      inst sub(to_sub, from_sub);
+      set b = from_sub;
+    }
+    module sub(in single a, out single b) { set b = a; }";
+  assert_eq!(util_lint(src, SingleMixedStrategy::Clean), vec![]);
+}
+
+#[test]
+pub fn lint_constant_trigger() {
+  let src = "version 2;
+    module m(in single a, out single b) {
+      wire single en = 1 + 2;
+      wire single on = en * 3;
+      mem single x;
+      mem single y;
+      mem single z;
+      mem single w;
+      trigger on raw { set x = a; };
+      trigger 1 increasing { set y = a; };
+      trigger a raw { set z = a; trigger 0 raw { set w = 1; }; };
+      wire single rst = 0;
+      mem single v reset rst;
+      set v += a;
+      set b = x + y + z + w + v;
+    }";
+  let lints = util_lint(src, SingleMixedStrategy::Clean);
+  // edge triggers on constants fire once on startup, nested triggers
+  // are combined with their outer trigger, and resets aren't triggers
+  assert_eq!(lints, vec![(9, Lint::ConstantTrigger)]);
+}
+
+#[test]
+pub fn lint_shift_overflow() {
+  let src = "version 2;
+    module m(in single a, out single b, out single c) {
+      set b = a << 32;
+      set c = (a >> 31) + (a >> 40);
+    }";
+  let lints = util_lint(src, SingleMixedStrategy::Clean);
+  assert_eq!(lints, vec![(3, Lint::ShiftOverflow(32)), (4, Lint::ShiftOverflow(40))]);
+}
+
+#[test]
+pub fn lint_dirty_op() {
+  let src = "version 2;
+    module m(in mixed a, in single b, out mixed c, out mixed d, out mixed e, out mixed f) {
+      set c = a + b;
+      set d = a * 2;
+      set e = b - a;
+      set f = a < b;
+    }";
+  assert_eq!(util_lint(src, SingleMixedStrategy::Clean), vec![]);
+  // `b - b` and `b < b` leave nothing on b's signal
+  assert_eq!(util_lint(src, SingleMixedStrategy::Dirty), vec![(3, Lint::DirtyOp(BinaryOp::Add))]);
+}
+
+#[test]
+pub fn lint_stdlib_and_examples() {
+  let mut sources = stdlib::imports()
+    .map(|v| stdlib::source(&stdlib::resolve(&v).unwrap()).unwrap().to_owned())
+    .collect::<Vec<_>>();
+  sources.push(include_str!("../../../examples/counter.fhdl").to_owned());
+  for src in sources {
+    assert_eq!(util_lint(&src, SingleMixedStrategy::Clean), vec![], "{}", src);
+  }
+}
+
+#[test]
+pub fn lint_latency_mismatch() {
+  let src = "version 2;
+    module m(in single a, in single b, in single c, out single d, out single e) {
+      set d = a + b * c;
+      set e += a;
+      set e += b * c * 2;
+    }";
+  let lints = lint_netlist(&util_synth(src, 5, SingleMixedStrategy::Clean))
+    .into_iter()
+    .map(|v| (v.span.unwrap().start.line, v.lint))
+    .collect::<Vec<_>>();
+  assert_eq!(lints, vec![(3, Lint::LatencyMismatch(0, 1)), (5, Lint::LatencyMismatch(1, 2))]);
+  let src = "version 2; module m(in single a, out single d) { mem single x; set d = x + a; }";
+  assert_eq!(lint_netlist(&util_synth(src, 2, SingleMixedStrategy::Clean)), vec![]);
+  // edge detectors compare their input with its delayed self on purpose
+  let src = "version 2; module m(in single a, out single d, out single e) {
+      mem single x = 1;
+      trigger a increasing once { set x += 1; };
+      set d = x;
+      set e = rising_pulse(a);
+    }";
+  assert_eq!(lint_netlist(&util_synth(src, 3, SingleMixedStrategy::Clean)), vec![]);
+}
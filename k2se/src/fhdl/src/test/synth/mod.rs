@@ -1 +1,5 @@
+mod lint;
+pub mod sim;
+mod synth;
+mod timing;
 mod transform;
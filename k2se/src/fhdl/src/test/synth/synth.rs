@@ -0,0 +1,281 @@
+use crate::err::Cerr;
+use crate::parse::ast::Program;
+use crate::parse::tokenizer::tokenize;
+use crate::parse::tokenstream::TokenStream;
+use crate::synth::builtins::collect_builtins;
+use crate::synth::ir::IRModule;
+use crate::synth::combinator::{Combinator, Quality, Signal, SignalRef, SignalType};
+use crate::synth::netlist::Netlist;
+use crate::synth::synth::{synthesize, SingleMixedStrategy, SynthSettings};
+use crate::synth::transform::transform_program;
+use crate::util::ResultExt;
+
+pub fn util_synth(src: &str, ports: usize, single_mixed_strategy: SingleMixedStrategy) -> Netlist {
+  util_synth_with(src, ports, single_mixed_strategy, false)
+}
+
+pub fn util_synth_with(src: &str, ports: usize, single_mixed_strategy: SingleMixedStrategy, balance_latency: bool) -> Netlist {
+  let (modules, settings) = util_synth_input(src, ports, single_mixed_strategy, balance_latency);
+  synthesize(&settings, &modules, &collect_builtins()).unwrap()
+}
+
+/// Transforms a program, and sets up synthesis of its first module.
+fn util_synth_input(
+  src: &str,
+  ports: usize,
+  single_mixed_strategy: SingleMixedStrategy,
+  balance_latency: bool,
+) -> (Vec<IRModule>, SynthSettings) {
+  let tokens = tokenize(src.chars())
+    .collect::<Result<Vec<_>, _>>()
+    .pretty_unwrap();
+  let token_stream = TokenStream::from_tokens(tokens);
+  let program = Program::parse(&token_stream.begin()).pretty_unwrap();
+  let (modules, errs) = transform_program(&program, &collect_builtins());
+  assert_eq!(errs, vec![]);
+  let settings = SynthSettings {
+    main: program.modules[0].0.name.clone(),
+    main_module_conn_names: vec![[' '; 4]; ports],
+    main_module_conn_signals: vec![
+      Signal {
+        ty: SignalType::Virtual,
+        name: "signal-X".into(),
+        quality: Quality::Normal,
+      };
+      ports
+    ],
+    single_mixed_strategy,
+    balance_latency,
+  };
+  (modules, settings)
+}
+
+#[test]
+pub fn synth_single_mixed_literal() {
+  let src = "version 2; module m(in mixed a, out mixed c) { set c = a * 3; }";
+  let netlist = util_synth(src, 2, SingleMixedStrategy::Clean);
+  assert_eq!(netlist.combinators.len(), 1);
+}
+
+#[test]
+pub fn synth_reduce() {
+  let src = "version 2; module m(in mixed a, out TY c) { set c = FUNC(a); }";
+  // the search has 31 stages of 4 combinators, plus 5 more per stage to track the value
+  let expected = [
+    ("sum", "single", 1),
+    ("count", "single", 1),
+    ("argmax", "mixed", 1 + 31 * 4),
+    ("argmin", "mixed", 2 + 31 * 4),
+    ("max", "single", 1 + 31 * 9 - 2 + 3),
+    ("min", "single", 2 + 31 * 9 - 2 + 3),
+  ];
+  for (func, ty, count) in expected {
+    let netlist = util_synth(&src.replace("FUNC", func).replace("TY", ty), 2, Default::default());
+    assert_eq!(netlist.combinators.len(), count, "func {}", func);
+  }
+}
+
+#[test]
+pub fn synth_delay_reg() {
+  let expected = [
+    ("in single a, out single c", "delay(a, 3)", 3),
+    ("in mixed a, out mixed c", "delay(a, 2)", 2),
+    ("in single a, in single e, out single c", "reg(a, e)", 2),
+    ("in mixed a, in single e, out mixed c", "reg(a, e)", 7),
+  ];
+  for (ports, expr, count) in expected {
+    let src = format!("version 2; module m({}) {{ set c = {}; }}", ports, expr);
+    let netlist = util_synth(&src, ports.split(',').count(), Default::default());
+    assert_eq!(netlist.combinators.len(), count, "{}", expr);
+  }
+}
+
+#[test]
+pub fn synth_edge_detector() {
+  let expected = [("trig_inc", 2), ("rising_pulse", 3), ("falling_pulse", 3)];
+  for (func, count) in expected {
+    let src = format!("version 2; module m(in single a, out single c) {{ set c = {}(a); }}", func);
+    let netlist = util_synth(&src, 2, Default::default());
+    assert_eq!(netlist.combinators.len(), count, "{}", func);
+    // the comparison must read the delayed input, which is written by the passthrough
+    let compare = netlist.combinators.iter()
+      .find_map(|v| match v {
+        Combinator::Vanilla(comb) if comb.input_nets.iter().all(Option::is_some) => Some(comb),
+        _ => None,
+      })
+      .unwrap();
+    let delayed = compare.input_nets[0].unwrap();
+    assert_eq!(netlist.nets[delayed].out_conn.len(), 1, "{}", func);
+  }
+}
+
+#[test]
+pub fn synth_trigger_expr() {
+  let src = "version 2; module m(in single a, in single b, out single c) {
+    mem single counter;
+    trigger (a > 5) raw { trigger b raw { set counter += 1; }; };
+    set c = counter;
+  }";
+  let netlist = util_synth(src, 3, Default::default());
+  // memory cell, condition, its `== 0`, two normalizers, constant, trigger gate and output passthrough
+  assert_eq!(netlist.combinators.len(), 8);
+}
+
+#[test]
+pub fn synth_mem_init_reset() {
+  let expected = [
+    ("mem single m;", 1),
+    // reset normalizer and gated feedback
+    ("mem single m reset r;", 2),
+    // power on constant, pulse, initial value, load gate and gated feedback
+    ("mem single m = 5;", 7),
+    ("mem single m = 5 reset r;", 8),
+  ];
+  for (decl, count) in expected {
+    let src = format!("version 2; module m(in single r) {{ {} }}", decl);
+    let netlist = util_synth(&src, 1, Default::default());
+    assert_eq!(netlist.combinators.len(), count, "{}", decl);
+  }
+}
+
+#[test]
+pub fn synth_mem_array() {
+  // every slot has its own signal in the index table
+  let netlist = util_synth(
+    "version 2; module m(in single addr, out single q) { mem single buf[64]; set q = buf[addr]; }",
+    2,
+    Default::default(),
+  );
+  let slots = netlist
+    .combinators
+    .iter()
+    .filter_map(|v| match v {
+      Combinator::Constant(comb) => Some(comb.output_signals.len()),
+      _ => None,
+    })
+    .sum::<usize>();
+  assert_eq!(slots, 64);
+}
+
+#[test]
+pub fn synth_rom() {
+  let expected = [
+    // one constant per entry
+    ("rom single r = [1, 2, 3];", 3),
+    // selector, one gate per entry and the value extractor
+    ("rom single r = [1, 2, 3]; set q = r[i];", 8),
+    ("rom mixed r = [{\"item=iron-plate\" = 1}, {}]; set m = r[i];", 5),
+    ("rom mixed r = [{\"item=iron-plate\" = 1}, {}]; set m = r[1];", 5),
+  ];
+  for (stmt, count) in expected {
+    let src = format!(
+      "version 2; module m(in single i, out single q, out mixed m) {{ {} }}",
+      stmt
+    );
+    let netlist = util_synth(&src, 3, Default::default());
+    assert_eq!(netlist.combinators.len(), count, "{}", stmt);
+  }
+  // the select signal is one that no entry uses
+  let netlist = util_synth(
+    "version 2; module m(in single i, out mixed m) { rom mixed r = [{\"virtual=signal-A\" = 1}]; set m = r[i]; }",
+    2,
+    Default::default(),
+  );
+  let select = netlist
+    .combinators
+    .iter()
+    .find_map(|v| match v {
+      Combinator::Vanilla(comb) if comb.output_count => Some(comb.input_signals[0].clone()),
+      _ => None,
+    })
+    .unwrap();
+  assert_eq!(select, SignalRef::Signal(Signal::parse("virtual=signal-B").unwrap()));
+}
+
+#[test]
+pub fn synth_bundle() {
+  let src = "version 2;
+  bundle Pair { single a; mixed b; }
+  module m(in Pair p, out Pair q) { wire Pair w = p; inst swap(w, q); }
+  module swap(in Pair x, out Pair y) { set y.a = sum(x.b); set y.b = x.b * x.a; }";
+  // bundle ports are flattened into one port per member
+  let netlist = util_synth(src, 4, Default::default());
+  assert_eq!(netlist.net_external_conn.len(), 4);
+  // one passthrough per copied member, the sum and the cleaned multiplication
+  assert_eq!(netlist.combinators.len(), 6);
+}
+
+#[test]
+pub fn synth_origin() {
+  let src = "version 2;
+  module top(in single a, out single b) {
+    wire single w;
+    inst inner(a, w);
+    inst inner(w, b);
+  }
+  module inner(in single x, out single y) {
+    mem single m;
+    set m += x;
+    set y = m * 2 + 1;
+  }";
+  let netlist = util_synth(src, 2, Default::default());
+  // one net of each color per wire
+  assert_eq!(netlist.nets_at("top.a").count(), 2);
+  assert_eq!(netlist.nets_at("top.w").count(), 2);
+  assert_eq!(netlist.nets_at("top.inner1.m").count(), 2);
+  let m = netlist.nets_at("top.inner0.m").next().unwrap();
+  assert_eq!(netlist.nets[m].origin.span.unwrap().start.line, 8);
+  // the memory cell and the addition write to the memory
+  let writers = netlist
+    .combinators
+    .iter()
+    .filter(|v| v.origin().path == "top.inner1.m")
+    .map(|v| v.origin().span.unwrap().start.line)
+    .collect::<Vec<_>>();
+  assert_eq!(writers, [8, 9]);
+  assert!(netlist.combinators.iter().all(|v| v.origin().path.starts_with("top.inner")));
+  // the multiplication writes to an anonymous net
+  let mul = netlist.combinators.iter().find(|v| v.origin().path == "top.inner0").unwrap();
+  assert_eq!(mul.origin().to_string(), "top.inner0 (10:4)");
+}
+
+#[test]
+pub fn synth_errors() {
+  let src = "version 2;
+  module top(in single a, out single b) {
+    wire single w;
+    set w = a + 1;
+    inst inner(w, b);
+  }
+  module inner(in single x, out single y) {
+    set y = x;
+  }";
+  let (mut modules, mut settings) = util_synth_input(src, 2, Default::default(), false);
+  let builtins = collect_builtins();
+  // errors point at the statement they come from
+  let errs = {
+    let mut modules = modules.clone();
+    modules[0].stmts[0].dest = "missing".into();
+    modules.retain(|v| v.name != "inner");
+    synthesize(&settings, &modules, &builtins).unwrap_err()
+  };
+  let errs = errs.iter().map(|v| (v.cerr.clone(), v.span.unwrap().start.line)).collect::<Vec<_>>();
+  assert_eq!(errs, [(Cerr::NetNotFound("missing".into()), 4)]);
+  // inner modules are still synthesized if the outer one has no errors
+  modules[1].stmts[0].op = "missing".into();
+  let errs = synthesize(&settings, &modules, &builtins).unwrap_err();
+  assert_eq!(errs.len(), 1);
+  assert_eq!(errs[0].cerr, Cerr::UnknownFunction("missing".into()));
+  assert_eq!(errs[0].span.unwrap().start.line, 8);
+  modules.retain(|v| v.name != "inner");
+  let errs = synthesize(&settings, &modules, &builtins).unwrap_err();
+  assert_eq!(errs[0].cerr, Cerr::NotDeclared("inner".into()));
+  assert_eq!(errs[0].span.unwrap().start.line, 5);
+  // misconfigured main modules
+  settings.main_module_conn_signals.pop();
+  let errs = synthesize(&settings, &modules, &builtins).unwrap_err();
+  assert_eq!(errs[0].cerr, Cerr::MainPortCount("top".into(), 2, 1));
+  settings.main = "missing".into();
+  let errs = synthesize(&settings, &modules, &builtins).unwrap_err();
+  assert_eq!(errs[0].cerr, Cerr::MainNotFound("missing".into()));
+}
@@ -0,0 +1,45 @@
+use crate::synth::synth::SingleMixedStrategy;
+use crate::synth::timing::{analyze_latency, LatencyMismatch};
+use crate::test::synth::synth::{util_synth, util_synth_with};
+
+#[test]
+pub fn timing_combinator_inputs() {
+  let src = "version 2; module m(in single a, in single b, in single c, out single d) { set d = a + b * c; }";
+  let netlist = util_synth(src, 4, SingleMixedStrategy::Clean);
+  let report = analyze_latency(&netlist);
+  assert!(matches!(
+    report.mismatches.as_slice(),
+    [LatencyMismatch::CombinatorInputs { latencies: [0, 1], .. }]
+  ));
+  let balanced = util_synth_with(src, 4, SingleMixedStrategy::Clean, true);
+  assert_eq!(balanced.combinators.len(), netlist.combinators.len() + 1);
+  let report = analyze_latency(&balanced);
+  assert_eq!(report.mismatches, vec![]);
+  assert_eq!(report.net_latency[balanced.net_external_conn[3].red_net], Some(2));
+}
+
+#[test]
+pub fn timing_net_writers() {
+  let src = "version 2; module m(in single a, in single b, in single c, out single d) { set d += a; set d += b * c * 2; }";
+  let netlist = util_synth(src, 4, SingleMixedStrategy::Clean);
+  let report = analyze_latency(&netlist);
+  // the red and green nets of `d` are only reported once
+  assert!(matches!(
+    report.mismatches.as_slice(),
+    [LatencyMismatch::NetWriters { writers, .. }] if writers.len() == 2
+  ));
+  let balanced = util_synth_with(src, 4, SingleMixedStrategy::Clean, true);
+  assert_eq!(balanced.combinators.len(), netlist.combinators.len() + 1);
+  let report = analyze_latency(&balanced);
+  assert_eq!(report.mismatches, vec![]);
+  assert_eq!(report.net_latency[balanced.net_external_conn[3].green_net], Some(2));
+}
+
+#[test]
+pub fn timing_memory_cycle() {
+  let src = "version 2; module m(in single a, out single d) { mem single x; set d = x + a; }";
+  let netlist = util_synth(src, 2, SingleMixedStrategy::Clean);
+  let report = analyze_latency(&netlist);
+  assert_eq!(report.mismatches, vec![]);
+  assert_eq!(report.net_latency[netlist.net_external_conn[1].red_net], Some(1));
+}
@@ -1,10 +1,12 @@
-use crate::err::{Cerr, TypeError};
-use crate::parse::ast::{Expr, Module, NetType, PortClass, PortDecl, Stmt, TriggerKind};
+use crate::err::{Cerr, PortDiff, TypeError};
+use crate::parse::ast::{Bundle, BundleMember, Expr, Module, ModuleArgs, NetType, PortClass, PortDecl, Program, RomData, RomEntry, Stmt, TriggerKind, Version};
 use crate::parse::span::Span;
-use crate::parse::tokenizer::BinaryOp;
-use crate::synth::builtins::{BuiltinFunction, FunctionArgReq, SynthRef};
+use crate::parse::tokenizer::{tokenize, BinaryOp};
+use crate::parse::tokenstream::TokenStream;
+use crate::synth::builtins::{collect_builtins, BuiltinFunction, FunctionArgReq, SynthRef};
 use crate::synth::synth::{IncompleteNetID, ModuleSynthState};
-use crate::synth::transform::transform_modules;
+use crate::synth::ir::{IRStmt, IRValue};
+use crate::synth::transform::{transform_modules, transform_program};
 use std::collections::HashMap;
 use std::fmt::{Debug};
 use std::slice;
@@ -20,7 +22,7 @@ impl BuiltinFunction for TestFunction {
     slice::from_ref(&self.arg_ty)
   }
 
-  fn return_ty(&self) -> Option<NetType> {
+  fn return_ty(&self, _: &[Option<NetType>]) -> Option<NetType> {
     Some(NetType::Single)
   }
 
@@ -72,6 +74,7 @@ pub fn transform_err_undeclared() {
         port_class: PortClass::Out,
         signal_class: NetType::Single,
         name: "port".into(),
+        bundle: None,
       }],
       stmts: vec![
         (
@@ -81,8 +84,10 @@ pub fn transform_err_undeclared() {
             expr: Some(Expr::BinaryOps {
               car: Box::new(Expr::Identifier {
                 name: "undeclared1".into(),
+                span: ds,
               }),
-              cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2 })],
+              cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2, span: ds })],
+              span: ds,
             }),
           },
           ds,
@@ -93,14 +98,17 @@ pub fn transform_err_undeclared() {
             assign_type: BinaryOp::Assign,
             expr: Expr::Identifier {
               name: "undeclared2".into(),
+              span: ds,
             },
           },
           ds,
         ),
         (
           Stmt::Trigger {
-            watching: "undeclared3".into(),
+            watching: Expr::Identifier { name: "undeclared3".into(), span: ds },
             trigger_kind: TriggerKind::Increasing,
+            once: false,
+            else_statements: vec![],
             statements: vec![],
           },
           ds,
@@ -108,7 +116,7 @@ pub fn transform_err_undeclared() {
         (
           Stmt::ModuleInst {
             module: "undeclared4".into(),
-            args: vec![],
+            args: ModuleArgs::Positional(vec![]),
           },
           ds,
         ),
@@ -138,11 +146,13 @@ pub fn transform_err_multiple_decl() {
             port_class: PortClass::In,
             signal_class: NetType::Single,
             name: "multi2".into(),
+            bundle: None,
           },
           PortDecl {
             port_class: PortClass::In,
             signal_class: NetType::Single,
             name: "multi2".into(),
+            bundle: None,
           },
         ],
         stmts: vec![],
@@ -166,6 +176,8 @@ pub fn transform_err_multiple_decl() {
             Stmt::MemDecl {
               name: "multi4".into(),
               signal_class: NetType::Single,
+              init: None,
+              reset: None,
             },
             ds,
           ),
@@ -189,6 +201,8 @@ pub fn transform_err_multiple_decl() {
             Stmt::MemDecl {
               name: "multi5".into(),
               signal_class: NetType::Single,
+              init: None,
+              reset: None,
             },
             ds,
           ),
@@ -196,6 +210,8 @@ pub fn transform_err_multiple_decl() {
             Stmt::MemDecl {
               name: "multi5".into(),
               signal_class: NetType::Single,
+              init: None,
+              reset: None,
             },
             ds,
           ),
@@ -206,11 +222,11 @@ pub fn transform_err_multiple_decl() {
   ];
   let errs = transform_modules(&ast, &test_builtins()).1;
   let expected = vec![
-    Cerr::MultipleDeclarations("multi1".into()).with(ds),
-    Cerr::MultipleDeclarations("multi2".into()).with(ds),
-    Cerr::MultipleDeclarations("multi4".into()).with(ds),
-    Cerr::MultipleDeclarations("multi3".into()).with(ds),
-    Cerr::MultipleDeclarations("multi5".into()).with(ds),
+    Cerr::AlreadyDeclaredAt("multi1".into(), "1:0".into()).with(ds),
+    Cerr::MultipleDeclarations("multi2".into(), ds).with(ds),
+    Cerr::MultipleDeclarations("multi4".into(), ds).with(ds),
+    Cerr::MultipleDeclarations("multi3".into(), ds).with(ds),
+    Cerr::MultipleDeclarations("multi5".into(), ds).with(ds),
   ];
   assert_eq!(errs, expected);
 }
@@ -225,12 +241,13 @@ pub fn transform_err_writes_to_input() {
         port_class: PortClass::In,
         signal_class: NetType::Single,
         name: "inputter".into(),
+        bundle: None,
       }],
       stmts: vec![(
         Stmt::Set {
           name: "inputter".into(),
           assign_type: BinaryOp::AddAssign,
-          expr: Expr::Literal { val: 5 },
+          expr: Expr::Literal { val: 5, span: ds },
         },
         ds,
       )],
@@ -262,7 +279,7 @@ pub fn transform_err_multiple_excl_assign1() {
           Stmt::Set {
             name: "w1".into(),
             assign_type: BinaryOp::Assign,
-            expr: Expr::Literal { val: 2 },
+            expr: Expr::Literal { val: 2, span: ds },
           },
           ds,
         ),
@@ -270,7 +287,7 @@ pub fn transform_err_multiple_excl_assign1() {
           Stmt::Set {
             name: "w1".into(),
             assign_type: BinaryOp::Assign,
-            expr: Expr::Literal { val: 2 },
+            expr: Expr::Literal { val: 2, span: ds },
           },
           ds,
         ),
@@ -278,7 +295,7 @@ pub fn transform_err_multiple_excl_assign1() {
           Stmt::WireDecl {
             name: "w2".into(),
             signal_class: NetType::Single,
-            expr: Some(Expr::Literal { val: 4 }),
+            expr: Some(Expr::Literal { val: 4, span: ds }),
           },
           ds,
         ),
@@ -286,7 +303,7 @@ pub fn transform_err_multiple_excl_assign1() {
           Stmt::Set {
             name: "w2".into(),
             assign_type: BinaryOp::AddAssign,
-            expr: Expr::Literal { val: 4 },
+            expr: Expr::Literal { val: 4, span: ds },
           },
           ds,
         ),
@@ -313,6 +330,7 @@ pub fn transform_err_multiple_excl_assign2() {
           port_class: PortClass::Out,
           signal_class: NetType::Single,
           name: "writee".into(),
+          bundle: None,
         }],
         stmts: vec![],
       },
@@ -334,14 +352,14 @@ pub fn transform_err_multiple_excl_assign2() {
           (
             Stmt::ModuleInst {
               module: "excl_writes".into(),
-              args: vec![Expr::Identifier { name: "w1".into() }],
+              args: ModuleArgs::Positional(vec![Expr::Identifier { name: "w1".into(), span: ds }]),
             },
             ds,
           ),
           (
             Stmt::ModuleInst {
               module: "excl_writes".into(),
-              args: vec![Expr::Identifier { name: "w1".into() }],
+              args: ModuleArgs::Positional(vec![Expr::Identifier { name: "w1".into(), span: ds }]),
             },
             ds,
           ),
@@ -356,25 +374,56 @@ pub fn transform_err_multiple_excl_assign2() {
 }
 
 #[test]
-pub fn transform_err_nested_trigger() {
+pub fn transform_nested_trigger() {
   let ds = Span::default();
   let ast = vec![(
     Module {
-      name: "invalid_module".into(),
-      ports: vec![PortDecl {
-        port_class: PortClass::In,
-        signal_class: NetType::Single,
-        name: "w1".into(),
-      }],
+      name: "nested".into(),
+      ports: vec![
+        PortDecl {
+          port_class: PortClass::In,
+          signal_class: NetType::Single,
+          name: "w1".into(),
+          bundle: None,
+        },
+        PortDecl {
+          port_class: PortClass::In,
+          signal_class: NetType::Single,
+          name: "w2".into(),
+          bundle: None,
+        },
+      ],
       stmts: vec![(
         Stmt::Trigger {
-          watching: "w1".into(),
+          watching: Expr::Identifier { name: "w1".into(), span: ds },
           trigger_kind: TriggerKind::Raw,
+          once: false,
+          else_statements: vec![],
           statements: vec![(
             Stmt::Trigger {
-              watching: "w1".into(),
+              watching: Expr::Identifier { name: "w2".into(), span: ds },
               trigger_kind: TriggerKind::Raw,
-              statements: vec![],
+              once: false,
+              else_statements: vec![],
+              statements: vec![
+                (
+                  Stmt::MemDecl {
+                    name: "m".into(),
+                    signal_class: NetType::Single,
+                    init: None,
+                    reset: None,
+                  },
+                  ds,
+                ),
+                (
+                  Stmt::Set {
+                    name: "m".into(),
+                    assign_type: BinaryOp::AddAssign,
+                    expr: Expr::Literal { val: 1, span: ds },
+                  },
+                  ds,
+                ),
+              ],
             },
             ds,
           )],
@@ -384,9 +433,30 @@ pub fn transform_err_nested_trigger() {
     },
     ds,
   )];
-  let errs = transform_modules(&ast, &test_builtins()).1;
-  let expected = vec![Cerr::NestedTriggerBlocks.with(ds)];
-  assert_eq!(errs, expected);
+  let (ir, errs) = transform_modules(&ast, &test_builtins());
+  assert_eq!(errs, vec![]);
+  let expected = vec![
+    IRStmt {
+      dest: "$anon_0".into(),
+      op: "$op_ne".into(),
+      args: vec![IRValue::Net("w1".into()), IRValue::Lit(0)],
+      span: Some(ds),
+    },
+    IRStmt {
+      dest: "$anon_0".into(),
+      op: "$op_ne".into(),
+      args: vec![IRValue::Net("w2".into()), IRValue::Lit(0)],
+      span: Some(ds),
+    },
+    IRStmt {
+      dest: "$anon_1".into(),
+      op: "$passthrough".into(),
+      args: vec![IRValue::Lit(1)],
+      span: Some(ds),
+    },
+  ];
+  assert_eq!(ir[0].stmts, expected);
+  assert_eq!(ir[0].trigger_stmt[0].on, "$anon_0");
 }
 
 #[test]
@@ -401,6 +471,8 @@ pub fn transform_err_bare_mem_assign() {
           Stmt::MemDecl {
             name: "reg".into(),
             signal_class: NetType::Single,
+            init: None,
+            reset: None,
           },
           ds,
         ),
@@ -408,7 +480,7 @@ pub fn transform_err_bare_mem_assign() {
           Stmt::Set {
             name: "reg".into(),
             assign_type: BinaryOp::Assign,
-            expr: Expr::Literal { val: 9 },
+            expr: Expr::Literal { val: 9, span: ds },
           },
           ds,
         ),
@@ -433,11 +505,13 @@ pub fn transform_err_arity_mismatch() {
             port_class: PortClass::In,
             signal_class: NetType::Single,
             name: "p1".into(),
+            bundle: None,
           },
           PortDecl {
             port_class: PortClass::Out,
             signal_class: NetType::Single,
             name: "p2".into(),
+            bundle: None,
           },
         ],
         stmts: vec![],
@@ -460,11 +534,11 @@ pub fn transform_err_arity_mismatch() {
           (
             Stmt::ModuleInst {
               module: "target_module".into(),
-              args: vec![
-                Expr::Identifier { name: "w1".into() },
-                Expr::Identifier { name: "w1".into() },
-                Expr::Identifier { name: "w1".into() },
-              ],
+              args: ModuleArgs::Positional(vec![
+                Expr::Identifier { name: "w1".into(), span: ds },
+                Expr::Identifier { name: "w1".into(), span: ds },
+                Expr::Identifier { name: "w1".into(), span: ds },
+              ]),
             },
             ds,
           ),
@@ -490,11 +564,13 @@ pub fn transform_err_expr_for_out_inout() {
             port_class: PortClass::InOut,
             signal_class: NetType::Single,
             name: "p1".into(),
+            bundle: None,
           },
           PortDecl {
             port_class: PortClass::Out,
             signal_class: NetType::Single,
             name: "p2".into(),
+            bundle: None,
           },
         ],
         stmts: vec![],
@@ -508,16 +584,18 @@ pub fn transform_err_expr_for_out_inout() {
         stmts: vec![(
           Stmt::ModuleInst {
             module: "target_module".into(),
-            args: vec![
+            args: ModuleArgs::Positional(vec![
               Expr::BinaryOps {
-                car: Box::new(Expr::Literal { val: 2 }),
-                cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2 })],
+                car: Box::new(Expr::Literal { val: 2, span: ds }),
+                cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2, span: ds })],
+                span: ds,
               },
               Expr::BinaryOps {
-                car: Box::new(Expr::Literal { val: 2 }),
-                cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2 })],
+                car: Box::new(Expr::Literal { val: 2, span: ds }),
+                cdr: vec![(BinaryOp::Add, Expr::Literal { val: 2, span: ds })],
+                span: ds,
               },
-            ],
+            ]),
           },
           ds,
         )],
@@ -544,7 +622,7 @@ pub fn transform_err_assign_type_error1() {
         Stmt::WireDecl {
           name: "w1".into(),
           signal_class: NetType::Mixed,
-          expr: Some(Expr::Literal { val: 46 }),
+          expr: Some(Expr::Literal { val: 46, span: ds }),
         },
         ds,
       )],
@@ -580,7 +658,7 @@ pub fn transform_err_assign_type_error2() {
           Stmt::WireDecl {
             name: "w2".into(),
             signal_class: NetType::Single,
-            expr: Some(Expr::Identifier { name: "w1".into() }),
+            expr: Some(Expr::Identifier { name: "w1".into(), span: ds }),
           },
           ds,
         ),
@@ -635,7 +713,8 @@ pub fn transform_err_arg_type_error() {
             assign_type: BinaryOp::AddAssign,
             expr: Expr::FnCall {
               func: "test1".into(),
-              args: vec![Expr::Identifier { name: "w2".into() }],
+              args: vec![Expr::Identifier { name: "w2".into(), span: ds }],
+              span: ds,
             },
           },
           ds,
@@ -646,7 +725,8 @@ pub fn transform_err_arg_type_error() {
             assign_type: BinaryOp::AddAssign,
             expr: Expr::FnCall {
               func: "test1".into(),
-              args: vec![Expr::Literal { val: 1 }],
+              args: vec![Expr::Literal { val: 1, span: ds }],
+              span: ds,
             },
           },
           ds,
@@ -657,7 +737,8 @@ pub fn transform_err_arg_type_error() {
             assign_type: BinaryOp::AddAssign,
             expr: Expr::FnCall {
               func: "test2".into(),
-              args: vec![Expr::Identifier { name: "w1".into() }],
+              args: vec![Expr::Identifier { name: "w1".into(), span: ds }],
+              span: ds,
             },
           },
           ds,
@@ -668,7 +749,8 @@ pub fn transform_err_arg_type_error() {
             assign_type: BinaryOp::AddAssign,
             expr: Expr::FnCall {
               func: "test2".into(),
-              args: vec![Expr::Literal { val: 1 }],
+              args: vec![Expr::Literal { val: 1, span: ds }],
+              span: ds,
             },
           },
           ds,
@@ -679,7 +761,8 @@ pub fn transform_err_arg_type_error() {
             assign_type: BinaryOp::AddAssign,
             expr: Expr::FnCall {
               func: "test3".into(),
-              args: vec![Expr::Identifier { name: "w2".into() }],
+              args: vec![Expr::Identifier { name: "w2".into(), span: ds }],
+              span: ds,
             },
           },
           ds,
@@ -753,6 +836,7 @@ pub fn transform_err_unknown_func() {
           expr: Some(Expr::FnCall {
             func: "not_exist".into(),
             args: vec![],
+            span: ds,
           }),
         },
         ds,
@@ -778,7 +862,8 @@ pub fn transform_err_wrong_number_of_args() {
           signal_class: NetType::Single,
           expr: Some(Expr::FnCall {
             func: "test3".into(),
-            args: vec![Expr::Literal { val: 4 }, Expr::Literal { val: 4 }],
+            args: vec![Expr::Literal { val: 4, span: ds }, Expr::Literal { val: 4, span: ds }],
+            span: ds,
           }),
         },
         ds,
@@ -813,7 +898,8 @@ pub fn transform_err_expected_string() {
             signal_class: NetType::Single,
             expr: Some(Expr::FnCall {
               func: "test4".into(),
-              args: vec![Expr::Literal { val: 1 }],
+              args: vec![Expr::Literal { val: 1, span: ds }],
+              span: ds,
             }),
           },
           ds,
@@ -838,6 +924,7 @@ pub fn transform_err_unexpected_string() {
           port_class: PortClass::In,
           signal_class: NetType::Single,
           name: "port".into(),
+          bundle: None,
         }],
         stmts: vec![],
       },
@@ -868,7 +955,7 @@ pub fn transform_err_unexpected_string() {
             Stmt::Set {
               name: "dst".into(),
               assign_type: BinaryOp::AddAssign,
-              expr: Expr::StringLiteral { str: "s".into() },
+              expr: Expr::StringLiteral { str: "s".into(), span: ds },
             },
             ds,
           ),
@@ -878,7 +965,8 @@ pub fn transform_err_unexpected_string() {
               assign_type: BinaryOp::AddAssign,
               expr: Expr::FnCall {
                 func: "test3".into(),
-                args: vec![Expr::StringLiteral { str: "s".into() }],
+                args: vec![Expr::StringLiteral { str: "s".into(), span: ds }],
+                span: ds,
               },
             },
             ds,
@@ -886,7 +974,7 @@ pub fn transform_err_unexpected_string() {
           (
             Stmt::ModuleInst {
               module: "no_strings".into(),
-              args: vec![Expr::StringLiteral { str: "s".into() }],
+              args: ModuleArgs::Positional(vec![Expr::StringLiteral { str: "s".into(), span: ds }]),
             },
             ds,
           ),
@@ -903,3 +991,959 @@ pub fn transform_err_unexpected_string() {
   ];
   assert_eq!(errs, expected);
 }
+
+#[test]
+pub fn transform_err_select_branch_mismatch() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "invalid_module".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "w1".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w2".into(),
+            signal_class: NetType::Mixed,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "dst".into(),
+            signal_class: NetType::Mixed,
+            expr: Some(Expr::FnCall {
+              func: "select".into(),
+              args: vec![
+                Expr::Identifier { name: "w1".into(), span: ds },
+                Expr::Identifier { name: "w2".into(), span: ds },
+                Expr::Literal { val: 1, span: ds },
+              ],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let errs = transform_modules(&ast, &collect_builtins()).1;
+  let expected = vec![Cerr::TypeErrArgMismatch(
+    2,
+    "select".into(),
+    TypeError {
+      src_ty: "Single".into(),
+      dst_ty: "Mixed".into(),
+    },
+  )
+  .with(ds)];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_logical_op_folding() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "logical".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "a".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "b".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "x".into(),
+            signal_class: NetType::Single,
+            expr: Some(Expr::BinaryOps {
+              car: Box::new(Expr::BinaryOps {
+                car: Box::new(Expr::Identifier { name: "a".into(), span: ds }),
+                cdr: vec![(BinaryOp::Gt, Expr::Literal { val: 1, span: ds })],
+                span: ds,
+              }),
+              cdr: vec![(BinaryOp::LAnd, Expr::Identifier { name: "b".into(), span: ds })],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let (ir, errs) = transform_modules(&ast, &collect_builtins());
+  assert_eq!(errs, vec![]);
+  let expected = vec![
+    IRStmt {
+      dest: "$anon_1".into(),
+      op: "$op_gt".into(),
+      args: vec![IRValue::Net("a".into()), IRValue::Lit(1)],
+      span: Some(ds),
+    },
+    IRStmt {
+      dest: "$anon_1".into(),
+      op: "$op_ne".into(),
+      args: vec![IRValue::Net("b".into()), IRValue::Lit(0)],
+      span: Some(ds),
+    },
+    IRStmt {
+      dest: "x".into(),
+      op: "$op_eq".into(),
+      args: vec![IRValue::Net("$anon_1".into()), IRValue::Lit(2)],
+      span: Some(ds),
+    },
+  ];
+  assert_eq!(ir[0].stmts, expected);
+  assert!(!ir[0].objects.contains_key("$anon_0"));
+}
+
+#[test]
+pub fn transform_err_logical_op_on_mixed() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "invalid_module".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "m".into(),
+            signal_class: NetType::Mixed,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "x".into(),
+            signal_class: NetType::Single,
+            expr: Some(Expr::BinaryOps {
+              car: Box::new(Expr::Identifier { name: "m".into(), span: ds }),
+              cdr: vec![(BinaryOp::LOr, Expr::Literal { val: 1, span: ds })],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let errs = transform_modules(&ast, &collect_builtins()).1;
+  let expected = vec![Cerr::LogicalOpOnMixedNet(BinaryOp::LOr).with(ds)];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_err_invalid_signal() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "invalid_module".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "m".into(),
+            signal_class: NetType::Mixed,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w1".into(),
+            signal_class: NetType::Single,
+            expr: Some(Expr::FnCall {
+              func: "extract".into(),
+              args: vec![
+                Expr::Identifier { name: "m".into(), span: ds },
+                Expr::StringLiteral {
+                  str: "iron-plate".into(),
+                  span: ds,
+                },
+              ],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w2".into(),
+            signal_class: NetType::Mixed,
+            expr: Some(Expr::FnCall {
+              func: "exclude".into(),
+              args: vec![
+                Expr::Identifier { name: "m".into(), span: ds },
+                Expr::Literal { val: 3, span: ds },
+              ],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w3".into(),
+            signal_class: NetType::Mixed,
+            expr: Some(Expr::FnCall {
+              func: "exclude".into(),
+              args: vec![
+                Expr::Identifier { name: "m".into(), span: ds },
+                Expr::StringLiteral {
+                  str: "virtual=signal-A".into(),
+                  span: ds,
+                },
+              ],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w4".into(),
+            signal_class: NetType::Mixed,
+            expr: Some(Expr::FnCall {
+              func: "exclude".into(),
+              args: vec![
+                Expr::Identifier { name: "m".into(), span: ds },
+                Expr::StringLiteral {
+                  str: "virtual=signal-A@shiny".into(),
+                  span: ds,
+                },
+              ],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w5".into(),
+            signal_class: NetType::Mixed,
+            expr: Some(Expr::FnCall {
+              func: "set_quality".into(),
+              args: vec![
+                Expr::Identifier { name: "m".into(), span: ds },
+                Expr::StringLiteral {
+                  str: "shiny".into(),
+                  span: ds,
+                },
+              ],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w6".into(),
+            signal_class: NetType::Mixed,
+            expr: Some(Expr::FnCall {
+              func: "filter_quality".into(),
+              args: vec![
+                Expr::Identifier { name: "m".into(), span: ds },
+                Expr::StringLiteral {
+                  str: "rare".into(),
+                  span: ds,
+                },
+              ],
+              span: ds,
+            }),
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let errs = transform_modules(&ast, &collect_builtins()).1;
+  let expected = vec![
+    Cerr::InvalidSignal(1, "extract".into(), "iron-plate".into()).with(ds),
+    Cerr::ExpectedString(1, "exclude".into()).with(ds),
+    Cerr::InvalidSignal(1, "exclude".into(), "virtual=signal-A@shiny".into()).with(ds),
+    Cerr::InvalidQuality(1, "set_quality".into(), "shiny".into()).with(ds),
+  ];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_delay_folding() {
+  let ds = Span::default();
+  let delay_zero = |expr| Expr::FnCall {
+    func: "delay".into(),
+    args: vec![expr, Expr::Literal { val: 0, span: ds }],
+    span: ds,
+  };
+  let ast = vec![(
+    Module {
+      name: "delay".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "a".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "x".into(),
+            signal_class: NetType::Single,
+            expr: Some(delay_zero(Expr::BinaryOps {
+              car: Box::new(Expr::Identifier { name: "a".into(), span: ds }),
+              cdr: vec![(BinaryOp::Add, Expr::Literal { val: 1, span: ds })],
+              span: ds,
+            })),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "y".into(),
+            signal_class: NetType::Single,
+            expr: Some(delay_zero(Expr::Identifier { name: "a".into(), span: ds })),
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let (ir, errs) = transform_modules(&ast, &collect_builtins());
+  assert_eq!(errs, vec![]);
+  let expected = vec![
+    IRStmt {
+      dest: "x".into(),
+      op: "$op_add".into(),
+      args: vec![IRValue::Net("a".into()), IRValue::Lit(1)],
+      span: Some(ds),
+    },
+    IRStmt {
+      dest: "y".into(),
+      op: "$passthrough".into(),
+      args: vec![IRValue::Net("a".into())],
+      span: Some(ds),
+    },
+  ];
+  assert_eq!(ir[0].stmts, expected);
+}
+
+#[test]
+pub fn transform_err_delay_count() {
+  let ds = Span::default();
+  let delay = |ticks| Expr::FnCall {
+    func: "delay".into(),
+    args: vec![Expr::Identifier { name: "a".into(), span: ds }, ticks],
+    span: ds,
+  };
+  let ast = vec![(
+    Module {
+      name: "invalid_module".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "a".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "x".into(),
+            signal_class: NetType::Single,
+            expr: Some(delay(Expr::Identifier { name: "a".into(), span: ds })),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "y".into(),
+            signal_class: NetType::Single,
+            expr: Some(delay(Expr::Literal { val: -1, span: ds })),
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let errs = transform_modules(&ast, &collect_builtins()).1;
+  let expected = vec![
+    Cerr::ExpectedCount(1, "delay".into()).with(ds),
+    Cerr::ExpectedCount(1, "delay".into()).with(ds),
+  ];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_trigger_once() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "once".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "a".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::MemDecl {
+            name: "m".into(),
+            signal_class: NetType::Single,
+            init: None,
+            reset: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::Trigger {
+            watching: Expr::Identifier { name: "a".into(), span: ds },
+            trigger_kind: TriggerKind::Raw,
+            once: true,
+            else_statements: vec![],
+            statements: vec![(
+              Stmt::Set {
+                name: "m".into(),
+                assign_type: BinaryOp::AddAssign,
+                expr: Expr::Literal { val: 1, span: ds },
+              },
+              ds,
+            )],
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let (ir, errs) = transform_modules(&ast, &collect_builtins());
+  assert_eq!(errs, vec![]);
+  let expected = vec![
+    IRStmt {
+      dest: "$anon_0".into(),
+      op: "$op_ne".into(),
+      args: vec![IRValue::Net("a".into()), IRValue::Lit(0)],
+      span: Some(ds),
+    },
+    IRStmt {
+      dest: "$anon_1".into(),
+      op: "trig_dec".into(),
+      args: vec![IRValue::Net("$anon_0".into())],
+      span: Some(ds),
+    },
+    IRStmt {
+      dest: "$anon_2".into(),
+      op: "$passthrough".into(),
+      args: vec![IRValue::Lit(1)],
+      span: Some(ds),
+    },
+  ];
+  assert_eq!(ir[0].stmts, expected);
+  assert_eq!(ir[0].trigger_stmt[0].on, "$anon_1");
+}
+
+fn trigger_else_module(then_writes: usize, else_writes: usize) -> Vec<(Module, Span)> {
+  let ds = Span::default();
+  let set_w = (
+    Stmt::Set {
+      name: "w".into(),
+      assign_type: BinaryOp::Assign,
+      expr: Expr::Literal { val: 1, span: ds },
+    },
+    ds,
+  );
+  vec![(
+    Module {
+      name: "trigger_else".into(),
+      ports: vec![PortDecl {
+        port_class: PortClass::In,
+        signal_class: NetType::Single,
+        name: "clk".into(),
+        bundle: None,
+      }],
+      stmts: vec![
+        (
+          Stmt::WireDecl {
+            name: "w".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::Trigger {
+            watching: Expr::Identifier { name: "clk".into(), span: ds },
+            trigger_kind: TriggerKind::Raw,
+            once: false,
+            statements: vec![set_w.clone(); then_writes],
+            else_statements: vec![set_w.clone(); else_writes],
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )]
+}
+
+#[test]
+pub fn transform_trigger_else() {
+  let (ir, errs) = transform_modules(&trigger_else_module(1, 1), &test_builtins());
+  assert_eq!(errs, vec![]);
+  let conditions = ir[0].stmts.iter().take(2).cloned().collect::<Vec<_>>();
+  let expected = vec![
+    IRStmt {
+      dest: "$anon_0".into(),
+      op: "$op_ne".into(),
+      args: vec![IRValue::Net("clk".into()), IRValue::Lit(0)],
+      span: Some(Span::default()),
+    },
+    IRStmt {
+      dest: "$anon_1".into(),
+      op: "$op_eq".into(),
+      args: vec![IRValue::Net("clk".into()), IRValue::Lit(0)],
+      span: Some(Span::default()),
+    },
+  ];
+  assert_eq!(conditions, expected);
+  let on = ir[0].trigger_stmt.iter().map(|v| v.on.as_str()).collect::<Vec<_>>();
+  assert_eq!(on, vec!["$anon_0", "$anon_1"]);
+}
+
+#[test]
+pub fn transform_err_trigger_else_multiple_writes() {
+  let errs = transform_modules(&trigger_else_module(1, 2), &test_builtins()).1;
+  let expected = vec![Cerr::MultipleExclusiveWrites.with(Span::default())];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_err_mem_init_reset() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "invalid_module".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::MemDecl {
+            name: "m1".into(),
+            signal_class: NetType::Mixed,
+            init: Some(5),
+            reset: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::MemDecl {
+            name: "m2".into(),
+            signal_class: NetType::Single,
+            init: None,
+            reset: Some("m1".into()),
+          },
+          ds,
+        ),
+        (
+          Stmt::MemDecl {
+            name: "m3".into(),
+            signal_class: NetType::Single,
+            init: None,
+            reset: Some("undeclared".into()),
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let errs = transform_modules(&ast, &test_builtins()).1;
+  let expected = vec![
+    Cerr::TypeErrorGeneric(TypeError {
+      src_ty: "Literal".into(),
+      dst_ty: "Mixed".into(),
+    })
+    .with(ds),
+    Cerr::TypeErrorGeneric(TypeError {
+      src_ty: "Mixed".into(),
+      dst_ty: "Single".into(),
+    })
+    .with(ds),
+    Cerr::NotDeclared("undeclared".into()).with(ds),
+  ];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_err_mem_array() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "invalid_module".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::MemArrayDecl {
+            name: "a".into(),
+            signal_class: NetType::Mixed,
+            len: 4,
+          },
+          ds,
+        ),
+        (
+          Stmt::MemArrayDecl {
+            name: "b".into(),
+            signal_class: NetType::Single,
+            len: 0,
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w".into(),
+            signal_class: NetType::Single,
+            expr: None,
+          },
+          ds,
+        ),
+        (
+          Stmt::SetIndexed {
+            name: "a".into(),
+            index: Expr::Literal { val: 1, span: ds },
+            assign_type: BinaryOp::Assign,
+            expr: Expr::Literal { val: 3, span: ds },
+          },
+          ds,
+        ),
+        (
+          Stmt::SetIndexed {
+            name: "w".into(),
+            index: Expr::Literal { val: 0, span: ds },
+            assign_type: BinaryOp::AddAssign,
+            expr: Expr::Literal { val: 1, span: ds },
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let errs = transform_modules(&ast, &test_builtins()).1;
+  let expected = vec![
+    Cerr::InvalidArrayLength(128).with(ds),
+    Cerr::TypeErrorGeneric(TypeError {
+      src_ty: "Mixed".into(),
+      dst_ty: "Single".into(),
+    })
+    .with(ds),
+    Cerr::MemAssignOutsideOfTrigger.with(ds),
+    Cerr::NotAnArray("w".into()).with(ds),
+  ];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_err_rom() {
+  let ds = Span::default();
+  let ast = vec![(
+    Module {
+      name: "invalid_module".into(),
+      ports: vec![],
+      stmts: vec![
+        (
+          Stmt::RomDecl {
+            name: "a".into(),
+            signal_class: NetType::Single,
+            data: RomData::Inline(vec![RomEntry::Single(1), RomEntry::Mixed(vec![])]),
+          },
+          ds,
+        ),
+        (
+          Stmt::RomDecl {
+            name: "b".into(),
+            signal_class: NetType::Mixed,
+            data: RomData::Inline(vec![RomEntry::Mixed(vec![("iron".into(), 1)])]),
+          },
+          ds,
+        ),
+        (
+          Stmt::WireDecl {
+            name: "w".into(),
+            signal_class: NetType::Single,
+            expr: Some(Expr::Identifier { name: "a".into(), span: ds }),
+          },
+          ds,
+        ),
+        (
+          Stmt::Set {
+            name: "a".into(),
+            assign_type: BinaryOp::AddAssign,
+            expr: Expr::Literal { val: 1, span: ds },
+          },
+          ds,
+        ),
+        (
+          Stmt::SetIndexed {
+            name: "a".into(),
+            index: Expr::Literal { val: 0, span: ds },
+            assign_type: BinaryOp::AddAssign,
+            expr: Expr::Literal { val: 1, span: ds },
+          },
+          ds,
+        ),
+      ],
+    },
+    ds,
+  )];
+  let errs = transform_modules(&ast, &test_builtins()).1;
+  let expected = vec![
+    Cerr::TypeErrorGeneric(TypeError {
+      src_ty: "Mixed".into(),
+      dst_ty: "Single".into(),
+    })
+    .with(ds),
+    Cerr::InvalidRomSignal("iron".into()).with(ds),
+    Cerr::RomWithoutIndex("a".into()).with(ds),
+    Cerr::WriteToRom.with(ds),
+    Cerr::WriteToRom.with(ds),
+  ];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_err_bundle() {
+  let ds = Span::default();
+  let bundle = |name: &str, signal_class: NetType| Bundle {
+    name: name.into(),
+    members: vec![BundleMember {
+      signal_class,
+      name: "item".into(),
+    }],
+  };
+  let port = |name: &str, bundle: Option<&str>| PortDecl {
+    port_class: PortClass::In,
+    signal_class: NetType::Single,
+    name: name.into(),
+    bundle: bundle.map(|v| v.into()),
+  };
+  let inst = |module: &str, arg: &str| Stmt::ModuleInst {
+    module: module.into(),
+    args: ModuleArgs::Positional(vec![Expr::Identifier { name: arg.into(), span: ds }]),
+  };
+  let program = Program {
+    version: Version::V2,
+    imports: vec![],
+    bundles: vec![(bundle("Req", NetType::Single), ds), (bundle("Resp", NetType::Mixed), ds)],
+    modules: vec![
+      (
+        Module {
+          name: "invalid_module".into(),
+          ports: vec![port("r", Some("Req")), port("q", None)],
+          stmts: vec![
+            (
+              Stmt::BundleWireDecl {
+                name: "w".into(),
+                bundle: "Resp".into(),
+                expr: Some(Expr::Identifier { name: "r".into(), span: ds }),
+              },
+              ds,
+            ),
+            (
+              Stmt::Set {
+                name: "r.item".into(),
+                assign_type: BinaryOp::AddAssign,
+                expr: Expr::Identifier { name: "r".into(), span: ds },
+              },
+              ds,
+            ),
+            (
+              Stmt::Set {
+                name: "w".into(),
+                assign_type: BinaryOp::AddAssign,
+                expr: Expr::Literal { val: 1, span: ds },
+              },
+              ds,
+            ),
+            (inst("takes_single", "r"), ds),
+            (inst("takes_bundle", "q"), ds),
+            (inst("takes_bundle", "r"), ds),
+          ],
+        },
+        ds,
+      ),
+      (
+        Module {
+          name: "takes_single".into(),
+          ports: vec![port("a", None)],
+          stmts: vec![],
+        },
+        ds,
+      ),
+      (
+        Module {
+          name: "takes_bundle".into(),
+          ports: vec![port("a", Some("Req")), port("b", Some("Missing"))],
+          stmts: vec![],
+        },
+        ds,
+      ),
+    ],
+  };
+  let errs = transform_program(&program, &test_builtins()).1;
+  let type_error = |src_ty: &str, dst_ty: &str| {
+    Cerr::TypeErrorGeneric(TypeError {
+      src_ty: src_ty.into(),
+      dst_ty: dst_ty.into(),
+    })
+    .with(ds)
+  };
+  let missing_b = Cerr::MissingPorts(PortDiff {
+    module: "takes_bundle".into(),
+    ports: vec![("a".into(), true), ("b".into(), false)],
+  })
+  .with(ds);
+  let expected = vec![
+    type_error("Req", "Resp"),
+    Cerr::WriteToInput.with(ds),
+    Cerr::BundleAsValue("r".into()).with(ds),
+    Cerr::BundleAsValue("w".into()).with(ds),
+    type_error("Req", "Single"),
+    missing_b.clone(),
+    type_error("Single", "Req"),
+    missing_b,
+    Cerr::NotDeclared("Missing".into()).with(ds),
+  ];
+  assert_eq!(errs, expected);
+}
+
+#[test]
+pub fn transform_err_named_ports() {
+  let ds = Span::default();
+  let port = |name: &str| PortDecl {
+    port_class: PortClass::In,
+    signal_class: NetType::Single,
+    name: name.into(),
+    bundle: None,
+  };
+  let inst = |args: Vec<(&str, i32)>| {
+    let args = args
+      .into_iter()
+      .map(|(name, val)| (name.into(), Expr::Literal { val, span: ds }))
+      .collect();
+    (
+      Stmt::ModuleInst {
+        module: "counter".into(),
+        args: ModuleArgs::Named(args),
+      },
+      ds,
+    )
+  };
+  let ast = vec![
+    (
+      Module {
+        name: "invalid_module".into(),
+        ports: vec![],
+        stmts: vec![
+          inst(vec![("output", 1), ("reset", 2), ("clk", 3)]),
+          inst(vec![("reset", 1), ("rst", 2), ("reset", 3), ("clk", 4)]),
+          inst(vec![("clk", 1)]),
+        ],
+      },
+      ds,
+    ),
+    (
+      Module {
+        name: "counter".into(),
+        ports: vec![port("clk"), port("reset"), port("output")],
+        stmts: vec![],
+      },
+      ds,
+    ),
+  ];
+  let errs = transform_modules(&ast, &test_builtins()).1;
+  let missing = Cerr::MissingPorts(PortDiff {
+    module: "counter".into(),
+    ports: vec![("clk".into(), true), ("reset".into(), false), ("output".into(), false)],
+  });
+  let expected = vec![
+    Cerr::UnknownPort("counter".into(), "rst".into()).with(ds),
+    Cerr::DuplicatePortConnection("reset".into()).with(ds),
+    Cerr::MissingPorts(PortDiff {
+      module: "counter".into(),
+      ports: vec![("clk".into(), true), ("reset".into(), true), ("output".into(), false)],
+    })
+    .with(ds),
+    missing.clone().with(ds),
+  ];
+  assert_eq!(errs, expected);
+  assert_eq!(
+    missing.to_string(),
+    "Missing connections to ports of module 'counter':\n    clk\n  - reset\n  - output"
+  );
+}
+
+
+#[test]
+pub fn transform_err_expr_spans() {
+  let src = "version 2;
+module m(in single s, in mixed a, in mixed b, out mixed o) {
+  set o = a + sum(s) + a * b + nope(s);
+}";
+  let tokens = tokenize(src.chars()).collect::<Result<Vec<_>, _>>().unwrap();
+  let program = Program::parse(&TokenStream::from_tokens(tokens).begin()).unwrap();
+  let errs = transform_program(&program, &collect_builtins()).1;
+  // errors underline the operand they're about, rather than the statement
+  let lines = src.lines().collect::<Vec<_>>();
+  let underlined = errs
+    .iter()
+    .map(|v| {
+      let span = v.span.unwrap();
+      assert_eq!(span.start.line, span.end.line);
+      (v.cerr.clone(), &lines[span.start.line as usize - 1][span.start.col as usize..=span.end.col as usize])
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(underlined, vec![
+    (
+      Cerr::TypeErrArgMismatch(0, "sum".into(), TypeError {
+        src_ty: "Single".into(),
+        dst_ty: "Mixed".into(),
+      }),
+      "s",
+    ),
+    (Cerr::InvalidOpOnMixedNets(BinaryOp::Mul), "a * b"),
+    (Cerr::UnknownFunction("nope".into()), "nope(s)"),
+  ]);
+}
@@ -15,7 +15,7 @@ pub fn imp_iter_take_while() {
   let elements = iter.imp_take_while(|v| *v > 0);
   assert_eq!(elements, vec![1, 1, 2, 3, 5]);
   let elements = iter.imp_take_while(|v| *v > 0);
-  assert_eq!(elements, vec![]);
+  assert_eq!(elements, Vec::<i32>::new());
   assert_eq!(iter.next(), Some(0));
   let elements = iter.imp_take_while(|v| *v > 0);
   assert_eq!(elements, vec![7, 8, 9]);